@@ -1,49 +1,190 @@
 //! Combining incoming connections and their consumers, ran in spawn threads.
 
-use std::fmt::{Debug, Display};
+use std::fmt::Display;
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 
 use nix::sys::socket::{setsockopt, sockopt};
 use rand::distributions::{Alphanumeric, DistString};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::task::JoinSet;
 use tokio_rustls::server::TlsStream;
 use tokio_rustls::TlsAcceptor;
-use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
-use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::handshake::machine::TryParse;
+use tokio_tungstenite::tungstenite::handshake::server::{self, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::{Role, WebSocketConfig};
+use tokio_tungstenite::{tungstenite, WebSocketStream};
+
+use crate::authentication::{authenticate_session, AuthenticationError, SESSION_COOKIE};
+use crate::sessions::{SessionHandle, SessionStream};
 
 /// Number of consecutive accept failures at which it is considered an error.
 const MAX_FAILURES: u32 = 3;
 
+/// Maximum size in bytes of the WebSocket upgrade request, which carries the cookies of the website.
+const MAX_REQUEST_SIZE: usize = 8 << 10;
+
+/// Maximum durations given to a client to complete each stage of the connection establishment.
+#[derive(Copy, Clone)]
+pub struct HandshakeTimeouts {
+    /// Time allowed for the TLS handshake, counted from the TCP connection acceptance.
+    pub tls: Duration,
+    /// Time allowed for the WebSocket upgrade, counted from the TLS handshake completion.
+    pub websocket: Duration,
+}
+
+/// Checks done on the WebSocket upgrade request, before accepting it.
+#[derive(Clone)]
+pub struct UpgradeChecks {
+    /// Values of the `Origin` header allowed to open a connection. Any origin is allowed if empty.
+    pub allowed_origins: Arc<[String]>,
+    /// If set, the upgrade request must carry the cookie of a website session, looked up in this database. The
+    /// connection is then authenticated as the session's player.
+    pub session_db: Option<Arc<tokio_postgres::Client>>,
+}
+
+impl UpgradeChecks {
+    /// Check the upgrade request, returning the username of the player signed in with the website session it carries,
+    /// if sessions are required.
+    ///
+    /// A foreign origin is rejected with a 403 response, a missing or invalid session with a 401 response, and a failed
+    /// session lookup with a 500 response.
+    async fn check<D: Display>(
+        &self,
+        request: &Request,
+        id: &D,
+    ) -> Result<Option<String>, StatusCode> {
+        if !self.allowed_origins.is_empty() {
+            let origin = request
+                .headers()
+                .get(header::ORIGIN)
+                .and_then(|origin| origin.to_str().ok());
+            if !origin.is_some_and(|origin| self.allowed_origins.iter().any(|o| o == origin)) {
+                log::info!("{id}: Rejecting an upgrade request from origin {origin:?}.");
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+        let Some(db_client) = &self.session_db else {
+            return Ok(None);
+        };
+        let Some(session_key) = find_cookie(request, SESSION_COOKIE) else {
+            log::info!("{id}: Rejecting an upgrade request without a session cookie.");
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        match authenticate_session(db_client, session_key).await {
+            Ok(username) => Ok(Some(username)),
+            Err(AuthenticationError::InvalidSession) => {
+                log::info!("{id}: Rejecting an upgrade request with an invalid session.");
+                Err(StatusCode::UNAUTHORIZED)
+            }
+            Err(e) => {
+                log::error!("{id}: Failed to look up the session of an upgrade request : {e}.");
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+/// Find the value of the named cookie in the request headers.
+fn find_cookie<'r>(request: &'r Request, name: &str) -> Option<&'r str> {
+    request
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find_map(|(cookie_name, value)| (cookie_name == name).then_some(value))
+}
+
+/// Make the HTTP response rejecting an upgrade request.
+fn error_response(status: StatusCode) -> Response {
+    let mut response = Response::new(());
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(0));
+    response
+}
+
+/// Errors encountered while upgrading an accepted TCP connection to a WebSocket connection.
+#[derive(thiserror::Error, Debug)]
+enum HandshakeError {
+    /// This error happens when the TLS handshake fails.
+    #[error("Failed to upgrade the connection to Tls : {0}")]
+    Tls(std::io::Error),
+
+    /// This error happens when the client doesn't complete the TLS handshake in time.
+    #[error("The Tls handshake didn't complete within {0:?}")]
+    TlsTimeout(Duration),
+
+    /// This error happens when the WebSocket upgrade fails.
+    #[error("Failed to upgrade the Tls connection to websocket : {0}")]
+    WebSocket(tokio_tungstenite::tungstenite::Error),
+
+    /// This error happens when the upgrade request is larger than [`MAX_REQUEST_SIZE`].
+    #[error("The upgrade request is larger than {MAX_REQUEST_SIZE} bytes")]
+    RequestTooLarge,
+
+    /// This error happens when the upgrade request is answered with an HTTP error response.
+    #[error("The upgrade request was rejected with a {0} response")]
+    Rejected(StatusCode),
+
+    /// This error happens when the client doesn't complete the WebSocket upgrade in time.
+    #[error("The websocket upgrade didn't complete within {0:?}")]
+    WebSocketTimeout(Duration),
+}
+
 /// Dispatcher of incoming connections to asynchronous tokio tasks created on-the-fly.
 pub struct OnAcceptGenerator {
     listener: TcpListener,
+    handshake_timeouts: HandshakeTimeouts,
+    upgrade_checks: UpgradeChecks,
     consecutive_accept_fail_count: u32,
 }
 
 impl OnAcceptGenerator {
     /// Create a new [`OnAcceptGenerator`], which will assign connections incoming on the given [`TcpListener`] to new
-    /// tasks.
-    pub fn new(tcp_listener: TcpListener) -> OnAcceptGenerator {
+    /// tasks. The handshakes happening on those connections are bounded by the given [`HandshakeTimeouts`], and the
+    /// upgrade requests must pass the given [`UpgradeChecks`].
+    pub fn new(
+        tcp_listener: TcpListener,
+        handshake_timeouts: HandshakeTimeouts,
+        upgrade_checks: UpgradeChecks,
+    ) -> OnAcceptGenerator {
         OnAcceptGenerator {
             listener: tcp_listener,
+            handshake_timeouts,
+            upgrade_checks,
             consecutive_accept_fail_count: 0,
         }
     }
 
-    /// Await for an incoming tcp connection, upgrade it to a websocket connection, and pass it to the given task,
-    /// spawned on a [`tokio::task`].
+    /// Await for an incoming tcp connection, and spawn a [`tokio::task`] that upgrades it to a websocket connection
+    /// before passing it to the given task, along with the username of the player signed in with the website session
+    /// of the upgrade request, if sessions are required, and the handle to register its session with.
+    ///
+    /// The handshakes run in the spawned task, so that a slow or malicious client can't hold the accept loop. Only
+    /// failures to accept the TCP connection count towards [`MAX_FAILURES`], handshake failures are the client's.
     pub async fn generate_next_task<F, T>(
         &mut self,
         tls_acceptor: &TlsAcceptor,
-        task_set: &mut JoinSet<F::Output>,
+        task_set: &mut JoinSet<()>,
         task_to_spawn: T,
     ) -> Result<(), ()>
     where
-        F: Future + Send + 'static,
-        F::Output: Send + 'static + Debug,
-        T: FnOnce(WebSocketStream<TlsStream<TcpStream>>, String) -> F,
+        F: Future<Output = ()> + Send + 'static,
+        T: FnOnce(
+                WebSocketStream<SessionStream<TlsStream<TcpStream>>>,
+                String,
+                Option<String>,
+                SessionHandle,
+            ) -> F
+            + Send
+            + 'static,
     {
         let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 8);
 
@@ -51,28 +192,29 @@ impl OnAcceptGenerator {
             Ok(stream) => stream,
             Err(e) => return self.handle_tcp_accept_error(&id, e),
         };
+        self.consecutive_accept_fail_count = 0;
 
-        log::trace!("Accepted a TCP connection with {id}. Trying to upgrade it to Tls...");
-        let tls_stream = match tls_acceptor.accept(stream).await {
-            Ok(tls_stream) => tls_stream,
-            Err(e) => {
-                log::warn!("{id}: Failed to upgrade a connection to Tls : {e}.");
-                return Ok(());
-            }
-        };
-
-        log::trace!("Accepted a Tls connection with {id}. Trying to upgrade it to WSS...");
-        let websocket = match ws_accept(tls_stream).await {
-            Ok(websocket) => websocket,
-            Err(e) => {
-                log::info!("Failed to upgrade the Tls connection to websocket with error : {e}.");
-                return Ok(());
+        log::trace!("Accepted a TCP connection with {id}. Spawning a task to handle it.");
+        let tls_acceptor = tls_acceptor.clone();
+        let handshake_timeouts = self.handshake_timeouts;
+        let upgrade_checks = self.upgrade_checks.clone();
+        task_set.spawn(async move {
+            match upgrade_connection(
+                stream,
+                &tls_acceptor,
+                handshake_timeouts,
+                &upgrade_checks,
+                &id,
+            )
+            .await
+            {
+                Ok((websocket, session_user, session)) => {
+                    log::info!("{id}: Established a websocket connection.");
+                    task_to_spawn(websocket, id, session_user, session).await;
+                }
+                Err(e) => log::info!("{id}: {e}."),
             }
-        };
-
-        log::info!("Established a websocket connection with {id}. Spawning a task to handle it.");
-        task_set.spawn(task_to_spawn(websocket, id));
-        self.consecutive_accept_fail_count = 0;
+        });
 
         Ok(())
     }
@@ -104,12 +246,100 @@ async fn tcp_accept_with_opts(listener: &mut TcpListener) -> std::io::Result<Tcp
     Ok(stream)
 }
 
-/// Upgrade the stream connection to a WebSocket connection. The configuration supplied sets small buffers.
-async fn ws_accept<S>(
-    tcp_stream: S,
-) -> Result<WebSocketStream<S>, tokio_tungstenite::tungstenite::Error>
+/// Upgrade the TCP connection to Tls then to WebSocket, each step being bounded by its timeout. Returns the username of
+/// the player signed in with the website session of the upgrade request, if sessions are required, and the handle of
+/// the connection's session.
+async fn upgrade_connection<D: Display>(
+    stream: TcpStream,
+    tls_acceptor: &TlsAcceptor,
+    handshake_timeouts: HandshakeTimeouts,
+    upgrade_checks: &UpgradeChecks,
+    id: &D,
+) -> Result<
+    (
+        WebSocketStream<SessionStream<TlsStream<TcpStream>>>,
+        Option<String>,
+        SessionHandle,
+    ),
+    HandshakeError,
+> {
+    log::trace!("{id}: Trying to upgrade the connection to Tls...");
+    let tls_stream = tokio::time::timeout(handshake_timeouts.tls, tls_acceptor.accept(stream))
+        .await
+        .map_err(|_| HandshakeError::TlsTimeout(handshake_timeouts.tls))?
+        .map_err(HandshakeError::Tls)?;
+    let (session_stream, session) = SessionStream::new(tls_stream);
+
+    log::trace!("{id}: Accepted a Tls connection. Trying to upgrade it to WSS...");
+    let (websocket, session_user) = tokio::time::timeout(
+        handshake_timeouts.websocket,
+        ws_accept(session_stream, upgrade_checks, id),
+    )
+    .await
+    .map_err(|_| HandshakeError::WebSocketTimeout(handshake_timeouts.websocket))??;
+    Ok((websocket, session_user, session))
+}
+
+/// Read the upgrade request from the stream, returning it along with the bytes the client sent after it.
+async fn read_request<S>(stream: &mut S) -> Result<(Request, Vec<u8>), HandshakeError>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 1 << 10];
+    loop {
+        let read = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| HandshakeError::WebSocket(e.into()))?;
+        if read == 0 {
+            return Err(HandshakeError::WebSocket(
+                tungstenite::Error::ConnectionClosed,
+            ));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some((size, request)) =
+            Request::try_parse(&buffer).map_err(HandshakeError::WebSocket)?
+        {
+            return Ok((request, buffer.split_off(size)));
+        }
+        if buffer.len() > MAX_REQUEST_SIZE {
+            return Err(HandshakeError::RequestTooLarge);
+        }
+    }
+}
+
+/// Write the head of the HTTP response to the stream.
+async fn write_response<S>(stream: &mut S, response: &Response) -> Result<(), HandshakeError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut head = Vec::new();
+    server::write_response(&mut head, response).map_err(HandshakeError::WebSocket)?;
+    stream
+        .write_all(&head)
+        .await
+        .map_err(|e| HandshakeError::WebSocket(e.into()))?;
+    stream
+        .flush()
+        .await
+        .map_err(|e| HandshakeError::WebSocket(e.into()))
+}
+
+/// Upgrade the stream connection to a WebSocket connection if the upgrade request passes the checks, returning the
+/// username of the player signed in with the session it carries, if sessions are required. The configuration supplied
+/// sets small buffers.
+///
+/// The upgrade is done by hand rather than by tungstenite, as the session lookup is asynchronous and has to happen
+/// before the response is sent.
+async fn ws_accept<S, D>(
+    mut stream: S,
+    upgrade_checks: &UpgradeChecks,
+    id: &D,
+) -> Result<(WebSocketStream<S>, Option<String>), HandshakeError>
 where
     S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
 {
     const NO_BUFFER: usize = 0;
     const KB_1: usize = 1 << 10;
@@ -121,5 +351,71 @@ where
         max_frame_size: Some(KB_1),
         ..Default::default()
     };
-    tokio_tungstenite::accept_async_with_config(tcp_stream, Some(ws_config)).await
+    let (request, rest) = read_request(&mut stream).await?;
+    let checked = match server::create_response(&request) {
+        Ok(response) => upgrade_checks
+            .check(&request, id)
+            .await
+            .map(|session_user| (response, session_user)),
+        Err(e) => {
+            log::info!("{id}: Rejecting an invalid upgrade request : {e}.");
+            Err(StatusCode::BAD_REQUEST)
+        }
+    };
+    match checked {
+        Ok((response, session_user)) => {
+            write_response(&mut stream, &response).await?;
+            let websocket =
+                WebSocketStream::from_partially_read(stream, rest, Role::Server, Some(ws_config))
+                    .await;
+            Ok((websocket, session_user))
+        }
+        Err(status) => {
+            // The connection is dropped right after, whether the response could be sent or not.
+            let _: Result<_, _> = write_response(&mut stream, &error_response(status)).await;
+            Err(HandshakeError::Rejected(status))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\n\
+        Host: localhost\r\n\
+        Origin: https://localhost\r\n\
+        Connection: Upgrade\r\n\
+        Upgrade: websocket\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+
+    /// Run the upgrade of a connection sending [`REQUEST`], returning whether it succeeded and the response head.
+    async fn upgrade(allowed_origin: &str) -> (bool, String) {
+        let (mut client, server) = tokio::io::duplex(1 << 10);
+        client.write_all(REQUEST).await.unwrap();
+        let upgrade_checks = UpgradeChecks {
+            allowed_origins: Arc::new([String::from(allowed_origin)]),
+            session_db: None,
+        };
+        let upgraded = ws_accept(server, &upgrade_checks, &"test").await.is_ok();
+        let mut response = vec![0; 1 << 10];
+        let size = client.read(&mut response).await.unwrap();
+        (
+            upgraded,
+            String::from_utf8_lossy(&response[..size]).into_owned(),
+        )
+    }
+
+    #[tokio::test]
+    async fn upgrade_checks() {
+        let (upgraded, response) = upgrade("https://localhost").await;
+        assert!(upgraded);
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+        let (upgraded, response) = upgrade("https://example.com").await;
+        assert!(!upgraded);
+        assert!(response.starts_with("HTTP/1.1 403"));
+    }
 }
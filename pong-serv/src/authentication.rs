@@ -0,0 +1,138 @@
+//! Authentication of the clients against the tickets and sessions issued by the website.
+//!
+//! The website gives a signed-in user a single-use ticket, valid for a short time, that its client puts in the Hello
+//! message. Redeeming the ticket deletes it and resolves the username of its owner.
+//!
+//! Alternatively, the client is authenticated by the website session cookie of its WebSocket upgrade request, looked up
+//! in the sessions table of the website.
+
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+
+/// Name of the cookie holding the key of the website session.
+pub const SESSION_COOKIE: &str = "sessionid";
+
+/// Errors encountered while authenticating a client.
+#[derive(thiserror::Error, Debug)]
+pub enum AuthenticationError {
+    /// This error happens when the token doesn't match any ticket, or when the ticket expired.
+    #[error("the token doesn't match any valid ticket")]
+    InvalidTicket,
+
+    /// This error happens when the session key doesn't match any session, when the session expired, or when it doesn't
+    /// belong to a signed-in player.
+    #[error("the session key doesn't match any signed-in session")]
+    InvalidSession,
+
+    /// This error happens when a version 3 client, which names its user instead of giving a ticket, doesn't connect
+    /// with the website session of that user.
+    #[error("the named user isn't the one signed in with the website session")]
+    UnverifiedUsername,
+
+    /// This error happens when an interaction with the database fails.
+    #[error("an error with the database occurred : {0}")]
+    DatabaseError(#[from] tokio_postgres::Error),
+
+    /// This error happens when the redemption of the ticket doesn't complete in time.
+    #[error("the lookup didn't complete within {0:?}")]
+    Timeout(Duration),
+}
+
+/// Redeem the ticket matching the token, and return the username of the player it was issued to.
+pub async fn redeem_ticket(
+    db_client: &Arc<tokio_postgres::Client>,
+    token: &str,
+) -> Result<String, AuthenticationError> {
+    let row = db_client
+        .query_opt(
+            "delete from account_gameticket \
+             using account_player \
+             where token = $1 and expire_date > now() and player_id = account_player.id \
+             returning account_player.username;",
+            &[&token],
+        )
+        .await?
+        .ok_or(AuthenticationError::InvalidTicket)?;
+    Ok(row.get(0))
+}
+
+/// Look up the session matching the key, and return the username of the player signed in with it.
+pub async fn authenticate_session(
+    db_client: &Arc<tokio_postgres::Client>,
+    session_key: &str,
+) -> Result<String, AuthenticationError> {
+    let session_data: String = db_client
+        .query_opt(
+            "select session_data from django_session where session_key = $1 and expire_date > now();",
+            &[&session_key],
+        )
+        .await?
+        .ok_or(AuthenticationError::InvalidSession)?
+        .get(0);
+    let player_id =
+        decode_session_player_id(&session_data).ok_or(AuthenticationError::InvalidSession)?;
+    let row = db_client
+        .query_opt(
+            "select username from account_player where id = $1;",
+            &[&player_id],
+        )
+        .await?
+        .ok_or(AuthenticationError::InvalidSession)?;
+    Ok(row.get(0))
+}
+
+/// Read the id of the signed-in player in the session data stored by the website.
+///
+/// The data is signed JSON : `payload:timestamp:signature`, the payload being URL-safe base64, and zlib-compressed
+/// when it starts with a dot. The signature is not checked, as the data comes from the database itself.
+fn decode_session_player_id(session_data: &str) -> Option<i64> {
+    let payload = session_data.split(':').next()?;
+    let json = match payload.strip_prefix('.') {
+        Some(compressed) => {
+            let mut json = Vec::new();
+            ZlibDecoder::new(URL_SAFE_NO_PAD.decode(compressed).ok()?.as_slice())
+                .read_to_end(&mut json)
+                .ok()?;
+            json
+        }
+        None => URL_SAFE_NO_PAD.decode(payload).ok()?,
+    };
+    serde_json::from_slice::<serde_json::Value>(&json)
+        .ok()?
+        .get("player_id")?
+        .as_i64()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    #[test]
+    fn session_data_decoding() {
+        let json = br#"{"player_id":42}"#;
+        let plain = format!("{}:1rZ3ab:signature", URL_SAFE_NO_PAD.encode(json));
+        assert_eq!(decode_session_player_id(&plain), Some(42));
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json).unwrap();
+        let compressed = format!(
+            ".{}:1rZ3ab:signature",
+            URL_SAFE_NO_PAD.encode(encoder.finish().unwrap())
+        );
+        assert_eq!(decode_session_player_id(&compressed), Some(42));
+
+        let anonymous = format!("{}:1rZ3ab:signature", URL_SAFE_NO_PAD.encode(b"{}"));
+        assert_eq!(decode_session_player_id(&anonymous), None);
+        assert_eq!(decode_session_player_id("not base64 !"), None);
+    }
+}
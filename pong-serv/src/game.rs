@@ -1,141 +1,505 @@
 //! Implementation of the logic of the Pong game.
 //!
-//! This mod defines and exposes the entrypoint function [`play_game_mode_0`], implemented below in sub-mods and in the
-//! [`crate::protocol`] mod.
+//! This mod defines and exposes the entrypoint functions [`play_game_mode_0`], [`play_game_mode_1`],
+//! [`play_game_mode_4`], [`play_tournament_match`], [`play_local_match`] and [`play_series`], implemented below in
+//! sub-mods and in the [`crate::protocol`] mod. The running remote games can be watched by spectators with
+//! [`spectate_game`].
 
 use std::sync::Arc;
 use std::time::SystemTime;
 
+use futures_util::SinkExt;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_postgres::types::ToSql;
+use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{tungstenite, WebSocketStream};
 
+pub use ai::Difficulty;
+pub use reconnection::Reconnections;
+pub use rules::GameRules;
+pub use series::{play_series, SeriesLength};
 pub use side::Side;
+pub use spectators::{spectate_game, GameSelector, LiveGames};
 use state::Game0State;
 
-use crate::game::state::{Game1State, GameResult};
+use crate::game::combined_send::CombinedSend;
+use crate::game::state::{Game1State, Game4State, GameResult, PostGameChoice, StageError};
+use crate::protocol::{
+    close_connection, send_message, CloseReason, ProtocolVersion, RatingUpdateMessage,
+    ServerToClientMessage,
+};
+use crate::sessions::SessionHandle;
+use crate::shutdown::Shutdown;
+use crate::{league, rating};
 
+mod ai;
+mod clock;
 mod combined_send;
 mod engine;
+mod reconnection;
+mod rules;
+mod series;
 mod side;
+mod spectators;
 mod state;
 
-/// Opaque structure coming out of matchmaking for use by this mod. Represents a player.
+/// Opaque structure coming out of matchmaking for use by this mod. Represents a player, the version of the protocol
+/// its client speaks, and the notification of the server shutdown its connection must watch.
 #[derive(Debug)]
 pub struct Player<S> {
     ws: WebSocketStream<S>,
     id: String,
+    version: ProtocolVersion,
+    session: SessionHandle,
+    shutdown: Shutdown,
 }
 
 impl<S> Player<S> {
     /// Creates a new [`Player`].
-    pub fn new(websocket: WebSocketStream<S>, id: String) -> Player<S> {
-        Player { ws: websocket, id }
+    pub fn new(
+        websocket: WebSocketStream<S>,
+        id: String,
+        version: ProtocolVersion,
+        session: SessionHandle,
+        shutdown: Shutdown,
+    ) -> Player<S> {
+        Player {
+            ws: websocket,
+            id,
+            version,
+            session,
+            shutdown,
+        }
+    }
+
+    /// The username of this player.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The version of the protocol spoken with this player's client.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Give access to the connection and to the shutdown notification, for the match-making to keep the former alive
+    /// while waiting, and to stop waiting on the latter.
+    pub fn connection_mut(&mut self) -> (&mut WebSocketStream<S>, &mut Shutdown) {
+        (&mut self.ws, &mut self.shutdown)
+    }
+
+    /// End the connection with the client, telling it why.
+    pub async fn close(&mut self, reason: CloseReason)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        close_connection(&mut self.ws, self.version, reason).await;
+    }
+
+    /// Serialize the message with the version spoken by the client, and send it, unless it doesn't exist in this
+    /// version.
+    pub async fn send<M>(&mut self, message: M) -> Result<(), tungstenite::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        M: Into<ServerToClientMessage>,
+    {
+        send_message(&mut self.ws, self.version, message).await
+    }
+}
+
+/// Send the message to both players, serialized with the version each client speaks. A player whose version doesn't
+/// have the message isn't sent it. On failure, the [`Side`] of the player whose send failed is returned.
+async fn send_to_both<S, M>(
+    left_player: &mut Player<S>,
+    right_player: &mut Player<S>,
+    message: M,
+) -> Result<(), (tungstenite::Error, Side)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    M: Into<ServerToClientMessage>,
+{
+    let message = message.into();
+    let left_message = left_player.version.encode(message.clone());
+    let right_message = right_player.version.encode(message);
+    match (left_message, right_message) {
+        (Some(left_message), Some(right_message)) => {
+            CombinedSend::new(
+                &mut left_player.ws,
+                &mut right_player.ws,
+                Message::Binary(left_message),
+                Message::Binary(right_message),
+            )
+            .await
+        }
+        (Some(left_message), None) => left_player
+            .ws
+            .send(Message::Binary(left_message))
+            .await
+            .map_err(|e| (e, Side::Left)),
+        (None, Some(right_message)) => right_player
+            .ws
+            .send(Message::Binary(right_message))
+            .await
+            .map_err(|e| (e, Side::Right)),
+        (None, None) => Ok(()),
     }
 }
 
 /// Errors encountered while playing the game.
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::large_enum_variant, reason = "the player is handed back")]
 pub enum PlayingError<S> {
     /// This error happens if a poll to a [`WebSocketStream`] returns an error when sending the
     /// [`protocol::GameStartMessage`] to a client.
     #[error("an error at the websocket layer occurred during pre-game grace period : {0}")]
-    ClientError(tungstenite::Error, (WebSocketStream<S>, String)),
+    ClientError(tungstenite::Error, Player<S>),
+
+    /// This error happens if the client of a game without any other client disconnects during the pre-game grace
+    /// period.
+    #[error("the client disconnected during pre-game grace period : {0}")]
+    ConnectionLost(tungstenite::Error),
 
     /// This error happens when an interaction with the database fails. This should never happen if everything is
     /// configured correctly, and therefore indicates a runtime issue outside the scope of this program.
     #[error("an error with the database occurred : {0}")]
     DatabaseError(#[from] tokio_postgres::Error),
+
+    /// This error happens when the server starts shutting down during the game. The connections have been closed.
+    #[error("the server is shutting down")]
+    ServerShutdown,
 }
 
-impl<S> From<(tungstenite::Error, Player<S>)> for PlayingError<S> {
-    fn from((error, player): (tungstenite::Error, Player<S>)) -> Self {
-        PlayingError::ClientError(error, (player.ws, player.id))
+impl<S> From<StageError<S>> for PlayingError<S> {
+    fn from(error: StageError<S>) -> Self {
+        match error {
+            StageError::Disconnected(error, player) => PlayingError::ClientError(error, player),
+            StageError::Lost(error) => PlayingError::ConnectionLost(error),
+            StageError::ServerShutdown => PlayingError::ServerShutdown,
+        }
     }
 }
 
-/// Play out a game of Pong opposing the two [`Player`]s. Returns them for further playing if no error occurred. The
-/// only possible errors are websocket-related and database-related.
+/// Play out games of Pong opposing the two [`Player`]s with the given rules, as long as they both ask for a rematch
+/// once a game is done. If they are paired for a league `fixture`, the first game counts for it. Returns the players
+/// that asked to go back into the match-making queue, left then right. The possible errors are websocket-related,
+/// database-related, or the server shutting down. On a database error, both connections are closed.
 pub async fn play_game_mode_0<S>(
     mut left_player: Player<S>,
     mut right_player: Player<S>,
+    mut fixture: Option<i64>,
+    rules: GameRules,
     db_client: &Arc<tokio_postgres::Client>,
-) -> Result<(Player<S>, Player<S>), PlayingError<S>>
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+) -> Result<[Option<Player<S>>; 2], PlayingError<S>>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    let game_start_time_point = SystemTime::now();
-
-    let mut game_state = Game0State::new();
-    let (game_result, pl, pr) = loop {
-        (game_state, left_player, right_player) =
-            match game_state.next_state(left_player, right_player).await? {
-                (Game0State::Done(result), pl, pr) => break (result, pl, pr),
-                other_state => other_state,
+    let choices = loop {
+        let (result_id, result, mut pl, mut pr) = play_game_0(
+            left_player,
+            right_player,
+            rules,
+            None,
+            db_client,
+            reconnections,
+            live_games,
+        )
+        .await?;
+        if let Some(fixture_id) = fixture.take() {
+            if let Err(e) = league::record_fixture_result(db_client, fixture_id, result_id).await {
+                pl.close(CloseReason::InternalError).await;
+                pr.close(CloseReason::InternalError).await;
+                return Err(e.into());
             }
+        }
+        (left_player, right_player) = match Game0State::Done(result)
+            .next_state(pl, pr, reconnections, live_games, rules)
+            .await?
+        {
+            (Game0State::Over(choices), pl, pr) => {
+                (left_player, right_player) = (pl, pr);
+                break choices;
+            }
+            (_, pl, pr) => (pl, pr),
+        };
     };
-    (left_player, right_player) = (pl, pr);
 
-    let game_end_time_point = SystemTime::now();
-    write_game_result_to_database(
+    let requeued = |choice, player| match choice {
+        PostGameChoice::Requeue => Some(player),
+        PostGameChoice::Quit => None,
+    };
+    Ok([
+        requeued(choices[0], left_player),
+        requeued(choices[1], right_player),
+    ])
+}
+
+/// Play out a single game of Pong opposing the two [`Player`]s with the given rules, as a match of a tournament : there
+/// is no post-game phase. Returns the id of the game result written to the database, the [`Side`] of the winner, and
+/// both players, left then right. The possible errors are the same as in [`play_game_mode_0`].
+pub async fn play_tournament_match<S>(
+    left_player: Player<S>,
+    right_player: Player<S>,
+    rules: GameRules,
+    db_client: &Arc<tokio_postgres::Client>,
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+) -> Result<(i64, Side, Player<S>, Player<S>), PlayingError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (result_id, result, pl, pr) = play_game_0(
+        left_player,
+        right_player,
+        rules,
+        None,
         db_client,
-        &left_player.id,
-        &right_player.id,
-        game_start_time_point,
-        game_end_time_point,
-        game_result,
+        reconnections,
+        live_games,
     )
     .await?;
+    Ok((result_id, result.winner, pl, pr))
+}
 
-    Ok((left_player, right_player))
+/// Play out a game mode 0 game with the given rules from its startup until it is done, and write its result to the
+/// database, as a game of the `series` if any. The game is rated : both players are sent their new rating. Returns the
+/// id of the written result, the result itself and both players. On a database error, both connections are closed.
+async fn play_game_0<S>(
+    mut left_player: Player<S>,
+    mut right_player: Player<S>,
+    rules: GameRules,
+    series: Option<i64>,
+    db_client: &Arc<tokio_postgres::Client>,
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+) -> Result<(i64, GameResult, Player<S>, Player<S>), PlayingError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let game_start_time_point = SystemTime::now();
+
+    let mut game_state = Game0State::new();
+    loop {
+        (game_state, left_player, right_player) = match game_state
+            .next_state(left_player, right_player, reconnections, live_games, rules)
+            .await?
+        {
+            (Game0State::Done(result), mut pl, mut pr) => {
+                let game_end_time_point = SystemTime::now();
+                return match write_game_result_to_database(
+                    db_client,
+                    &pl.id,
+                    &pr.id,
+                    game_start_time_point,
+                    game_end_time_point,
+                    &result,
+                    true,
+                    series,
+                )
+                .await
+                {
+                    Ok((result_id, [left_rating, right_rating])) => {
+                        // A player who doesn't get its rating is noticed as disconnected right after.
+                        let _: Result<_, _> = pl.send(left_rating).await;
+                        let _: Result<_, _> = pr.send(right_rating).await;
+                        Ok((result_id, result, pl, pr))
+                    }
+                    Err(e) => {
+                        pl.close(CloseReason::InternalError).await;
+                        pr.close(CloseReason::InternalError).await;
+                        Err(e.into())
+                    }
+                };
+            }
+            other_state => other_state,
+        };
+    }
 }
 
+/// Play out a local game of Pong with the given rules on the [`Player`]'s connection. Returns it for further playing if
+/// no error occurred.
 pub async fn play_game_mode_1<S>(
-    mut connection: WebSocketStream<S>,
-) -> Result<WebSocketStream<S>, tokio_tungstenite::tungstenite::Error>
+    player: Player<S>,
+    rules: GameRules,
+) -> Result<Player<S>, PlayingError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    play_local_match(player, rules)
+        .await
+        .map(|(_, player)| player)
+}
+
+/// Play out a local game of Pong with the given rules on the [`Player`]'s connection, as a match of a local
+/// tournament. Returns the [`Side`] of the winner, or [`None`] if the client left before the end, and the player for
+/// further playing.
+pub async fn play_local_match<S>(
+    mut player: Player<S>,
+    rules: GameRules,
+) -> Result<(Option<Side>, Player<S>), PlayingError<S>>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     let mut game_state = Game1State::new();
-    let connection = loop {
-        (game_state, connection) = match game_state.next_state(connection).await? {
-            (Game1State::Done, connection) => break connection,
+    loop {
+        (game_state, player) = match game_state.next_state(player, rules).await? {
+            (Game1State::Done(result), player) => {
+                return Ok((result.map(|result| result.winner), player))
+            }
+            other_state => other_state,
+        };
+    }
+}
+
+/// Play out a game of Pong with the given rules, opposing the [`Player`] to an AI of the given [`Difficulty`], going by
+/// the username `ai_id`. Returns the player if no error occurred. The result is written to the database against the
+/// AI's account, and isn't rated. On a database error, the connection is closed.
+pub async fn play_game_mode_4<S>(
+    mut player: Player<S>,
+    difficulty: Difficulty,
+    rules: GameRules,
+    ai_id: &str,
+    db_client: &Arc<tokio_postgres::Client>,
+) -> Result<Player<S>, PlayingError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let player_side = rand::random();
+    let game_start_time_point = SystemTime::now();
+
+    let mut game_state = Game4State::new();
+    let game_result = loop {
+        (game_state, player) = match game_state
+            .next_state(player, player_side, ai_id, difficulty, rules)
+            .await?
+        {
+            (Game4State::Done(result), pl) => {
+                player = pl;
+                break result;
+            }
             other_state => other_state,
         };
     };
-    Ok(connection)
+
+    let game_end_time_point = SystemTime::now();
+    let (pl_id, pr_id) = match player_side {
+        Side::Left => (player.id.as_str(), ai_id),
+        Side::Right => (ai_id, player.id.as_str()),
+    };
+    if let Err(e) = write_game_result_to_database(
+        db_client,
+        pl_id,
+        pr_id,
+        game_start_time_point,
+        game_end_time_point,
+        &game_result,
+        false,
+        None,
+    )
+    .await
+    {
+        player.close(CloseReason::InternalError).await;
+        return Err(e.into());
+    }
+
+    Ok(player)
 }
 
-/// Try to write the game outcome to the database. Errors here are database errors - this is hard.
+/// Insert the result of a game, updating the ratings of the players if it is rated. The change of the left player's
+/// rating is computed along, the right player's being the opposite.
+const GAME_RESULT_QUERY: &str = "\
+    with players as ( \
+        select p1.id as p1_id, p2.id as p2_id, p1.rating as p1_rating, p2.rating as p2_rating, \
+               case when $8 \
+                    then round($9::integer * ((case when $7 then 1 else 0 end) \
+                                              - 1 / (1 + power(10, (p2.rating - p1.rating) / 400.0))))::integer \
+                    else 0 end as p1_change \
+        from account_player p1, account_player p2 \
+        where p1.username = $1 and p2.username = $2 \
+    ), result as ( \
+        insert \
+        into account_gameresult(p1_score, p2_score, date, duration, p1_id, p2_id, winner_id, \
+                                win_type, series_id) \
+        select $3, $4, $5, cast ($6 as timestamp with time zone) - $5, p1_id, p2_id, \
+               case when $7 then p1_id else p2_id end, $10, $11 \
+        from players \
+        returning id \
+    ), rated as ( \
+        update account_player player \
+        set rating = player.rating + case when player.id = players.p1_id then players.p1_change \
+                                          else -players.p1_change end \
+        from players \
+        where $8 and player.id in (players.p1_id, players.p2_id) \
+    ) \
+    select result.id, players.p1_rating + players.p1_change, players.p2_rating - players.p1_change, \
+           players.p1_change \
+    from result, players;";
+
+/// Try to write the game outcome to the database, along with the way it was won and the series it is part of if any,
+/// returning the id of the written row.
+/// If the game is `rated`, the ratings of both players are updated in the same statement. The new rating of each player
+/// and its change are returned too, left then right. Errors here are database errors - this is hard.
+#[allow(clippy::too_many_arguments)]
 async fn write_game_result_to_database(
     db_client: &Arc<tokio_postgres::Client>,
     pl_id: &str,
     pr_id: &str,
     game_start_time_point: SystemTime,
     game_end_time_point: SystemTime,
-    game_result: GameResult,
-) -> Result<(), tokio_postgres::Error> {
-    let query = match game_result.winner {
-        Side::Left => "with id1 as (select id from account_player where username = $1), \
-                            id2 as (select id from account_player where username = $2) \
-                       insert \
-                       into account_gameresult(p1_score, p2_score, date, duration, p1_id, p2_id, winner_id) \
-                       values($3, $4, $5, cast ($6 as timestamp with time zone) - $5, \
-                              (select id from id1), (select id from id2), (select id from id1));",
-        Side::Right => "with id1 as (select id from account_player where username = $1), \
-                             id2 as (select id from account_player where username = $2) \
-                        insert \
-                        into account_gameresult(p1_score, p2_score, date, duration, p1_id, p2_id, winner_id) \
-                        values($3, $4, $5, cast ($6 as timestamp with time zone) - $5, \
-                               (select id from id1), (select id from id2), (select id from id2));",
-    };
-    let parameters: [&(dyn ToSql + Sync); 6] = [
+    game_result: &GameResult,
+    rated: bool,
+    series: Option<i64>,
+) -> Result<(i64, [RatingUpdateMessage; 2]), tokio_postgres::Error> {
+    let parameters: [&(dyn ToSql + Sync); 11] = [
         &pl_id,
         &pr_id,
         &i16::try_from(game_result.score[0]).expect("Score is beyond an i16."),
         &i16::try_from(game_result.score[1]).expect("Score is beyond an i16."),
         &game_start_time_point,
         &game_end_time_point,
+        &(game_result.winner == Side::Left),
+        &rated,
+        &rating::K_FACTOR,
+        &i16::from(game_result.win_type),
+        &series,
     ];
-    db_client.execute(query, &parameters).await.map(|_| ())
+    let row = db_client.query_one(GAME_RESULT_QUERY, &parameters).await?;
+    let change: i32 = row.get(3);
+    Ok((
+        row.get(0),
+        [
+            RatingUpdateMessage::new(row.get(1), change),
+            RatingUpdateMessage::new(row.get(2), -change),
+        ],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the parameters of the game result query accept the types bound to them. The statement is prepared
+    /// on the database given by the `PONG_SERV_TEST_DATABASE` connection string.
+    #[tokio::test]
+    #[ignore = "needs PONG_SERV_TEST_DATABASE"]
+    async fn game_result_query_parameters() {
+        let config =
+            std::env::var("PONG_SERV_TEST_DATABASE").expect("PONG_SERV_TEST_DATABASE is not set");
+        let (db_client, connection) = tokio_postgres::connect(&config, tokio_postgres::NoTls)
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+        let statement = db_client.prepare(GAME_RESULT_QUERY).await.unwrap();
+        let types = statement.params();
+
+        assert_eq!(types.len(), 11);
+        assert!(<&str>::accepts(&types[0]) && <&str>::accepts(&types[1]));
+        assert!(i16::accepts(&types[2]) && i16::accepts(&types[3]));
+        assert!(SystemTime::accepts(&types[4]) && SystemTime::accepts(&types[5]));
+        assert!(bool::accepts(&types[6]) && bool::accepts(&types[7]));
+        assert!(i32::accepts(&types[8]));
+        assert!(i16::accepts(&types[9]));
+        assert!(Option::<i64>::accepts(&types[10]));
+    }
 }
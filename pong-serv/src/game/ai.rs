@@ -0,0 +1,233 @@
+//! Implementation of the server-side opponent driving a pad in the AI opponent game mode.
+//!
+//! The AI is held to the limits of a human client : it only looks at the game a few times per second, reacts with a
+//! delay, misjudges where the ball will land, and can't change the movement of its pad more often than a client may
+//! send input updates.
+
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::game::engine::bounce_off_horizontal_edges;
+use crate::game::{GameRules, Side};
+use crate::protocol::constants::{
+    BALL_EDGE, BALL_RADIUS, MAX_CLIENT_UPDATES_PER_SECOND, PAD_WIDTH, RATIO, TICKS_PER_SECOND,
+};
+
+/// Time the AI waits before serving, leaving its opponent time to get ready.
+const SERVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Difficulty levels of the AI opponent.
+///
+/// Conversions from [`u8`] are implemented in [`crate::protocol`]. They follow the Protocol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// The human-like limits an AI plays with.
+struct Handicap {
+    /// Time between two looks at the game.
+    refresh_period: Duration,
+    /// Time between a look at the game and the pad acting on it.
+    reaction_delay: Duration,
+    /// Maximum error when predicting where the ball will reach the pad.
+    prediction_error: f64,
+}
+
+impl From<Difficulty> for Handicap {
+    fn from(difficulty: Difficulty) -> Self {
+        match difficulty {
+            Difficulty::Easy => Handicap {
+                refresh_period: Duration::from_millis(1000),
+                reaction_delay: Duration::from_millis(400),
+                prediction_error: 0.2,
+            },
+            Difficulty::Medium => Handicap {
+                refresh_period: Duration::from_millis(1000),
+                reaction_delay: Duration::from_millis(250),
+                prediction_error: 0.1,
+            },
+            Difficulty::Hard => Handicap {
+                refresh_period: Duration::from_millis(500),
+                reaction_delay: Duration::from_millis(150),
+                prediction_error: 0.03,
+            },
+        }
+    }
+}
+
+/// An AI driving the pad of the given [`Side`]. It is fed the game state on each tick, and answers with the movement of
+/// its pad, as a client would.
+pub(super) struct Ai {
+    side: Side,
+    rules: GameRules,
+    refresh_ticks: u64,
+    reaction_ticks: u64,
+    prediction_error: f64,
+    tick: u64,
+    /// Target of the pad center on the vertical axis, and the tick from which the pad goes for it.
+    pending_target: Option<(f64, u64)>,
+    target_y: f64,
+    pad_dy: i8,
+    last_update_tick: Option<u64>,
+}
+
+impl Ai {
+    /// Creates a new [`Ai`] playing on `side` with the given [`Difficulty`], in a game with the given [`GameRules`].
+    pub(super) fn new(side: Side, difficulty: Difficulty, rules: GameRules) -> Ai {
+        let handicap = Handicap::from(difficulty);
+        Ai {
+            side,
+            rules,
+            refresh_ticks: duration_to_ticks(handicap.refresh_period).max(1),
+            reaction_ticks: duration_to_ticks(handicap.reaction_delay),
+            prediction_error: handicap.prediction_error,
+            tick: 0,
+            pending_target: None,
+            target_y: 1.0 / 2.0,
+            pad_dy: 0,
+            last_update_tick: None,
+        }
+    }
+
+    /// Look at the game if it is time to, and return the movement of the pad for this tick.
+    pub(super) fn next_movement<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        ball_x: f64,
+        ball_y: f64,
+        angle: f64,
+        pad_y: f64,
+    ) -> i8 {
+        if self.tick.is_multiple_of(self.refresh_ticks) {
+            let ball_movement = self.rules.ball_movement_per_tick();
+            let target_y =
+                match predict_ball_center_y(self.side, ball_x, ball_y, angle, ball_movement) {
+                    Some(y) => y + rng.gen_range(-self.prediction_error..=self.prediction_error),
+                    None => 1.0 / 2.0,
+                };
+            self.pending_target = Some((target_y, self.tick + self.reaction_ticks));
+        }
+        if let Some((target_y, from_tick)) = self.pending_target {
+            if self.tick >= from_tick {
+                self.target_y = target_y;
+                self.pending_target = None;
+            }
+        }
+
+        let pad_height = self.rules.pad_height;
+        let distance = self.target_y - (pad_y + pad_height / 2.0);
+        let wanted_dy = if distance.abs() <= pad_height / 3.0 {
+            0
+        } else if distance > 0.0 {
+            1
+        } else {
+            -1
+        };
+        let may_update = self.last_update_tick.is_none_or(|last_update_tick| {
+            self.tick >= last_update_tick + TICKS_PER_SECOND / MAX_CLIENT_UPDATES_PER_SECOND
+        });
+        if wanted_dy != self.pad_dy && may_update {
+            self.pad_dy = wanted_dy;
+            self.last_update_tick = Some(self.tick);
+        }
+        self.tick += 1;
+        self.pad_dy
+    }
+
+    /// Whether the AI serves the ball, which has waited for its serve for the given ticks.
+    pub(super) fn serves(&self, waited_ticks: u64) -> bool {
+        waited_ticks >= duration_to_ticks(SERVE_DELAY)
+    }
+}
+
+/// Number of game ticks in the duration, rounded down.
+fn duration_to_ticks(duration: Duration) -> u64 {
+    duration.as_millis() as u64 * TICKS_PER_SECOND / 1000
+}
+
+/// Follow the ball, moving by `ball_movement` each tick and bouncing off the top and bottom walls, until it reaches the
+/// pad of the given [`Side`]. Returns the position of the center of the ball on the vertical axis at that moment, or
+/// [`None`] if the ball is going the other way.
+fn predict_ball_center_y(
+    side: Side,
+    ball_x: f64,
+    ball_y: f64,
+    angle: f64,
+    ball_movement: f64,
+) -> Option<f64> {
+    let (mut ball_x, mut ball_y, mut angle) = (ball_x, ball_y, angle);
+    let going_right = f64::cos(angle) > 0.0;
+    if going_right != (side == Side::Right) {
+        return None;
+    }
+    let reached = |ball_x: f64| match side {
+        Side::Left => ball_x <= PAD_WIDTH,
+        Side::Right => ball_x + BALL_EDGE >= RATIO - PAD_WIDTH,
+    };
+    // A ball going almost vertically takes too long to be worth following.
+    for _ in 0..10 * TICKS_PER_SECOND {
+        if reached(ball_x) {
+            return Some(ball_y + BALL_RADIUS);
+        }
+        ball_x += ball_movement * f64::cos(angle);
+        ball_y -= ball_movement * f64::sin(angle);
+        (ball_y, angle) = bounce_off_horizontal_edges(ball_y, angle);
+        angle = angle.rem_euclid(2.0 * PI);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_4;
+
+    use crate::protocol::constants::PAD_HEIGHT;
+
+    use super::*;
+
+    const BIAS: f64 = 1.0e-2;
+
+    #[test]
+    fn ball_prediction() {
+        let center_y = 1.0 / 2.0 - BALL_RADIUS;
+        let predict = |side, angle| {
+            let ball_movement = GameRules::default().ball_movement_per_tick();
+            predict_ball_center_y(side, RATIO / 2.0, center_y, angle, ball_movement)
+        };
+
+        let straight = predict(Side::Right, 0.0).unwrap();
+        assert!((1.0 / 2.0 - BIAS..=1.0 / 2.0 + BIAS).contains(&straight));
+        assert_eq!(predict(Side::Left, 0.0), None);
+        assert_eq!(predict(Side::Right, PI), None);
+
+        // Going up at 45 degrees from the center, the ball bounces off the top wall and comes back down.
+        let bounced = predict(Side::Right, FRAC_PI_4).unwrap();
+        let travel = RATIO - PAD_WIDTH - BALL_EDGE - RATIO / 2.0;
+        let expected = travel - (1.0 / 2.0 - BALL_RADIUS) + BALL_RADIUS;
+        assert!((expected - BIAS..=expected + BIAS).contains(&bounced));
+    }
+
+    #[test]
+    fn input_rate_limit() {
+        let mut ai = Ai::new(Side::Right, Difficulty::Hard, GameRules::default());
+        let mut rng = rand::thread_rng();
+        let mut updates = 0;
+        let mut last_dy = 0;
+        for tick in 0..TICKS_PER_SECOND {
+            // The pad jumps across the center each tick, so that the AI wants to change its movement each tick.
+            let pad_y = if tick % 2 == 0 { 0.0 } else { 1.0 - PAD_HEIGHT };
+            let dy = ai.next_movement(&mut rng, RATIO / 2.0, 1.0 / 2.0, PI, pad_y);
+            if dy != last_dy {
+                updates += 1;
+                last_dy = dy;
+            }
+        }
+        assert!(updates > 0);
+        assert!(updates <= MAX_CLIENT_UPDATES_PER_SECOND);
+    }
+}
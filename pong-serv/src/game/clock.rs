@@ -0,0 +1,81 @@
+//! The game clock of time-limited games.
+//!
+//! The clock runs down with the ticks of the game loop, so that it stops while the game is paused. Once it runs out,
+//! the game is decided at the buzzer on score, or goes to sudden death if the score is tied : the next point wins.
+
+use std::time::Duration;
+
+use crate::game::Side;
+use crate::protocol::constants::TICKS_PER_SECOND;
+
+/// The time left to play in a time-limited game.
+#[derive(Copy, Clone, Debug)]
+pub(super) struct GameClock {
+    remaining_ticks: u64,
+}
+
+impl GameClock {
+    /// Creates a [`GameClock`] set to the time limit, rounded down to a whole number of ticks.
+    pub(super) fn new(time_limit: Duration) -> Self {
+        let tick_millis = 1000 / TICKS_PER_SECOND;
+        let remaining_ticks =
+            u64::try_from(time_limit.as_millis() / u128::from(tick_millis)).unwrap_or(u64::MAX);
+        Self { remaining_ticks }
+    }
+
+    /// Run the clock down by a tick. Returns whether the buzzer sounds on this tick.
+    pub(super) fn tick(&mut self) -> bool {
+        match self.remaining_ticks {
+            0 => false,
+            remaining => {
+                self.remaining_ticks = remaining - 1;
+                self.remaining_ticks == 0
+            }
+        }
+    }
+
+    /// Whether the clock ran out : the game is in sudden death.
+    pub(super) fn ran_out(&self) -> bool {
+        self.remaining_ticks == 0
+    }
+
+    /// The time left to play.
+    pub(super) fn remaining(&self) -> Duration {
+        Duration::from_millis(self.remaining_ticks.saturating_mul(1000 / TICKS_PER_SECOND))
+    }
+}
+
+/// The side leading the game at the buzzer, or [`None`] if the score is tied.
+pub(super) fn leader(scores: [u32; 2]) -> Option<Side> {
+    match scores[0].cmp(&scores[1]) {
+        std::cmp::Ordering::Greater => Some(Side::Left),
+        std::cmp::Ordering::Less => Some(Side::Right),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_runs_out() {
+        let mut clock = GameClock::new(Duration::from_millis(35));
+        assert_eq!(clock.remaining(), Duration::from_millis(30));
+        assert!(!clock.tick());
+        assert!(!clock.tick());
+        assert!(!clock.ran_out());
+        assert!(clock.tick());
+        assert!(clock.ran_out());
+        // The buzzer sounds only once.
+        assert!(!clock.tick());
+        assert_eq!(clock.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn leader_at_the_buzzer() {
+        assert_eq!(leader([3, 1]), Some(Side::Left));
+        assert_eq!(leader([3, 4]), Some(Side::Right));
+        assert_eq!(leader([2, 2]), None);
+    }
+}
@@ -1,5 +1,5 @@
 //! Implementation of a [`Future`] that concurrently awaits two sends, and informs the caller of the one which failed,
-//! if any.
+//! if any. Each sink gets its own item, as clients may speak different versions of the protocol.
 
 use std::future::Future;
 use std::pin::Pin;
@@ -13,15 +13,20 @@ use crate::game::Side;
 pub struct CombinedSend<'a, Si: ?Sized, Item> {
     left_sink: &'a mut Si,
     right_sink: &'a mut Si,
-    item: Option<Item>,
+    items: Option<(Item, Item)>,
 }
 
 impl<'a, Si, Item> CombinedSend<'a, Si, Item> {
-    pub fn new(left_sink: &'a mut Si, right_sink: &'a mut Si, item: Item) -> Self {
+    pub fn new(
+        left_sink: &'a mut Si,
+        right_sink: &'a mut Si,
+        left_item: Item,
+        right_item: Item,
+    ) -> Self {
         Self {
             left_sink,
             right_sink,
-            item: Some(item),
+            items: Some((left_item, right_item)),
         }
     }
 }
@@ -31,7 +36,6 @@ impl<Si: Unpin, Item> Unpin for CombinedSend<'_, Si, Item> {}
 impl<Si, Item> Future for CombinedSend<'_, Si, Item>
 where
     Si: Sink<Item> + Unpin,
-    Item: Clone,
 {
     type Output = Result<(), (Si::Error, Side)>;
 
@@ -43,7 +47,7 @@ where
             Pin::new(&mut this.right_sink),
         );
 
-        if this.item.is_some() {
+        if this.items.is_some() {
             let (left_ready, right_ready) = (
                 left_sink.as_mut().poll_ready(cx),
                 right_sink.as_mut().poll_ready(cx),
@@ -55,14 +59,14 @@ where
                 return Poll::Ready(Err((e, Side::Right)));
             }
 
-            let item = this
-                .item
+            let (left_item, right_item) = this
+                .items
                 .take()
                 .expect("polled CombinedSend after completion");
-            if let Err(e) = left_sink.as_mut().start_send(item.clone()) {
+            if let Err(e) = left_sink.as_mut().start_send(left_item) {
                 return Poll::Ready(Err((e, Side::Left)));
             }
-            if let Err(e) = right_sink.as_mut().start_send(item) {
+            if let Err(e) = right_sink.as_mut().start_send(right_item) {
                 return Poll::Ready(Err((e, Side::Right)));
             }
         }
@@ -4,10 +4,11 @@ use std::f64::consts::{FRAC_PI_2, PI};
 
 use rand::distributions::{Distribution, Standard, Uniform};
 
+use crate::game::rules::GameRules;
 use crate::game::side::Side;
 use crate::protocol::constants::{
     BALL_EDGE, BALL_RADIUS, HALF_BOUNCE_ANGLE_AMPL, HALF_SERVICE_ANGLE_AMPL, PAD_BOUNCE_ANGLE_AMPL,
-    PAD_HEIGHT, PAD_WIDTH, RATIO,
+    PAD_WIDTH, RATIO,
 };
 
 /// Preemptively optimized structure containing distributions needed to generate random service angles.
@@ -78,20 +79,22 @@ pub(super) fn bounce_off_horizontal_edges(ball_y: f64, angle: f64) -> (f64, f64)
     }
 }
 
-/// Computes collisions of the ball with the pads, and the bounce it makes. Returns the post-bounce position, which can
-/// be the same as the input.
+/// Computes collisions of the ball with the pads, of the height given by the rules, and the bounce it makes. Returns
+/// the post-bounce position, which can be the same as the input.
 pub(super) fn bounce_off_pads(
     ball_x: f64,
     ball_y: f64,
     angle: f64,
     l_pad_y: f64,
     r_pad_y: f64,
+    rules: &GameRules,
 ) -> (f64, f64, f64) {
-    if ball_pad_collide(ball_x, ball_y, 0.0, l_pad_y) {
+    let pad_height = rules.pad_height;
+    if ball_pad_collide(ball_x, ball_y, 0.0, l_pad_y, pad_height) {
         let collision_amount = (0.0 + PAD_WIDTH) - (ball_x + 0.0);
         let collision_x = 0.0 + PAD_WIDTH;
         let collision_y = match angle {
-            angle if 1.0 * FRAC_PI_2 <= angle && angle <= 2.0 * FRAC_PI_2 => {
+            angle if (1.0 * FRAC_PI_2..=2.0 * FRAC_PI_2).contains(&angle) => {
                 ball_y + collision_amount / f64::tan(angle - FRAC_PI_2)
             }
             angle if 2.0 * FRAC_PI_2 < angle && angle <= 3.0 * FRAC_PI_2 => {
@@ -99,25 +102,27 @@ pub(super) fn bounce_off_pads(
             }
             _ => return (ball_x, ball_y, angle),
         };
-        let angle = (0.0 + HALF_BOUNCE_ANGLE_AMPL) - pad_bounce_angle(collision_y, l_pad_y);
+        let angle =
+            (0.0 + HALF_BOUNCE_ANGLE_AMPL) - pad_bounce_angle(collision_y, l_pad_y, pad_height);
         let angle = if angle < 0.0 { angle + 2.0 * PI } else { angle };
         let correction_distance = distance(ball_x, ball_y, collision_x, collision_y);
         let ball_x = collision_x + correction_distance * f64::cos(angle);
         let ball_y = collision_y - correction_distance * f64::sin(angle);
         (ball_x, ball_y, angle)
-    } else if ball_pad_collide(ball_x, ball_y, RATIO - PAD_WIDTH, r_pad_y) {
+    } else if ball_pad_collide(ball_x, ball_y, RATIO - PAD_WIDTH, r_pad_y, pad_height) {
         let collision_amount = (ball_x + BALL_EDGE) - (RATIO - PAD_WIDTH);
         let collision_x = RATIO - PAD_WIDTH - BALL_EDGE;
         let collision_y = match angle {
-            angle if 0.0 * FRAC_PI_2 <= angle && angle <= 1.0 * FRAC_PI_2 => {
+            angle if (0.0 * FRAC_PI_2..=1.0 * FRAC_PI_2).contains(&angle) => {
                 ball_y + collision_amount * f64::tan(angle)
             }
-            angle if 3.0 * FRAC_PI_2 <= angle && angle <= 4.0 * FRAC_PI_2 => {
+            angle if (3.0 * FRAC_PI_2..=4.0 * FRAC_PI_2).contains(&angle) => {
                 ball_y - collision_amount / f64::tan(angle - 3.0 * FRAC_PI_2)
             }
             _ => return (ball_x, ball_y, angle),
         };
-        let angle = (PI - HALF_BOUNCE_ANGLE_AMPL) + pad_bounce_angle(collision_y, r_pad_y);
+        let angle =
+            (PI - HALF_BOUNCE_ANGLE_AMPL) + pad_bounce_angle(collision_y, r_pad_y, pad_height);
         let correction_distance = distance(ball_x, ball_y, collision_x, collision_y);
         let ball_x = collision_x + correction_distance * f64::cos(angle);
         let ball_y = collision_y - correction_distance * f64::sin(angle);
@@ -132,19 +137,17 @@ fn distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
 }
 
 /// Compute whether the ball collides with the given pad.
-fn ball_pad_collide(ball_x: f64, ball_y: f64, pad_x: f64, pad_y: f64) -> bool {
-    if ball_y <= pad_y + PAD_HEIGHT && ball_y + BALL_EDGE >= pad_y {
-        if ball_x <= pad_x + PAD_WIDTH && ball_x + BALL_EDGE >= pad_x {
-            return true;
-        }
-    }
-    return false;
+fn ball_pad_collide(ball_x: f64, ball_y: f64, pad_x: f64, pad_y: f64, pad_height: f64) -> bool {
+    ball_y <= pad_y + pad_height
+        && ball_y + BALL_EDGE >= pad_y
+        && ball_x <= pad_x + PAD_WIDTH
+        && ball_x + BALL_EDGE >= pad_x
 }
 
 /// Compute the angle offset based on where the ball is hitting the pad.
-fn pad_bounce_angle(ball_y: f64, pad_y: f64) -> f64 {
+fn pad_bounce_angle(ball_y: f64, pad_y: f64, pad_height: f64) -> f64 {
     let ball_center_y_relative_to_pad = (ball_y + BALL_RADIUS) - pad_y;
-    let amplitude_ratio = ball_center_y_relative_to_pad / PAD_HEIGHT;
+    let amplitude_ratio = ball_center_y_relative_to_pad / pad_height;
     amplitude_ratio * PAD_BOUNCE_ANGLE_AMPL
 }
 
@@ -164,12 +167,12 @@ mod tests {
         let mut thread_rng = rand::thread_rng();
 
         for angle in make_some_angles(&service_generator, Side::Left, &mut thread_rng) {
-            assert!(5.0 * FRAC_PI_6 - BIAS <= angle && angle <= 7.0 * FRAC_PI_6 + BIAS);
+            assert!((5.0 * FRAC_PI_6 - BIAS..=7.0 * FRAC_PI_6 + BIAS).contains(&angle));
         }
         for angle in make_some_angles(&service_generator, Side::Right, &mut thread_rng) {
             assert!(
-                (0.0 * FRAC_PI_6 - BIAS <= angle && angle <= 1.0 * FRAC_PI_6 + BIAS)
-                    || (11.0 * FRAC_PI_6 - BIAS <= angle && angle <= 12.0 * FRAC_PI_6 + BIAS)
+                (0.0 * FRAC_PI_6 - BIAS..=1.0 * FRAC_PI_6 + BIAS).contains(&angle)
+                    || (11.0 * FRAC_PI_6 - BIAS..=12.0 * FRAC_PI_6 + BIAS).contains(&angle)
             );
         }
     }
@@ -216,15 +219,15 @@ mod tests {
         let ball_y_in_area = 1.0 / 2.0;
 
         let (new_y, new_angle) = bounce_off_horizontal_edges(ball_y_top, vertical_up_angle);
-        assert!(0.0 + AMOUNT - BIAS <= new_y && new_y <= 0.0 + AMOUNT + BIAS);
-        assert!(3.0 * FRAC_PI_2 - BIAS <= new_angle && new_angle <= 3.0 * FRAC_PI_2 + BIAS);
+        assert!((0.0 + AMOUNT - BIAS..=0.0 + AMOUNT + BIAS).contains(&new_y));
+        assert!((3.0 * FRAC_PI_2 - BIAS..=3.0 * FRAC_PI_2 + BIAS).contains(&new_angle));
 
         let (new_y, new_angle) = bounce_off_horizontal_edges(ball_y_bot, vertical_down_angle);
         assert!(
-            (1.0 - BALL_EDGE) - AMOUNT - BIAS <= new_y
-                && new_y <= (1.0 - BALL_EDGE) - AMOUNT + BIAS
+            ((1.0 - BALL_EDGE) - AMOUNT - BIAS..=(1.0 - BALL_EDGE) - AMOUNT + BIAS)
+                .contains(&new_y)
         );
-        assert!(1.0 * FRAC_PI_2 - BIAS <= new_angle && new_angle <= 1.0 * FRAC_PI_2 + BIAS);
+        assert!((1.0 * FRAC_PI_2 - BIAS..=1.0 * FRAC_PI_2 + BIAS).contains(&new_angle));
 
         let (new_y, new_angle) = bounce_off_horizontal_edges(ball_y_in_area, vertical_up_angle);
         assert_eq!(ball_y_in_area, new_y);
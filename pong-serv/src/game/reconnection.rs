@@ -0,0 +1,64 @@
+//! Hand-over of reconnecting players to the running games waiting for them.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use super::Player;
+
+/// A server-wide structure, shared in the [`ServerContext`], through which the running games waiting for a disconnected
+/// player get its new connection.
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct Reconnections<S> {
+    window: Duration,
+    awaited: Mutex<HashMap<String, oneshot::Sender<Player<S>>>>,
+}
+
+impl<S> Reconnections<S> {
+    /// Creates a new [`Reconnections`] instance. Disconnected players are waited for during `window`, a zero duration
+    /// disabling reconnections.
+    pub fn new(window: Duration) -> Reconnections<S> {
+        Reconnections {
+            window,
+            awaited: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Time given to a disconnected player to come back.
+    pub(super) fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Start waiting for the player with the given id. Returns [`None`] if reconnections are disabled.
+    pub(super) fn register(&self, id: &str) -> Option<oneshot::Receiver<Player<S>>> {
+        if self.window.is_zero() {
+            return None;
+        }
+        let (sender, receiver) = oneshot::channel();
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        self.awaited
+            .lock()
+            .unwrap()
+            .insert(String::from(id), sender);
+        Some(receiver)
+    }
+
+    /// Stop waiting for the player with the given id.
+    pub(super) fn unregister(&self, id: &str) {
+        self.awaited.lock().unwrap().remove(id);
+    }
+
+    /// Hand the player to the game waiting for it. The player is given back if no game is waiting for it.
+    #[allow(clippy::result_large_err, reason = "the player is handed back")]
+    pub fn resume(&self, player: Player<S>) -> Result<(), Player<S>> {
+        // The lock is held while sending, so that a game unregistering the player afterwards can find it received.
+        let mut awaited = self.awaited.lock().unwrap();
+        match awaited.remove(&player.id) {
+            Some(sender) => sender.send(player),
+            None => Err(player),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! The rules a game is played with, chosen by the players among the ranges described in the Protocol.
+
+use std::time::Duration;
+
+use crate::protocol::constants::{
+    BALL_MOVEMENT_PER_SECOND, MAX_SCORE, PAD_HEIGHT, TARGET_SCORE, TICKS_PER_SECOND,
+};
+
+/// The rules of a game. The [`Default`] rule set is the one of the Protocol constants.
+///
+/// Conversions from the game mode parameters are implemented in [`crate::protocol`], which checks the values are
+/// within the ranges of the Protocol.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GameRules {
+    /// The score a player must reach to win.
+    pub target_score: u32,
+    /// Whether the winner must also lead by two points.
+    pub win_by_two: bool,
+    /// The distance the ball travels each second.
+    pub ball_speed: f64,
+    /// The height of the pads.
+    pub pad_height: f64,
+    /// The longest the game is played before it is decided on score, if it is time-limited. This one is set by the
+    /// server, not the players.
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            target_score: TARGET_SCORE,
+            win_by_two: false,
+            ball_speed: BALL_MOVEMENT_PER_SECOND,
+            pad_height: PAD_HEIGHT,
+            time_limit: None,
+        }
+    }
+}
+
+impl GameRules {
+    /// The distance the ball travels each tick.
+    pub(super) fn ball_movement_per_tick(&self) -> f64 {
+        self.ball_speed / TICKS_PER_SECOND as f64
+    }
+
+    /// Whether the player who just scored wins the game, given its score and the one of its opponent.
+    pub(super) fn is_won(&self, score: u32, opponent_score: u32) -> bool {
+        let lead_needed = if self.win_by_two { 2 } else { 1 };
+        score >= MAX_SCORE || (score >= self.target_score && score >= opponent_score + lead_needed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_end() {
+        let rules = GameRules::default();
+        assert!(!rules.is_won(9, 9));
+        assert!(rules.is_won(10, 9));
+        let rules = GameRules {
+            target_score: 5,
+            win_by_two: true,
+            ..GameRules::default()
+        };
+        assert!(rules.is_won(5, 3));
+        assert!(!rules.is_won(5, 4));
+        assert!(!rules.is_won(7, 6));
+        assert!(rules.is_won(8, 6));
+        assert!(rules.is_won(MAX_SCORE, MAX_SCORE - 1));
+    }
+}
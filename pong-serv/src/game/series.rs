@@ -0,0 +1,191 @@
+//! Best-of-N series : successive games between the same two players, on the same connections.
+//!
+//! The players swap sides between games, and are told the score of the series after each of them. The series is won
+//! by the first player to win the majority of its games. Each game is written to the database against the series, and
+//! the series itself is written with its result once it is over.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_postgres::types::ToSql;
+
+use crate::game::state::WinType;
+use crate::game::{play_game_0, GameRules, LiveGames, Player, PlayingError, Reconnections, Side};
+use crate::protocol::{CloseReason, SeriesScoreMessage};
+
+/// The number of games a series is played over at most.
+///
+/// Conversions from [`u8`] are implemented in [`crate::protocol`]. They follow the Protocol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SeriesLength {
+    BestOf3,
+    BestOf5,
+}
+
+impl SeriesLength {
+    /// The number of games the series is played over at most.
+    pub fn games(self) -> u8 {
+        match self {
+            Self::BestOf3 => 3,
+            Self::BestOf5 => 5,
+        }
+    }
+
+    /// The number of games a player must win to win the series.
+    fn wins_needed(self) -> u8 {
+        self.games() / 2 + 1
+    }
+}
+
+/// Play out a series of games of Pong between the two [`Player`]s with the given rules, until one of them wins the
+/// majority of its games. The `first` player plays the first game on the left side, then the players swap sides for
+/// every game. Returns the players still connected once the series is over, first then second.
+///
+/// A player withdrawing from a game, or leaving during the startup of a game after the first one, forfeits the series.
+/// The possible errors are the same as in [`super::play_game_mode_0`] : in particular, a failed startup of the first
+/// game returns the remaining player, as the series didn't begin. On a database error, both connections are closed.
+pub async fn play_series<S>(
+    first: Player<S>,
+    second: Player<S>,
+    length: SeriesLength,
+    rules: GameRules,
+    db_client: &Arc<tokio_postgres::Client>,
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+) -> Result<[Option<Player<S>>; 2], PlayingError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut first, mut second) = (first, second);
+    let series_id = match write_series_start(db_client, &first.id, &second.id, length).await {
+        Ok(series_id) => series_id,
+        Err(e) => {
+            first.close(CloseReason::InternalError).await;
+            second.close(CloseReason::InternalError).await;
+            return Err(e.into());
+        }
+    };
+    let ids = [first.id.clone(), second.id.clone()];
+    let mut wins = [0u8; 2];
+    let mut game = 0;
+    // The index of the winner of the series, and the players still connected.
+    let (winner, players) = loop {
+        // The first player is on the left side for the even games.
+        let first_side = if game % 2 == 0 {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        let (left, right) = match first_side {
+            Side::Left => (first, second),
+            Side::Right => (second, first),
+        };
+        let (result, pl, pr) = match play_game_0(
+            left,
+            right,
+            rules,
+            Some(series_id),
+            db_client,
+            reconnections,
+            live_games,
+        )
+        .await
+        {
+            Ok((_, result, pl, pr)) => (result, pl, pr),
+            // The remaining player wins by forfeit, unless the series didn't begin.
+            Err(PlayingError::ClientError(_, remaining)) if game > 0 => {
+                let winner = usize::from(remaining.id != ids[0]);
+                let mut players = [None, None];
+                players[winner] = Some(remaining);
+                break (winner, players);
+            }
+            Err(e) => return Err(e),
+        };
+        (first, second) = match first_side {
+            Side::Left => (pl, pr),
+            Side::Right => (pr, pl),
+        };
+        let game_winner = usize::from(result.winner != first_side);
+        wins[game_winner] += 1;
+        game += 1;
+        // A player told the series is over isn't sent anything else, save for the closing of its connection.
+        let _: Result<_, _> = first
+            .send(SeriesScoreMessage::new(wins[0], wins[1], length.games()))
+            .await;
+        let _: Result<_, _> = second
+            .send(SeriesScoreMessage::new(wins[1], wins[0], length.games()))
+            .await;
+        if result.win_type == WinType::Withdrawal {
+            let mut players = [Some(first), Some(second)];
+            players[1 - game_winner] = None;
+            break (game_winner, players);
+        }
+        if wins[game_winner] == length.wins_needed() {
+            break (game_winner, [Some(first), Some(second)]);
+        }
+    };
+
+    if let Err(e) = write_series_end(db_client, series_id, wins, &ids[winner]).await {
+        for mut player in players.into_iter().flatten() {
+            player.close(CloseReason::InternalError).await;
+        }
+        return Err(e.into());
+    }
+    Ok(players)
+}
+
+/// Record the start of a series between the two players, returning its id.
+async fn write_series_start(
+    db_client: &Arc<tokio_postgres::Client>,
+    first_id: &str,
+    second_id: &str,
+    length: SeriesLength,
+) -> Result<i64, tokio_postgres::Error> {
+    let query =
+        "insert into account_gameseries(p1_id, p2_id, best_of, p1_wins, p2_wins, start_date) \
+                 values((select id from account_player where username = $1), \
+                        (select id from account_player where username = $2), \
+                        $3, 0, 0, $4) \
+                 returning id;";
+    let parameters: [&(dyn ToSql + Sync); 4] = [
+        &first_id,
+        &second_id,
+        &i16::from(length.games()),
+        &SystemTime::now(),
+    ];
+    Ok(db_client.query_one(query, &parameters).await?.get(0))
+}
+
+/// Record the result of the series : the games won by each player, first then second, and the winner.
+async fn write_series_end(
+    db_client: &Arc<tokio_postgres::Client>,
+    series_id: i64,
+    wins: [u8; 2],
+    winner_id: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let query = "update account_gameseries \
+                 set p1_wins = $2, p2_wins = $3, end_date = $4, \
+                     winner_id = (select id from account_player where username = $5) \
+                 where id = $1;";
+    let parameters: [&(dyn ToSql + Sync); 5] = [
+        &series_id,
+        &i16::from(wins[0]),
+        &i16::from(wins[1]),
+        &SystemTime::now(),
+        &winner_id,
+    ];
+    db_client.execute(query, &parameters).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wins_needed() {
+        assert_eq!(SeriesLength::BestOf3.wins_needed(), 2);
+        assert_eq!(SeriesLength::BestOf5.wins_needed(), 3);
+    }
+}
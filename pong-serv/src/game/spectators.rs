@@ -0,0 +1,277 @@
+//! Broadcast of the running remote games to their spectators.
+//!
+//! Each running game feeds its messages to a [`broadcast`] channel, which never waits for its receivers : a spectator
+//! that doesn't keep up misses messages and is disconnected, without the players noticing anything.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use super::{GameRules, Player};
+use crate::protocol::constants::TICKS_PER_SECOND;
+use crate::protocol::{CloseReason, ServerToClientMessage, SpectatorSnapshotMessage};
+
+/// A server-wide structure, shared in the [`ServerContext`], listing the running remote games and giving access to
+/// their feeds.
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct LiveGames {
+    delay: Duration,
+    next_id: AtomicU32,
+    games: Mutex<HashMap<u32, Arc<LiveGame>>>,
+}
+
+/// A running remote game, with the usernames of its players, left then right.
+struct LiveGame {
+    usernames: [String; 2],
+    rules: GameRules,
+    feed: Mutex<Feed>,
+}
+
+/// The current score of a running game, and the channel its messages are broadcast through. They are updated
+/// together, so that a spectator gets a snapshot consistent with the messages that follow it.
+struct Feed {
+    scores: [u32; 2],
+    sender: broadcast::Sender<Broadcast>,
+}
+
+/// A message of a running game, and when it was sent to the players.
+#[derive(Clone)]
+struct Broadcast {
+    sent_at: Instant,
+    message: ServerToClientMessage,
+}
+
+/// How a spectator designates the game it wants to watch.
+#[derive(Debug)]
+pub enum GameSelector {
+    /// The id of the game, as listed.
+    Id(u32),
+    /// The username of one of its players.
+    Player(String),
+}
+
+impl LiveGames {
+    /// Creates a new [`LiveGames`] instance. The games are shown to their spectators `delay` after their players.
+    pub fn new(delay: Duration) -> LiveGames {
+        LiveGames {
+            delay,
+            next_id: AtomicU32::new(0),
+            games: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// List the running games, by id, with the usernames of their players, left then right.
+    pub fn list(&self) -> Vec<(u32, String, String)> {
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        let games = self.games.lock().unwrap();
+        let mut list: Vec<_> = games
+            .iter()
+            .map(|(&id, game)| {
+                let [left, right] = game.usernames.clone();
+                (id, left, right)
+            })
+            .collect();
+        list.sort_unstable_by_key(|&(id, _, _)| id);
+        list
+    }
+
+    /// List a starting game between the two players, left then right, played with the given rules, and return the feed
+    /// to broadcast its messages through. The game is unlisted once the feed is dropped.
+    pub(super) fn open(&self, usernames: [&str; 2], rules: GameRules) -> LiveFeed<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // The messages of the whole delay are held for the spectators, plus a second for them to be sent.
+        let capacity = (self.delay.as_secs() as usize + 1) * TICKS_PER_SECOND as usize;
+        let (sender, _) = broadcast::channel(capacity);
+        let game = Arc::new(LiveGame {
+            usernames: usernames.map(String::from),
+            rules,
+            feed: Mutex::new(Feed {
+                scores: [0, 0],
+                sender,
+            }),
+        });
+        self.games.lock().unwrap().insert(id, Arc::clone(&game));
+        LiveFeed {
+            live_games: self,
+            id,
+            game,
+        }
+    }
+
+    /// Find the selected game, unless the spectator plays in it. Returns a snapshot of it, along with a receiver of the
+    /// messages following the snapshot.
+    fn subscribe(
+        &self,
+        selector: &GameSelector,
+        spectator_id: &str,
+    ) -> Option<(SpectatorSnapshotMessage, broadcast::Receiver<Broadcast>)> {
+        let (id, game) = {
+            // The lock cannot panic as nothing in the guard's scope can panic.
+            let games = self.games.lock().unwrap();
+            let (&id, game) = match selector {
+                GameSelector::Id(id) => games.get_key_value(id)?,
+                GameSelector::Player(username) => games
+                    .iter()
+                    .find(|(_, game)| game.usernames.contains(username))?,
+            };
+            (id, Arc::clone(game))
+        };
+        if game
+            .usernames
+            .iter()
+            .any(|username| username == spectator_id)
+        {
+            return None;
+        }
+        let feed = game.feed.lock().unwrap();
+        let snapshot = SpectatorSnapshotMessage::new(id, &game.usernames, feed.scores, game.rules);
+        Some((snapshot, feed.sender.subscribe()))
+    }
+}
+
+/// Handle held by a running game to broadcast its messages to its spectators. Dropping it unlists the game, and ends
+/// the feed once the spectators have been sent the last messages.
+pub(super) struct LiveFeed<'a> {
+    live_games: &'a LiveGames,
+    id: u32,
+    game: Arc<LiveGame>,
+}
+
+impl LiveFeed<'_> {
+    /// Broadcast a message sent to the players, along with the score it leaves the game at.
+    pub(super) fn send<M: Into<ServerToClientMessage>>(&self, message: M, scores: [u32; 2]) {
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        let mut feed = self.game.feed.lock().unwrap();
+        feed.scores = scores;
+        // Sending fails only if nobody watches.
+        let _: Result<_, _> = feed.sender.send(Broadcast {
+            sent_at: Instant::now(),
+            message: message.into(),
+        });
+    }
+}
+
+impl Drop for LiveFeed<'_> {
+    fn drop(&mut self) {
+        self.live_games.games.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Errors ending the watch of a game before its end.
+#[derive(thiserror::Error, Debug)]
+pub enum SpectatingError {
+    /// This error happens when the selected game isn't running, or when the spectator plays in it.
+    #[error("no such running game")]
+    GameNotFound,
+
+    /// This error happens when a poll to the spectator's websocket returns an error.
+    #[error("connection error or close : {0}")]
+    ConnectionError(#[source] Box<tungstenite::Error>),
+
+    /// This error happens when the connection is lost, or when the client closes it.
+    #[error("the client left")]
+    Left,
+
+    /// This error happens when the client sends anything else than a ping or a pong.
+    #[error("received an unexpected message from the client : {0}")]
+    ProtocolViolation(Message),
+
+    /// This error happens when the messages pile up faster than they are sent to the client, until some are missed.
+    #[error("the client fell {0} messages behind")]
+    TooSlow(u64),
+
+    /// This error happens when the server starts shutting down.
+    #[error("the server is shutting down")]
+    ServerShutdown,
+}
+
+impl From<tungstenite::Error> for SpectatingError {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::ConnectionError(Box::new(error))
+    }
+}
+
+impl SpectatingError {
+    /// The reason to give the client when closing its connection, if it can still be told anything.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::ConnectionError(_) | Self::Left => None,
+            Self::GameNotFound => Some(CloseReason::GameNotFound),
+            Self::ProtocolViolation(_) => Some(CloseReason::ProtocolViolation),
+            Self::TooSlow(_) => Some(CloseReason::TooSlow),
+            Self::ServerShutdown => Some(CloseReason::ServerShutdown),
+        }
+    }
+}
+
+/// Make the spectator watch the selected game until its end : it is sent a snapshot of the game, then the messages
+/// the players are sent from then on, all of them with the delay of the [`LiveGames`]. The client is expected to only
+/// send pings and pongs.
+pub async fn spectate_game<S>(
+    spectator: &mut Player<S>,
+    selector: &GameSelector,
+    live_games: &LiveGames,
+) -> Result<(), SpectatingError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (snapshot, mut receiver) = live_games
+        .subscribe(selector, &spectator.id)
+        .ok_or(SpectatingError::GameNotFound)?;
+    hold_until(spectator, Instant::now() + live_games.delay).await?;
+    spectator.send(snapshot).await?;
+    loop {
+        tokio::select! {
+            broadcast = receiver.recv() => match broadcast {
+                Ok(Broadcast { sent_at, message }) => {
+                    hold_until(spectator, sent_at + live_games.delay).await?;
+                    spectator.send(message).await?;
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    return Err(SpectatingError::TooSlow(missed))
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            message = spectator.ws.next() => check_spectator_message(message)?,
+            () = spectator.shutdown.notified() => return Err(SpectatingError::ServerShutdown),
+        }
+    }
+}
+
+/// Wait until the given instant, watching the spectator's connection in the meantime.
+async fn hold_until<S>(spectator: &mut Player<S>, instant: Instant) -> Result<(), SpectatingError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let sleep = tokio::time::sleep_until(instant);
+    tokio::pin!(sleep);
+    loop {
+        tokio::select! {
+            () = &mut sleep => return Ok(()),
+            message = spectator.ws.next() => check_spectator_message(message)?,
+            () = spectator.shutdown.notified() => return Err(SpectatingError::ServerShutdown),
+        }
+    }
+}
+
+/// Check that a message received from a spectator is a ping or a pong.
+fn check_spectator_message(
+    message: Option<Result<Message, tungstenite::Error>>,
+) -> Result<(), SpectatingError> {
+    match message {
+        Some(Ok(Message::Ping(_) | Message::Pong(_))) => Ok(()),
+        Some(Ok(Message::Close(_))) | None | Some(Err(tungstenite::Error::ConnectionClosed)) => {
+            Err(SpectatingError::Left)
+        }
+        Some(Ok(message)) => Err(SpectatingError::ProtocolViolation(message)),
+        Some(Err(e)) => Err(SpectatingError::ConnectionError(Box::new(e))),
+    }
+}
@@ -1,22 +1,46 @@
 //! Encapsulation of a game state, and computation of its evolution.
 
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite;
 
-pub(super) use done::GameResult;
+pub(super) use done::{GameResult, WinType};
+pub use post_game::PostGameChoice;
+use post_game::PostGameOutcome;
+use running::Interrupted;
 pub(super) use running::RunningState;
 
-use super::Player;
+use super::ai::Ai;
+use super::{Difficulty, GameRules, LiveGames, Player, Reconnections, Side};
 
 mod done;
+mod post_game;
 mod running;
 mod startup;
 
+/// Reasons for a stage not to lead to the next one.
+#[allow(clippy::large_enum_variant, reason = "the player is handed back")]
+pub(super) enum StageError<S> {
+    /// A client disconnected during the startup period of a game between two clients. The other player is returned.
+    Disconnected(tungstenite::Error, Player<S>),
+    /// The only client of the game disconnected during the startup period.
+    Lost(tungstenite::Error),
+    /// The server started shutting down. The connections have been closed.
+    ServerShutdown,
+}
+
+impl<S> From<Interrupted> for StageError<S> {
+    fn from(_: Interrupted) -> Self {
+        StageError::ServerShutdown
+    }
+}
+
 /// Current state - or stage - of a game mode 0 game.
 pub(super) enum Game0State {
     Startup,
     Running(RunningState),
     Done(GameResult),
+    /// The post-game phase is over without a rematch. Players that went back into the queue are still connected.
+    Over([PostGameChoice; 2]),
 }
 
 impl Game0State {
@@ -25,37 +49,57 @@ impl Game0State {
         Self::Startup
     }
 
-    /// Try to complete the current stage to get to the next one and return it.
+    /// Try to complete the current stage to get to the next one and return it. The game is played with `rules`.
     ///
     /// # Error
     ///
-    /// Only fails with an error if there's a disconnection during the startup period. Later disconnections are not
-    /// errors, they are withdrawals.
-    /// The returned error is paired with the websocket that didn't fail.
+    /// Fails with an error if there's a disconnection during the startup period, or if the server shuts down. Later
+    /// disconnections are not errors, they pause the game until the player reconnects or withdraws, or - once the game
+    /// is done - they end the post-game phase for that player.
+    /// A disconnection error is paired with the websocket that didn't fail.
     pub(super) async fn next_state<S>(
         self,
         mut left_player: Player<S>,
         mut right_player: Player<S>,
-    ) -> Result<(Self, Player<S>, Player<S>), (tokio_tungstenite::tungstenite::Error, Player<S>)>
+        reconnections: &Reconnections<S>,
+        live_games: &LiveGames,
+        rules: GameRules,
+    ) -> Result<(Self, Player<S>, Player<S>), StageError<S>>
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
         match self {
             Self::Startup => {
                 (left_player, right_player) =
-                    startup::wait_game_0_start(left_player, right_player).await?;
+                    startup::wait_game_0_start(left_player, right_player, rules).await?;
                 Ok((
-                    Self::Running(RunningState::new(&mut rand::thread_rng())),
+                    Self::Running(RunningState::new(&mut rand::thread_rng(), rules)),
                     left_player,
                     right_player,
                 ))
             }
             Self::Running(rs) => {
-                let game_result =
-                    running::run_game_0_loop(&mut left_player.ws, &mut right_player.ws, rs).await;
+                let game_result = running::run_game_0_loop(
+                    &mut left_player,
+                    &mut right_player,
+                    rs,
+                    reconnections,
+                    live_games,
+                )
+                .await?;
                 Ok((Self::Done(game_result), left_player, right_player))
             }
-            Self::Done(d) => Ok((Self::Done(d), left_player, right_player)),
+            Self::Done(_) => {
+                let next_state =
+                    match post_game::run_post_game_phase(&mut left_player, &mut right_player)
+                        .await?
+                    {
+                        PostGameOutcome::Rematch => Self::Startup,
+                        PostGameOutcome::Over(choices) => Self::Over(choices),
+                    };
+                Ok((next_state, left_player, right_player))
+            }
+            Self::Over(choices) => Ok((Self::Over(choices), left_player, right_player)),
         }
     }
 }
@@ -64,7 +108,8 @@ impl Game0State {
 pub(super) enum Game1State {
     Startup,
     Running(RunningState),
-    Done,
+    /// The game is over. There is no result if the client left before the end.
+    Done(Option<GameResult>),
 }
 
 impl Game1State {
@@ -73,27 +118,82 @@ impl Game1State {
         Self::Startup
     }
 
-    /// Try to complete the current stage, getting to the next one and returning it.
+    /// Try to complete the current stage, getting to the next one and returning it. The game is played with `rules`.
+    ///
+    /// # Error
+    ///
+    /// Fails with an error if the client disconnects during the startup period, or if the server shuts down.
     pub(super) async fn next_state<S>(
         self,
-        mut connection: WebSocketStream<S>,
-    ) -> Result<(Self, WebSocketStream<S>), tokio_tungstenite::tungstenite::Error>
+        mut player: Player<S>,
+        rules: GameRules,
+    ) -> Result<(Self, Player<S>), StageError<S>>
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
         match self {
             Self::Startup => {
-                connection = startup::wait_game_1_start(connection).await?;
+                player = startup::wait_game_1_start(player, rules).await?;
                 Ok((
-                    Self::Running(RunningState::new(&mut rand::thread_rng())),
-                    connection,
+                    Self::Running(RunningState::new(&mut rand::thread_rng(), rules)),
+                    player,
                 ))
             }
             Self::Running(rs) => {
-                running::run_game_1_loop(&mut connection, rs).await;
-                Ok((Self::Done, connection))
+                let game_result = running::run_game_1_loop(&mut player, rs).await?;
+                Ok((Self::Done(game_result), player))
+            }
+            Self::Done(d) => Ok((Self::Done(d), player)),
+        }
+    }
+}
+
+/// Current state - or stage - of a game mode 4 game.
+pub(super) enum Game4State {
+    Startup,
+    Running(RunningState),
+    Done(GameResult),
+}
+
+impl Game4State {
+    /// Create a new game (mode 4) state at the initial stage of startup.
+    pub(super) fn new() -> Self {
+        Self::Startup
+    }
+
+    /// Try to complete the current stage, getting to the next one and returning it. The player plays on `player_side`
+    /// against an AI going by `ai_id`, with `rules`.
+    ///
+    /// # Error
+    ///
+    /// Fails with an error if the client disconnects during the startup period, or if the server shuts down. Later
+    /// disconnections are withdrawals.
+    pub(super) async fn next_state<S>(
+        self,
+        mut player: Player<S>,
+        player_side: Side,
+        ai_id: &str,
+        difficulty: Difficulty,
+        rules: GameRules,
+    ) -> Result<(Self, Player<S>), StageError<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match self {
+            Self::Startup => {
+                player = startup::wait_game_4_start(player, player_side, ai_id, rules).await?;
+                Ok((
+                    Self::Running(RunningState::new(&mut rand::thread_rng(), rules)),
+                    player,
+                ))
+            }
+            Self::Running(rs) => {
+                let ai = Ai::new(!player_side, difficulty, rules);
+                let game_result =
+                    running::run_game_4_loop(&mut player, player_side, rs, ai).await?;
+                Ok((Self::Done(game_result), player))
             }
-            Self::Done => Ok((Self::Done, connection)),
+            Self::Done(d) => Ok((Self::Done(d), player)),
         }
     }
 }
@@ -18,10 +18,30 @@ impl GameResult {
 }
 
 /// Reason for the victory of a player.
+///
+/// Conversions to the code stored in the database are implemented below.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum WinType {
     /// The winner reached the number of points tha make him win the game according to the rules.
     ScoreReached,
 
     /// The winner's opponent disconnected, effectively withdrawing.
     Withdrawal,
+
+    /// The winner led on score when the game clock ran out.
+    TimeUp,
+
+    /// The score was tied when the game clock ran out, and the winner scored the golden point of sudden death.
+    SuddenDeath,
+}
+
+impl From<WinType> for i16 {
+    fn from(value: WinType) -> Self {
+        match value {
+            WinType::ScoreReached => 0,
+            WinType::Withdrawal => 1,
+            WinType::TimeUp => 2,
+            WinType::SuddenDeath => 3,
+        }
+    }
 }
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+use crate::game::{Player, Side};
+use crate::protocol::{
+    parse_post_game_message, CloseReason, PostGameRequest, PostGameStatus, PostGameStatusMessage,
+};
+
+use super::running::Interrupted;
+
+/// Time given to the players to choose what to do once their game is over.
+const POST_GAME_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// What a player chose to do once its game is over.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PostGameChoice {
+    /// Go back into the match-making queue.
+    Requeue,
+    /// Leave. The connection has been closed.
+    Quit,
+}
+
+/// Outcome of the post-game phase.
+pub(super) enum PostGameOutcome {
+    /// Both players accepted a rematch.
+    Rematch,
+    /// The players parted ways, each with its own choice, left then right.
+    Over([PostGameChoice; 2]),
+}
+
+/// Where a player stands during the post-game phase.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Stance {
+    Undecided,
+    WantsRematch,
+    Decided(PostGameChoice),
+}
+
+/// Let the players ask for a rematch or to go back into the queue, until both are decided or [`POST_GAME_TIMEOUT`]
+/// elapses. Each player is told what its opponent does. Players that don't decide in time are let go, as are right away
+/// the players whose version of the protocol has no post-game phase.
+pub(super) async fn run_post_game_phase<S>(
+    left_player: &mut Player<S>,
+    right_player: &mut Player<S>,
+) -> Result<PostGameOutcome, Interrupted>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let deadline = Instant::now() + POST_GAME_TIMEOUT;
+    let mut stances = [Stance::Undecided; 2];
+    for (stance, player) in stances
+        .iter_mut()
+        .zip([&mut *left_player, &mut *right_player])
+    {
+        if !player.version().has_post_game() {
+            player.close(CloseReason::Done).await;
+            *stance = Stance::Decided(PostGameChoice::Quit);
+        }
+    }
+    for (stance, opponent_stance, opponent) in [
+        (stances[0], stances[1], &mut *right_player),
+        (stances[1], stances[0], &mut *left_player),
+    ] {
+        if is_decided(stance) && !is_decided(opponent_stance) {
+            let message = PostGameStatusMessage::new(PostGameStatus::OpponentGone);
+            let _: Result<_, _> = opponent.send(message).await;
+        }
+    }
+    if stances.iter().all(|stance| is_decided(*stance)) {
+        return Ok(PostGameOutcome::Over(choices(stances)));
+    }
+    loop {
+        let (side, request) = tokio::select! {
+            msg = left_player.ws.next(), if !is_decided(stances[0]) => {
+                (Side::Left, parse_post_game_message(msg))
+            },
+            msg = right_player.ws.next(), if !is_decided(stances[1]) => {
+                (Side::Right, parse_post_game_message(msg))
+            },
+            _ = tokio::time::sleep_until(deadline) => {
+                for (stance, player) in stances.iter_mut().zip([&mut *left_player, &mut *right_player]) {
+                    if !is_decided(*stance) {
+                        player.close(CloseReason::Done).await;
+                        *stance = Stance::Decided(PostGameChoice::Quit);
+                    }
+                }
+                return Ok(PostGameOutcome::Over(choices(stances)));
+            },
+            () = left_player.shutdown.notified() => {
+                for (stance, player) in stances.iter().zip([&mut *left_player, &mut *right_player]) {
+                    if *stance != Stance::Decided(PostGameChoice::Quit) {
+                        player.close(CloseReason::ServerShutdown).await;
+                    }
+                }
+                return Err(Interrupted);
+            },
+        };
+        let (player, opponent) = match side {
+            Side::Left => (&mut *left_player, &mut *right_player),
+            Side::Right => (&mut *right_player, &mut *left_player),
+        };
+        let (stance, opponent_stance) = match side {
+            Side::Left => (stances[0], stances[1]),
+            Side::Right => (stances[1], stances[0]),
+        };
+        let new_stance = match request {
+            Ok(None) => stance,
+            Ok(Some(PostGameRequest::Rematch)) => {
+                if opponent_stance == Stance::WantsRematch {
+                    return Ok(PostGameOutcome::Rematch);
+                }
+                if opponent_stance == Stance::Undecided {
+                    let message = PostGameStatusMessage::new(PostGameStatus::RematchRequested);
+                    let _: Result<_, _> = opponent.send(message).await;
+                }
+                Stance::WantsRematch
+            }
+            Ok(Some(PostGameRequest::Requeue)) => Stance::Decided(PostGameChoice::Requeue),
+            Err(e) => {
+                if let Some(reason) = e.close_reason() {
+                    player.close(reason).await;
+                }
+                Stance::Decided(PostGameChoice::Quit)
+            }
+        };
+        if is_decided(new_stance) && !is_decided(opponent_stance) {
+            let message = PostGameStatusMessage::new(PostGameStatus::OpponentGone);
+            let _: Result<_, _> = opponent.send(message).await;
+        }
+        stances[u8::from(side) as usize] = new_stance;
+        if stances.iter().all(|stance| is_decided(*stance)) {
+            return Ok(PostGameOutcome::Over(choices(stances)));
+        }
+    }
+}
+
+/// Whether the player is done with the post-game phase.
+fn is_decided(stance: Stance) -> bool {
+    matches!(stance, Stance::Decided(_))
+}
+
+/// The choices of the players, once they are all decided.
+fn choices(stances: [Stance; 2]) -> [PostGameChoice; 2] {
+    stances.map(|stance| match stance {
+        Stance::Decided(choice) => choice,
+        Stance::Undecided | Stance::WantsRematch => {
+            unreachable!("The post-game phase ends with all the players decided.")
+        }
+    })
+}
@@ -1,31 +1,36 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
 use rand::Rng;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::time::Instant;
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::WebSocketStream;
 
-use crate::game::combined_send::CombinedSend;
+use crate::game::ai::Ai;
+use crate::game::clock::{self, GameClock};
 use crate::game::engine::{
     bounce_off_horizontal_edges, bounce_off_pads, side_of_ball_collision_with_wall,
     ServiceGenerator,
 };
-use crate::game::Side;
+use crate::game::{send_to_both, GameRules, LiveGames, Player, Reconnections, Side};
 use crate::protocol::constants::{
-    BALL_MOVEMENT_PER_TICK, BALL_RADIUS, MAX_CLIENT_UPDATES_PER_SECOND, PAD_HEIGHT,
-    PAD_MOVEMENT_PER_TICK, RATIO, TICKS_PER_SECOND,
+    BALL_RADIUS, MAX_CLIENT_UPDATES_PER_SECOND, PAD_MOVEMENT_PER_TICK, RATIO, SERVE_TIMEOUT_TICKS,
+    TICKS_PER_SECOND,
 };
 use crate::protocol::{
-    parse_gm0_input_message, parse_gm1_input_message, GameAbortedMessage, GameCompletedMessage,
-    PointScoredMessage, PositionUpdateMessage, ServerToClientMessage,
+    parse_gm0_input_message, parse_gm1_input_message, ClientInput, CloseReason, GameAbortedMessage,
+    GameCompletedMessage, GameResumeMessage, OpponentConnectionMessage, OpponentConnectionStatus,
+    PointScoredMessage, PositionUpdateMessage, ProtocolVersion, ServeMessage,
+    ServerToClientMessage,
 };
 
 use super::done::WinType;
 use super::GameResult;
 
-/// This structure encapsulates the Pong game state : elements, score and service side.
+/// This structure encapsulates the Pong game state : elements, score and service side, along with the rules the game is
+/// played with and its clock if it is time-limited.
+///
+/// Each service starts with a serving phase : the ball waits at the center until the serving side serves it, or for
+/// [`SERVE_TIMEOUT_TICKS`] at most. The pads can move in the meantime.
 #[derive(Clone)]
 pub struct RunningState {
     ball_x: f64,
@@ -36,25 +41,55 @@ pub struct RunningState {
     service_side: Side,
     service_generator: ServiceGenerator,
     scores: [u32; 2],
+    rules: GameRules,
+    clock: Option<GameClock>,
+    /// The ticks the ball has waited for its serve, while it does.
+    serve_wait: Option<u64>,
 }
 
-/// Run a game loop using [`RunningState`] until either the remote game is completed or a client disconnects.
-/// The latter is not a program error - it is simply handled as a [`WinType::Withdrawal`].
+/// Delay between the reconnection of a player and the game resuming.
+const RESUME_DELAY: Duration = Duration::from_secs(3);
+
+/// The game loop was interrupted by the server shutdown. The connections have been closed.
+pub(super) struct Interrupted;
+
+/// Run a game loop using [`RunningState`] until either the remote game is completed or a client leaves.
+///
+/// A client losing its connection pauses the game, until it reconnects within the reconnection window, if its version
+/// of the protocol lets it resume the game. A client that doesn't come back, closes its connection or breaks the
+/// protocol withdraws. That is not a program error - it is simply handled as a [`WinType::Withdrawal`]. A client
+/// breaking the protocol is told so before its connection is closed.
+///
+/// The game is listed in the [`LiveGames`] while it runs, and the messages sent to both players are broadcast to its
+/// spectators. They are told the winner at the end, whatever the way the game is won.
+///
+/// Each player serves the ball when its side is serving, or has it served right away if its version of the protocol has
+/// no serve. Once a game paused during a serving phase resumes, the serving side is given its whole time to serve
+/// again.
 pub(super) async fn run_game_0_loop<S>(
-    pl_ws: &mut WebSocketStream<S>,
-    pr_ws: &mut WebSocketStream<S>,
+    left_player: &mut Player<S>,
+    right_player: &mut Player<S>,
     mut rs: RunningState,
-) -> GameResult
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+) -> Result<GameResult, Interrupted>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     let (mut l_pad_dy, mut r_pad_dy) = (0, 0);
+    let feed = live_games.open([&left_player.id, &right_player.id], rs.rules);
 
     let mut tick_interval = tokio::time::interval(Duration::from_millis(1000 / TICKS_PER_SECOND));
 
     let game_result = loop {
-        tokio::select! {
+        // The side of the player gone, if any, and whether it may come back.
+        let (left_version, right_version) = (left_player.version(), right_player.version());
+        let absence = tokio::select! {
             _ = tick_interval.tick() => {
+                rs.serve_for_client(|side| match side {
+                    Side::Left => left_version,
+                    Side::Right => right_version,
+                });
                 let (next_state, message) = rs.update_on_tick(
                     &mut rand::thread_rng(),
                     l_pad_dy.into(),
@@ -64,36 +99,194 @@ where
                     UpdateOutcome::Continue(rs) => rs,
                     UpdateOutcome::Done(res) => break res,
                 };
-                if let Err((_, side)) = CombinedSend::new(
-                    pl_ws, pr_ws, Message::Binary(message.into())
-                ).await {
-                    break GameResult::new(rs.end_game(), !side, WinType::Withdrawal);
-                }
+                feed.send(message.clone(), rs.scores);
+                send_to_both(left_player, right_player, message)
+                    .await
+                    .err()
+                    .map(|(_, side)| (side, true))
             }
-            first_msg = pl_ws.next() => {
-                l_pad_dy = match parse_gm0_input_message(first_msg) {
-                    Ok(new_dy) => new_dy.unwrap_or(l_pad_dy),
-                    Err(_) => break GameResult::new(
-                        rs.end_game(), Side::Right, WinType::Withdrawal
-                    ),
-                };
+            first_msg = left_player.ws.next() => match parse_gm0_input_message(first_msg, left_version) {
+                Ok(input) => {
+                    match input {
+                        Some(ClientInput::Movement(new_dy)) => l_pad_dy = new_dy,
+                        Some(ClientInput::Serve) => rs.serve(Side::Left),
+                        None => {}
+                    }
+                    None
+                }
+                Err(e) => {
+                    if let Some(reason) = e.close_reason() {
+                        left_player.close(reason).await;
+                    }
+                    Some((Side::Left, e.is_connection_loss()))
+                }
+            },
+            executor_msg = right_player.ws.next() => match parse_gm0_input_message(executor_msg, right_version) {
+                Ok(input) => {
+                    match input {
+                        Some(ClientInput::Movement(new_dy)) => r_pad_dy = new_dy,
+                        Some(ClientInput::Serve) => rs.serve(Side::Right),
+                        None => {}
+                    }
+                    None
+                }
+                Err(e) => {
+                    if let Some(reason) = e.close_reason() {
+                        right_player.close(reason).await;
+                    }
+                    Some((Side::Right, e.is_connection_loss()))
+                }
+            },
+            () = left_player.shutdown.notified() => {
+                left_player.close(CloseReason::ServerShutdown).await;
+                right_player.close(CloseReason::ServerShutdown).await;
+                return Err(Interrupted);
             }
-            executor_msg = pr_ws.next() => {
-                r_pad_dy = match parse_gm0_input_message(executor_msg) {
-                    Ok(new_dy) => new_dy.unwrap_or(r_pad_dy),
-                    Err(_) => break GameResult::new(
-                        rs.end_game(), Side::Left, WinType::Withdrawal
-                    ),
-                };
+        };
+        if let Some((side, may_come_back)) = absence {
+            let absent_version = match side {
+                Side::Left => left_player.version(),
+                Side::Right => right_player.version(),
+            };
+            let outcome = if may_come_back && absent_version.resumes_games() {
+                pause_for_reconnection(left_player, right_player, side, &rs, reconnections).await
+            } else {
+                PauseOutcome::Withdrawal
+            };
+            match outcome {
+                PauseOutcome::Resumed => {
+                    match side {
+                        Side::Left => l_pad_dy = 0,
+                        Side::Right => r_pad_dy = 0,
+                    }
+                    rs.renew_serve();
+                    tick_interval.reset();
+                }
+                PauseOutcome::Withdrawal => {
+                    break GameResult::new(rs.end_game(), !side, WinType::Withdrawal)
+                }
+                PauseOutcome::Interrupted => return Err(Interrupted),
             }
         }
     };
-    send_result_message(pl_ws, pr_ws, &game_result).await;
-    game_result
+    send_result_message(left_player, right_player, &game_result).await;
+    feed.send(
+        GameCompletedMessage::new(game_result.winner),
+        game_result.score,
+    );
+    Ok(game_result)
 }
 
-/// Run a game loop using [`RunningState`] until the game is completed or the client disconnects.
-pub(super) async fn run_game_1_loop<S>(connection: &mut WebSocketStream<S>, mut rs: RunningState)
+/// Outcome of the pause of a game whose player lost its connection.
+enum PauseOutcome {
+    /// The player reconnected, the game goes on.
+    Resumed,
+    /// The player didn't come back in time, or the other player left in the meantime : the former withdraws.
+    Withdrawal,
+    /// The server is shutting down. The connections have been closed.
+    Interrupted,
+}
+
+/// Pause the game until the absent player reconnects within the reconnection window. The present player is told about
+/// the disconnection, then about the reconnection. The reconnected player is resynchronized with the whole game state,
+/// and the game resumes after [`RESUME_DELAY`].
+async fn pause_for_reconnection<S>(
+    left_player: &mut Player<S>,
+    right_player: &mut Player<S>,
+    absent_side: Side,
+    rs: &RunningState,
+    reconnections: &Reconnections<S>,
+) -> PauseOutcome
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (absent, present) = match absent_side {
+        Side::Left => (left_player, right_player),
+        Side::Right => (right_player, left_player),
+    };
+    // The lost connection may not have been noticed by its stream yet : its session is ended for the user to be able to
+    // open the one it resumes the game with.
+    absent.session.end();
+    let Some(mut receiver) = reconnections.register(&absent.id) else {
+        return PauseOutcome::Withdrawal;
+    };
+    let deadline = Instant::now() + reconnections.window();
+    let message = OpponentConnectionMessage::new(
+        OpponentConnectionStatus::Disconnected,
+        SystemTime::now() + reconnections.window(),
+    );
+    let _: Result<_, _> = present.send(message).await;
+
+    let reconnected = loop {
+        tokio::select! {
+            receive_result = &mut receiver => break receive_result.ok(),
+            _ = tokio::time::sleep_until(deadline) => {
+                reconnections.unregister(&absent.id);
+                // The player may have been handed over right before the unregistration.
+                break receiver.try_recv().ok();
+            },
+            msg = present.ws.next() => if let Err(e) = parse_gm0_input_message(msg, present.version()) {
+                reconnections.unregister(&absent.id);
+                if let Some(reason) = e.close_reason() {
+                    present.close(reason).await;
+                }
+                if let Ok(mut late_player) = receiver.try_recv() {
+                    late_player.close(CloseReason::Done).await;
+                }
+                return PauseOutcome::Withdrawal;
+            },
+            () = present.shutdown.notified() => {
+                reconnections.unregister(&absent.id);
+                present.close(CloseReason::ServerShutdown).await;
+                if let Ok(mut late_player) = receiver.try_recv() {
+                    late_player.close(CloseReason::ServerShutdown).await;
+                }
+                return PauseOutcome::Interrupted;
+            },
+        }
+    };
+    let Some(reconnected) = reconnected else {
+        return PauseOutcome::Withdrawal;
+    };
+    *absent = reconnected;
+
+    let resuming_time = SystemTime::now() + RESUME_DELAY;
+    let resuming_instant = Instant::now() + RESUME_DELAY;
+    let resync = GameResumeMessage::new(
+        &present.id,
+        absent_side,
+        rs.scores,
+        rs.l_pad_y,
+        rs.r_pad_y,
+        rs.ball_x,
+        rs.ball_y,
+        resuming_time,
+        rs.rules,
+    );
+    if absent.send(resync).await.is_err() {
+        return PauseOutcome::Withdrawal;
+    }
+    let message =
+        OpponentConnectionMessage::new(OpponentConnectionStatus::Reconnected, resuming_time);
+    let _: Result<_, _> = present.send(message).await;
+    tokio::select! {
+        _ = tokio::time::sleep_until(resuming_instant) => PauseOutcome::Resumed,
+        () = present.shutdown.notified() => {
+            present.close(CloseReason::ServerShutdown).await;
+            absent.close(CloseReason::ServerShutdown).await;
+            PauseOutcome::Interrupted
+        },
+    }
+}
+
+/// Run a game loop using [`RunningState`] until the game is completed or the client disconnects. The client serves the
+/// ball for whichever side is serving, or has it served right away if its version of the protocol has no serve. A
+/// client breaking the protocol is told so before its connection is closed.
+/// Returns the result of the game, or [`None`] if the client left.
+pub(super) async fn run_game_1_loop<S>(
+    player: &mut Player<S>,
+    mut rs: RunningState,
+) -> Result<Option<GameResult>, Interrupted>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
@@ -106,6 +299,7 @@ where
     let game_result = loop {
         tokio::select! {
             _ = tick_interval.tick() => {
+                rs.serve_for_client(|_| player.version());
                 let (next_state, message) = rs.update_on_tick(
                     &mut rand::thread_rng(),
                     l_pad_dy.into(),
@@ -115,29 +309,129 @@ where
                     UpdateOutcome::Continue(rs) => rs,
                     UpdateOutcome::Done(res) => break res,
                 };
-                if let Err(_) = connection.send(Message::Binary(message.into())).await {
-                    return;
+                if player.send(message).await.is_err() {
+                    return Ok(None);
                 }
             }
             _ = to.tick(), if to_active => {
                 to_active = false;
             }
-            msg = connection.next(), if !to_active => {
+            msg = player.ws.next(), if !to_active => {
                 to = interval_for_next_to();
-                (l_pad_dy, r_pad_dy) = match parse_gm1_input_message(msg) {
-                    Ok(Some((left_movement, right_movement))) => (left_movement, right_movement),
+                (l_pad_dy, r_pad_dy) = match parse_gm1_input_message(msg, player.version()) {
+                    Ok(Some(ClientInput::Movement(movements))) => movements,
+                    Ok(Some(ClientInput::Serve)) => {
+                        if let Some((serving_side, _)) = rs.waiting_serve() {
+                            rs.serve(serving_side);
+                        }
+                        (l_pad_dy, r_pad_dy)
+                    }
                     Ok(None) => (l_pad_dy, r_pad_dy),
-                    Err(_) => return,
+                    Err(e) => {
+                        if let Some(reason) = e.close_reason() {
+                            player.close(reason).await;
+                        }
+                        return Ok(None);
+                    }
                 };
                 to_active = true;
             }
+            () = player.shutdown.notified() => {
+                player.close(CloseReason::ServerShutdown).await;
+                return Err(Interrupted);
+            }
         }
     };
-    let _: Result<_, _> = connection
-        .send(Message::Binary(
-            GameCompletedMessage::new(game_result.winner).into(),
-        ))
+    let _: Result<_, _> = player
+        .send(GameCompletedMessage::new(game_result.winner))
         .await;
+    Ok(Some(game_result))
+}
+
+/// Run a game loop using [`RunningState`] until either the game against the [`Ai`] is completed or the client leaves.
+///
+/// The client drives the pad of `player_side`, the AI the other one, each serving the ball for its side as in a remote
+/// game. A client losing its connection, closing it or breaking the protocol withdraws. A client breaking the protocol
+/// is told so before its connection is closed.
+pub(super) async fn run_game_4_loop<S>(
+    player: &mut Player<S>,
+    player_side: Side,
+    mut rs: RunningState,
+    mut ai: Ai,
+) -> Result<GameResult, Interrupted>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut player_dy = 0;
+
+    let mut tick_interval = tokio::time::interval(Duration::from_millis(1000 / TICKS_PER_SECOND));
+
+    let withdrawn = loop {
+        tokio::select! {
+            _ = tick_interval.tick() => {
+                if let Some((serving_side, waited_ticks)) = rs.waiting_serve() {
+                    let served = if serving_side == player_side {
+                        !player.version().serves()
+                    } else {
+                        ai.serves(waited_ticks)
+                    };
+                    if served {
+                        rs.serve(serving_side);
+                    }
+                }
+                let ai_pad_y = match player_side {
+                    Side::Left => rs.r_pad_y,
+                    Side::Right => rs.l_pad_y,
+                };
+                let ai_dy = ai.next_movement(
+                    &mut rand::thread_rng(),
+                    rs.ball_x,
+                    rs.ball_y,
+                    rs.angle,
+                    ai_pad_y,
+                );
+                let (l_pad_dy, r_pad_dy) = match player_side {
+                    Side::Left => (player_dy, ai_dy),
+                    Side::Right => (ai_dy, player_dy),
+                };
+                let (next_state, message) = rs.update_on_tick(
+                    &mut rand::thread_rng(),
+                    l_pad_dy.into(),
+                    r_pad_dy.into(),
+                );
+                rs = match next_state {
+                    UpdateOutcome::Continue(rs) => rs,
+                    UpdateOutcome::Done(res) => {
+                        let _: Result<_, _> = player.send(message).await;
+                        return Ok(res);
+                    }
+                };
+                if player.send(message).await.is_err() {
+                    break rs;
+                }
+            }
+            msg = player.ws.next() => match parse_gm0_input_message(msg, player.version()) {
+                Ok(Some(ClientInput::Movement(new_dy))) => player_dy = new_dy,
+                Ok(Some(ClientInput::Serve)) => rs.serve(player_side),
+                Ok(None) => {}
+                Err(e) => {
+                    if let Some(reason) = e.close_reason() {
+                        player.close(reason).await;
+                    }
+                    break rs;
+                }
+            },
+            () = player.shutdown.notified() => {
+                player.close(CloseReason::ServerShutdown).await;
+                return Err(Interrupted);
+            }
+        }
+    };
+    Ok(GameResult::new(
+        withdrawn.end_game(),
+        !player_side,
+        WinType::Withdrawal,
+    ))
 }
 
 /// Make an interval that will tick at the moment the timeout is lifted.
@@ -150,27 +444,23 @@ fn interval_for_next_to() -> tokio::time::Interval {
 
 /// Send the appropriate end-of-game message to the appropriate client(s).
 async fn send_result_message<S>(
-    pl_ws: &mut WebSocketStream<S>,
-    pr_ws: &mut WebSocketStream<S>,
+    left_player: &mut Player<S>,
+    right_player: &mut Player<S>,
     game_result: &GameResult,
 ) where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     match game_result.win_type {
-        WinType::ScoreReached => {
-            let bytes = Vec::from(GameCompletedMessage::new(game_result.winner));
-            let _: Result<_, _> = tokio::try_join!(
-                pl_ws.send(Message::Binary(bytes.clone())),
-                pr_ws.send(Message::Binary(bytes)),
-            );
+        WinType::ScoreReached | WinType::TimeUp | WinType::SuddenDeath => {
+            let message = GameCompletedMessage::new(game_result.winner);
+            let _: Result<_, _> = send_to_both(left_player, right_player, message).await;
         }
         WinType::Withdrawal => {
-            let bytes = GameAbortedMessage::new().into();
             let _: Result<_, _> = match game_result.winner {
-                Side::Left => pl_ws,
-                Side::Right => pr_ws,
+                Side::Left => left_player,
+                Side::Right => right_player,
             }
-            .send(Message::Binary(bytes))
+            .send(GameAbortedMessage)
             .await;
         }
     }
@@ -186,19 +476,22 @@ impl RunningState {
     const INITIAL_BALL_Y: f64 = 1.0 / 2.0 - BALL_RADIUS;
 
     /// Creates a [`RunningState`] instance with the elements positioned for game start, scores set at 0 and a random
-    /// side for the first service.
-    pub(super) fn new<R: Rng + ?Sized>(rng: &mut R) -> RunningState {
+    /// side for the first service, waiting for its serve. The game is played with the given rules.
+    pub(super) fn new<R: Rng + ?Sized>(rng: &mut R, rules: GameRules) -> RunningState {
         let initial_side = rng.gen();
         let service_generator = ServiceGenerator::new();
         RunningState {
             ball_x: Self::INITIAL_BALL_X,
             ball_y: Self::INITIAL_BALL_Y,
             angle: service_generator.gen_angle(initial_side, rng),
-            l_pad_y: (1.0 - PAD_HEIGHT) / 2.0,
-            r_pad_y: (1.0 - PAD_HEIGHT) / 2.0,
+            l_pad_y: (1.0 - rules.pad_height) / 2.0,
+            r_pad_y: (1.0 - rules.pad_height) / 2.0,
             service_side: initial_side,
             service_generator,
             scores: [0, 0],
+            rules,
+            clock: rules.time_limit.map(GameClock::new),
+            serve_wait: Some(0),
         }
     }
 
@@ -207,27 +500,63 @@ impl RunningState {
         self.scores
     }
 
-    /// Updates the elements' positions by their movement per tick. No collision check is done here.
+    /// The serving side and the ticks the ball has waited for its serve, if it does.
+    fn waiting_serve(&self) -> Option<(Side, u64)> {
+        self.serve_wait.map(|waited| (self.service_side, waited))
+    }
+
+    /// Serve the ball, if the given side is serving. A serve before the serving side was announced is ignored.
+    fn serve(&mut self, side: Side) {
+        if side == self.service_side && self.serve_wait.is_some_and(|waited| waited > 0) {
+            self.serve_wait = None;
+        }
+    }
+
+    /// Serve the ball right away for the serving side if the client driving it, of the given version of the protocol,
+    /// has no serve message. The ball is served once the serving side has been announced.
+    fn serve_for_client(&mut self, client_version: impl FnOnce(Side) -> ProtocolVersion) {
+        if let Some((serving_side, _)) = self.waiting_serve() {
+            if !client_version(serving_side).serves() {
+                self.serve(serving_side);
+            }
+        }
+    }
+
+    /// Give the serving side its whole time to serve again, if the ball waits for its serve. The serving side is then
+    /// announced again.
+    fn renew_serve(&mut self) {
+        if self.serve_wait.is_some() {
+            self.serve_wait = Some(0);
+        }
+    }
+
+    /// Updates the elements' positions by their movement per tick, the ball staying still while it waits for its serve.
+    /// No collision check is done here.
     fn move_elements(&mut self, l_pad_dy: f64, r_pad_dy: f64) {
         self.l_pad_y = f64::clamp(
             self.l_pad_y + l_pad_dy * PAD_MOVEMENT_PER_TICK,
             0.0,
-            1.0 - PAD_HEIGHT,
+            1.0 - self.rules.pad_height,
         );
         self.r_pad_y = f64::clamp(
             self.r_pad_y + r_pad_dy * PAD_MOVEMENT_PER_TICK,
             0.0,
-            1.0 - PAD_HEIGHT,
+            1.0 - self.rules.pad_height,
         );
-        self.ball_x += BALL_MOVEMENT_PER_TICK * f64::cos(self.angle);
-        self.ball_y -= BALL_MOVEMENT_PER_TICK * f64::sin(self.angle);
+        if self.serve_wait.is_none() {
+            let ball_movement = self.rules.ball_movement_per_tick();
+            self.ball_x += ball_movement * f64::cos(self.angle);
+            self.ball_y -= ball_movement * f64::sin(self.angle);
+        }
     }
 
-    /// Sets the elements positions' back at their appropriate initial positions.
+    /// Sets the elements positions' back at their appropriate initial positions, the ball waiting for the serve of the
+    /// other side.
     fn reset_elements<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         (self.ball_x, self.ball_y) = (Self::INITIAL_BALL_X, Self::INITIAL_BALL_Y);
         self.service_side = !self.service_side;
         self.angle = self.service_generator.gen_angle(self.service_side, rng);
+        self.serve_wait = Some(0);
     }
 
     fn update_on_tick<R>(
@@ -240,6 +569,24 @@ impl RunningState {
         Self: Sized,
         R: Rng + ?Sized,
     {
+        //Run the clock down, the game being decided at the buzzer unless the score is tied
+        if self.clock.as_mut().is_some_and(GameClock::tick) {
+            if let Some(leader) = clock::leader(self.scores) {
+                let message = GameCompletedMessage::new(leader);
+                return (
+                    UpdateOutcome::Done(GameResult::new(self.scores, leader, WinType::TimeUp)),
+                    ServerToClientMessage::GameDone(message),
+                );
+            }
+        }
+
+        //Serve automatically once the serving side is out of time, announcing the serving side first
+        let announce_serve = self.serve_wait == Some(0);
+        self.serve_wait = match self.serve_wait {
+            Some(waited) if waited + 1 < SERVE_TIMEOUT_TICKS => Some(waited + 1),
+            _ => None,
+        };
+
         //Update the state
         self.move_elements(l_pad_dy, r_pad_dy);
 
@@ -250,14 +597,18 @@ impl RunningState {
             self.angle,
             self.l_pad_y,
             self.r_pad_y,
+            &self.rules,
         );
 
         if let Some(out_side) = side_of_ball_collision_with_wall(self.ball_x) {
             //Ball is out, update scores
             let win_side = !out_side;
             self.scores[u8::from(win_side) as usize] += 1;
-            if self.scores[u8::from(win_side) as usize] != 10 {
-                //10 points not reached, play another round
+            let score = self.scores[u8::from(win_side) as usize];
+            let opponent_score = self.scores[u8::from(out_side) as usize];
+            let sudden_death = self.clock.is_some_and(|clock| clock.ran_out());
+            if !sudden_death && !self.rules.is_won(score, opponent_score) {
+                //The game is not won yet, play another round
                 self.reset_elements(rng);
                 let message = PointScoredMessage::new(
                     win_side,
@@ -271,22 +622,35 @@ impl RunningState {
                     ServerToClientMessage::PointScored(message),
                 )
             } else {
-                //End the game
+                //End the game, on the golden point in sudden death
+                let win_type = match sudden_death {
+                    true => WinType::SuddenDeath,
+                    false => WinType::ScoreReached,
+                };
                 let message = GameCompletedMessage::new(win_side);
                 (
-                    UpdateOutcome::Done(GameResult::new(
-                        self.scores,
-                        win_side,
-                        WinType::ScoreReached,
-                    )),
+                    UpdateOutcome::Done(GameResult::new(self.scores, win_side, win_type)),
                     ServerToClientMessage::GameDone(message),
                 )
             }
         } else {
             //Ball is in, keep playing
             (self.ball_y, self.angle) = bounce_off_horizontal_edges(self.ball_y, self.angle);
-            let message =
-                PositionUpdateMessage::new(self.l_pad_y, self.r_pad_y, self.ball_x, self.ball_y);
+            if announce_serve {
+                let timeout = Duration::from_millis(SERVE_TIMEOUT_TICKS * 1000 / TICKS_PER_SECOND);
+                let message = ServeMessage::new(self.service_side, SystemTime::now() + timeout);
+                return (
+                    UpdateOutcome::Continue(self),
+                    ServerToClientMessage::Serve(message),
+                );
+            }
+            let message = PositionUpdateMessage::new(
+                self.l_pad_y,
+                self.r_pad_y,
+                self.ball_x,
+                self.ball_y,
+                self.clock.map(|clock| clock.remaining()),
+            );
             (
                 UpdateOutcome::Continue(self),
                 ServerToClientMessage::PositionUpdate(message),
@@ -294,3 +658,69 @@ impl RunningState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(rs: RunningState) -> (RunningState, ServerToClientMessage) {
+        match rs.update_on_tick(&mut rand::thread_rng(), 0.0, 0.0) {
+            (UpdateOutcome::Continue(rs), message) => (rs, message),
+            (UpdateOutcome::Done(_), _) => panic!("The game ended during a serve."),
+        }
+    }
+
+    fn ball_at_center(rs: &RunningState) -> bool {
+        (rs.ball_x, rs.ball_y) == (RunningState::INITIAL_BALL_X, RunningState::INITIAL_BALL_Y)
+    }
+
+    #[test]
+    fn ball_waits_for_its_serve() {
+        let rs = RunningState::new(&mut rand::thread_rng(), GameRules::default());
+        let serving_side = rs.service_side;
+        let (mut rs, message) = tick(rs);
+        assert!(matches!(message, ServerToClientMessage::Serve(_)));
+        // Only the serving side serves.
+        rs.serve(!serving_side);
+        (rs, _) = tick(rs);
+        assert!(ball_at_center(&rs));
+        rs.serve(serving_side);
+        (rs, _) = tick(rs);
+        assert!(rs.waiting_serve().is_none());
+        assert!(!ball_at_center(&rs));
+    }
+
+    #[test]
+    fn ball_is_served_at_the_timeout() {
+        let mut rs = RunningState::new(&mut rand::thread_rng(), GameRules::default());
+        for _ in 1..SERVE_TIMEOUT_TICKS {
+            (rs, _) = tick(rs);
+        }
+        assert!(ball_at_center(&rs));
+        assert!(rs.waiting_serve().is_some());
+        (rs, _) = tick(rs);
+        assert!(rs.waiting_serve().is_none());
+        // A resumed game waiting for a serve announces it again.
+        rs.reset_elements(&mut rand::thread_rng());
+        (rs, _) = tick(rs);
+        rs.renew_serve();
+        assert!(matches!(tick(rs).1, ServerToClientMessage::Serve(_)));
+    }
+
+    #[test]
+    fn ball_is_served_right_away_for_clients_without_serve() {
+        let rs = RunningState::new(&mut rand::thread_rng(), GameRules::default());
+        let serving_side = rs.service_side;
+        let (mut rs, _) = tick(rs);
+        rs.serve_for_client(|side| {
+            if side == serving_side {
+                ProtocolVersion::V20
+            } else {
+                ProtocolVersion::V19
+            }
+        });
+        assert!(rs.waiting_serve().is_some());
+        rs.serve_for_client(|_| ProtocolVersion::V19);
+        assert!(rs.waiting_serve().is_none());
+    }
+}
@@ -1,98 +1,195 @@
 use std::time::{Duration, SystemTime};
 
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::time::Instant;
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::WebSocketStream;
 
-use crate::game::{Player, Side};
-use crate::protocol::{GameMode0StartMessage, GameMode1StartMessage};
+use crate::game::state::StageError;
+use crate::game::{send_to_both, GameRules, Player, Side};
+use crate::protocol::{
+    CloseReason, GameMode0StartMessage, GameMode1StartMessage, GameStartStatus,
+    GameStartStatusMessage,
+};
 
-/// Send the game start message and wait until the deadline sent to clients elapses or a client disconnects.
+/// Send the game start message, with the rules of the game, and wait until the deadline sent to clients elapses or a
+/// client disconnects.
 pub(super) async fn wait_game_0_start<S>(
     mut left_player: Player<S>,
     mut right_player: Player<S>,
-) -> Result<(Player<S>, Player<S>), (tokio_tungstenite::tungstenite::Error, Player<S>)>
+    rules: GameRules,
+) -> Result<(Player<S>, Player<S>), StageError<S>>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     let game_start_time = SystemTime::now() + Duration::from_secs(5);
     let game_start_instant = Instant::now() + Duration::from_secs(5);
     (left_player, right_player) =
-        send_mode_0_start_messages(left_player, right_player, game_start_time).await?;
+        send_mode_0_start_messages(left_player, right_player, game_start_time, rules).await?;
     (left_player, right_player) =
         wait_grace_period(left_player, right_player, game_start_instant).await?;
     Ok((left_player, right_player))
 }
 
+/// Send each client its game start message. On failure, the other player is returned.
 async fn send_mode_0_start_messages<S>(
     mut left_player: Player<S>,
     mut right_player: Player<S>,
     game_start_time: SystemTime,
-) -> Result<(Player<S>, Player<S>), (tokio_tungstenite::tungstenite::Error, Player<S>)>
+    rules: GameRules,
+) -> Result<(Player<S>, Player<S>), StageError<S>>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     if let Err(e) = left_player
-        .ws
-        .send(Message::Binary(
-            GameMode0StartMessage::new(&right_player.id, Side::Left, game_start_time).into(),
+        .send(GameMode0StartMessage::new(
+            &right_player.id,
+            Side::Left,
+            game_start_time,
+            rules,
         ))
         .await
     {
-        return Err((e, right_player));
+        return Err(StageError::Disconnected(e, right_player));
     }
     if let Err(e) = right_player
-        .ws
-        .send(Message::Binary(
-            GameMode0StartMessage::new(&left_player.id, Side::Right, game_start_time).into(),
+        .send(GameMode0StartMessage::new(
+            &left_player.id,
+            Side::Right,
+            game_start_time,
+            rules,
         ))
         .await
     {
-        return Err((e, left_player));
+        // The left client already received its game start message.
+        send_aborted_status(&mut left_player).await;
+        return Err(StageError::Disconnected(e, left_player));
     }
     Ok((left_player, right_player))
 }
 
+/// Wait until the game start, then tell both clients the game is starting.
+///
+/// If a client disconnects in the meantime, the other one is told the game is aborted before being returned, so that
+/// it can be put back in queue. If the server starts shutting down, both connections are closed.
 async fn wait_grace_period<S>(
     mut left_player: Player<S>,
     mut right_player: Player<S>,
     game_start_instant: Instant,
-) -> Result<(Player<S>, Player<S>), (tokio_tungstenite::tungstenite::Error, Player<S>)>
+) -> Result<(Player<S>, Player<S>), StageError<S>>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    loop {
+    let (error, mut survivor) = loop {
         tokio::select! {
             msg = right_player.ws.next() => match msg {
                 Some(Ok(_)) => {}
-                Some(Err(e)) => return Err((e, left_player)),
-                None => return Err((tokio_tungstenite::tungstenite::Error::ConnectionClosed, left_player)),
+                Some(Err(e)) => break (e, left_player),
+                None => break (tokio_tungstenite::tungstenite::Error::ConnectionClosed, left_player),
             },
             msg = left_player.ws.next() => match msg {
                 Some(Ok(_)) => {}
-                Some(Err(e)) => return Err((e, right_player)),
-                None => return Err((tokio_tungstenite::tungstenite::Error::ConnectionClosed, right_player)),
+                Some(Err(e)) => break (e, right_player),
+                None => break (tokio_tungstenite::tungstenite::Error::ConnectionClosed, right_player),
+            },
+            _ = tokio::time::sleep_until(game_start_instant) => {
+                let message = GameStartStatusMessage::new(GameStartStatus::Starting);
+                match send_to_both(&mut left_player, &mut right_player, message).await {
+                    Ok(()) => return Ok((left_player, right_player)),
+                    Err((e, Side::Left)) => break (e, right_player),
+                    Err((e, Side::Right)) => break (e, left_player),
+                }
+            },
+            () = left_player.shutdown.notified() => {
+                left_player.close(CloseReason::ServerShutdown).await;
+                right_player.close(CloseReason::ServerShutdown).await;
+                return Err(StageError::ServerShutdown);
             },
-            _ = tokio::time::sleep_until(game_start_instant) => return Ok((left_player, right_player)),
         }
-    }
+    };
+    send_aborted_status(&mut survivor).await;
+    Err(StageError::Disconnected(error, survivor))
+}
+
+/// Tell the client its game start is aborted and that it is put back in queue.
+///
+/// A failure to send is ignored : it will be detected by the match-making, as if it happened right after.
+async fn send_aborted_status<S>(player: &mut Player<S>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let _: Result<_, _> = player
+        .send(GameStartStatusMessage::new(GameStartStatus::Aborted))
+        .await;
 }
 
+/// Send the game start message, with the rules of the game, and wait until the deadline sent to the client elapses.
 pub(super) async fn wait_game_1_start<S>(
-    mut connection: WebSocketStream<S>,
-) -> Result<WebSocketStream<S>, tokio_tungstenite::tungstenite::Error>
+    mut player: Player<S>,
+    rules: GameRules,
+) -> Result<Player<S>, StageError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let game_start_time = SystemTime::now() + Duration::from_secs(5);
+    let game_start_instant = Instant::now() + Duration::from_secs(5);
+    if let Err(e) = player
+        .send(GameMode1StartMessage::new(game_start_time, rules))
+        .await
+    {
+        return Err(StageError::Lost(e));
+    }
+    tokio::select! {
+        _ = tokio::time::sleep_until(game_start_instant) => Ok(player),
+        () = player.shutdown.notified() => {
+            player.close(CloseReason::ServerShutdown).await;
+            Err(StageError::ServerShutdown)
+        },
+    }
+}
+
+/// Send the game start message, naming the AI as the opponent and with the rules of the game, and wait until the
+/// deadline sent to the client elapses. Then tell the client the game is starting.
+pub(super) async fn wait_game_4_start<S>(
+    mut player: Player<S>,
+    player_side: Side,
+    ai_id: &str,
+    rules: GameRules,
+) -> Result<Player<S>, StageError<S>>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     let game_start_time = SystemTime::now() + Duration::from_secs(5);
     let game_start_instant = Instant::now() + Duration::from_secs(5);
-    connection
-        .send(Message::Binary(
-            GameMode1StartMessage::new(game_start_time).into(),
+    if let Err(e) = player
+        .send(GameMode0StartMessage::new(
+            ai_id,
+            player_side,
+            game_start_time,
+            rules,
         ))
-        .await?;
-    tokio::time::sleep_until(game_start_instant).await;
-    Ok(connection)
+        .await
+    {
+        return Err(StageError::Lost(e));
+    }
+    let error = loop {
+        tokio::select! {
+            msg = player.ws.next() => match msg {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break e,
+                None => break tokio_tungstenite::tungstenite::Error::ConnectionClosed,
+            },
+            _ = tokio::time::sleep_until(game_start_instant) => {
+                let message = GameStartStatusMessage::new(GameStartStatus::Starting);
+                match player.send(message).await {
+                    Ok(()) => return Ok(player),
+                    Err(e) => break e,
+                }
+            },
+            () = player.shutdown.notified() => {
+                player.close(CloseReason::ServerShutdown).await;
+                return Err(StageError::ServerShutdown);
+            },
+        }
+    };
+    Err(StageError::Lost(error))
 }
@@ -0,0 +1,148 @@
+//! Round-robin leagues between registered players.
+//!
+//! Leagues and their members are set up on the website. The first time one of its members enters the match-making
+//! queue, the server schedules the league : every member is to meet every other once, over matchdays. Fixtures are not
+//! played at a set time. When two members with an unplayed fixture are in the queue at the same time, they are paired
+//! together in priority, and the first game they play counts for the fixture. Its result updates the league table,
+//! with the points, wins and point difference of both members.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use tokio_postgres::types::ToSql;
+
+mod round_robin;
+
+/// Points awarded to the winner of a fixture. The loser gets none.
+const POINTS_PER_WIN: i16 = 3;
+
+/// Get the usernames of the players the given one has an unplayed fixture against, in any of its leagues. Its leagues
+/// that haven't been scheduled yet are scheduled first.
+pub async fn fixture_opponents(
+    db_client: &Arc<tokio_postgres::Client>,
+    username: &str,
+) -> Result<HashSet<String>, tokio_postgres::Error> {
+    schedule_leagues(db_client, username).await?;
+    let query = "select opponent.username \
+                 from account_leaguefixture fixture \
+                 join account_player player on player.id in (fixture.p1_id, fixture.p2_id) \
+                 join account_player opponent on opponent.id in (fixture.p1_id, fixture.p2_id) \
+                 where player.username = $1 and opponent.id <> player.id and fixture.result_id is null;";
+    let rows = db_client.query(query, &[&username]).await?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Get the unplayed fixture between the two players, if any. If they meet in several leagues, the fixture of the
+/// earliest matchday is returned.
+pub async fn pending_fixture(
+    db_client: &Arc<tokio_postgres::Client>,
+    first_username: &str,
+    second_username: &str,
+) -> Result<Option<i64>, tokio_postgres::Error> {
+    let query = "select fixture.id \
+                 from account_leaguefixture fixture \
+                 join account_player p1 on p1.id = fixture.p1_id \
+                 join account_player p2 on p2.id = fixture.p2_id \
+                 where fixture.result_id is null \
+                   and ((p1.username = $1 and p2.username = $2) or (p1.username = $2 and p2.username = $1)) \
+                 order by fixture.matchday, fixture.id \
+                 limit 1;";
+    let row = db_client
+        .query_opt(query, &[&first_username, &second_username])
+        .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Record the game result as the outcome of the fixture, and update the league table with it. Nothing is recorded if
+/// the fixture has been played in the meantime, or if the game wasn't played by its members.
+pub async fn record_fixture_result(
+    db_client: &Arc<tokio_postgres::Client>,
+    fixture_id: i64,
+    result_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+    let query = "with result as ( \
+                     select id, p1_id, p2_id, p1_score, p2_score, winner_id \
+                     from account_gameresult where id = $2 \
+                 ), fixture_played as ( \
+                     update account_leaguefixture fixture set result_id = result.id \
+                     from result \
+                     where fixture.id = $1 and fixture.result_id is null \
+                       and (fixture.p1_id, fixture.p2_id) in ((result.p1_id, result.p2_id), \
+                                                               (result.p2_id, result.p1_id)) \
+                     returning fixture.league_id \
+                 ) \
+                 update account_leaguestanding standing \
+                 set played = standing.played + 1, \
+                     wins = standing.wins + case when standing.player_id = result.winner_id then 1 else 0 end, \
+                     losses = standing.losses + case when standing.player_id = result.winner_id then 0 else 1 end, \
+                     points = standing.points \
+                              + case when standing.player_id = result.winner_id then $3::smallint else 0 end, \
+                     point_difference = standing.point_difference \
+                                        + case when standing.player_id = result.p1_id \
+                                               then result.p1_score - result.p2_score \
+                                               else result.p2_score - result.p1_score end \
+                 from fixture_played, result \
+                 where standing.league_id = fixture_played.league_id \
+                   and standing.player_id in (result.p1_id, result.p2_id);";
+    let parameters: [&(dyn ToSql + Sync); 3] = [&fixture_id, &result_id, &POINTS_PER_WIN];
+    db_client.execute(query, &parameters).await?;
+    Ok(())
+}
+
+/// Schedule the leagues of the player that haven't been scheduled yet.
+async fn schedule_leagues(
+    db_client: &Arc<tokio_postgres::Client>,
+    username: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let query = "select league.id \
+                 from account_league league \
+                 join account_league_members member on member.league_id = league.id \
+                 join account_player player on player.id = member.player_id \
+                 where player.username = $1 and not league.scheduled;";
+    for row in db_client.query(query, &[&username]).await? {
+        schedule_league(db_client, row.get(0)).await?;
+    }
+    Ok(())
+}
+
+/// Write the fixtures of the league between its current members, and set its table up with a blank line for each of
+/// them. Nothing is written if the league has been scheduled in the meantime.
+async fn schedule_league(
+    db_client: &Arc<tokio_postgres::Client>,
+    league_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+    let query =
+        "select player_id from account_league_members where league_id = $1 order by player_id;";
+    let members: Vec<i64> = db_client
+        .query(query, &[&league_id])
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let (mut matchdays, mut p1_ids, mut p2_ids) = (Vec::new(), Vec::new(), Vec::new());
+    for (matchday, meetings) in (1i16..).zip(round_robin::matchdays(members.len())) {
+        for (p1, p2) in meetings {
+            matchdays.push(matchday);
+            p1_ids.push(members[p1]);
+            p2_ids.push(members[p2]);
+        }
+    }
+
+    // The league is marked as scheduled in the same statement, so that it is only scheduled once.
+    let query = "with league as ( \
+                     update account_league set scheduled = true where id = $1 and not scheduled returning id \
+                 ), fixtures as ( \
+                     insert into account_leaguefixture(league_id, matchday, p1_id, p2_id) \
+                     select league.id, fixture.matchday, fixture.p1_id, fixture.p2_id \
+                     from league, unnest($2::smallint[], $3::bigint[], $4::bigint[]) \
+                                  as fixture(matchday, p1_id, p2_id) \
+                 ) \
+                 insert into account_leaguestanding(league_id, player_id, played, wins, losses, points, \
+                                                    point_difference) \
+                 select league.id, member.player_id, 0, 0, 0, 0, 0 \
+                 from league, unnest($5::bigint[]) as member(player_id);";
+    let parameters: [&(dyn ToSql + Sync); 5] = [&league_id, &matchdays, &p1_ids, &p2_ids, &members];
+    db_client.execute(query, &parameters).await?;
+    Ok(())
+}
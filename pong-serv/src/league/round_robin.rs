@@ -0,0 +1,55 @@
+//! Schedule of a round-robin, in which every member meets every other once.
+
+/// Split the meetings of a round-robin between `members` members into matchdays, in which each member plays at most
+/// once. Members are referred to by their index.
+///
+/// This is the circle method : the first member stays in place while the others rotate around it, one position per
+/// matchday, and members facing each other across the circle meet. With an odd number of members, an empty position
+/// is added to the circle, and the member facing it rests for the matchday.
+pub(super) fn matchdays(members: usize) -> Vec<Vec<(usize, usize)>> {
+    if members < 2 {
+        return Vec::new();
+    }
+    let positions = members + members % 2;
+    let rotating = positions - 1;
+    (0..rotating)
+        .map(|matchday| {
+            let member_at = |position: usize| match position {
+                0 => 0,
+                position => (position - 1 + matchday) % rotating + 1,
+            };
+            (0..positions / 2)
+                .map(|position| (member_at(position), member_at(positions - 1 - position)))
+                .filter(|&(first, second)| first < members && second < members)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everyone_meets_once() {
+        assert!(matchdays(1).is_empty());
+        assert_eq!(matchdays(2), vec![vec![(0, 1)]]);
+        for members in 2..=9 {
+            let matchdays = matchdays(members);
+            assert_eq!(matchdays.len(), members + members % 2 - 1);
+            let mut meetings = Vec::new();
+            for matchday in &matchdays {
+                let mut playing: Vec<usize> = matchday.iter().flat_map(|&(a, b)| [a, b]).collect();
+                playing.sort_unstable();
+                playing.dedup();
+                assert_eq!(playing.len(), matchday.len() * 2);
+                meetings.extend(matchday.iter().map(|&(a, b)| (a.min(b), a.max(b))));
+            }
+            meetings.sort_unstable();
+            let expected: Vec<_> = (0..members)
+                .flat_map(|a| (a + 1..members).map(move |b| (a, b)))
+                .collect();
+            assert_eq!(meetings, expected);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::error::Error;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use std::{fs, io};
 
 use clap::{Parser, ValueEnum};
@@ -17,13 +18,26 @@ use tokio::task::JoinSet;
 use tokio_rustls::server::TlsStream;
 use tokio_rustls::TlsAcceptor;
 
-use crate::accept_tasks::OnAcceptGenerator;
-use crate::match_making::MatchMaker;
+use crate::accept_tasks::{HandshakeTimeouts, OnAcceptGenerator, UpgradeChecks};
+use crate::game::{GameRules, LiveGames, Reconnections};
+use crate::match_making::{Challenges, Invitations, MatchMaker, QueueService, Tournaments};
+use crate::protocol::ServerContext;
+use crate::sessions::{SessionPolicy, SessionStream, Sessions};
+use crate::shutdown::Shutdown;
 
 mod accept_tasks;
+mod authentication;
 mod game;
+mod league;
 mod match_making;
 mod protocol;
+mod rating;
+mod sessions;
+mod shutdown;
+mod tournament;
+
+/// Time given to the tasks to close their connections once the server is shutting down.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
 
 #[derive(Parser)]
 #[command(about, long_about = None)]
@@ -57,6 +71,66 @@ struct Cli {
     /// Set where the printed logging is outputted.
     #[arg(value_enum, long, short, default_value_t)]
     console_channel: ConsoleChannel,
+
+    /// Set the time in seconds a client has to complete the TLS handshake once its connection is accepted.
+    #[arg(long, default_value = "5", value_name = "SECONDS")]
+    tls_handshake_timeout: u64,
+
+    /// Set the time in seconds a client has to complete the WebSocket upgrade once the TLS handshake is done.
+    #[arg(long, default_value = "5", value_name = "SECONDS")]
+    ws_handshake_timeout: u64,
+
+    /// Allow WebSocket upgrade requests from this origin only. Can be given several times to allow several origins.
+    ///
+    /// Requests from other origins are rejected with a 403 response. All origins are allowed if none is given.
+    #[arg(long = "allowed-origin", value_name = "ORIGIN")]
+    allowed_origins: Vec<String>,
+
+    /// Authenticate clients during the WebSocket upgrade with the website session cookie.
+    ///
+    /// Requests without a valid session cookie are rejected with a 401 response. The token of the Hello message is then
+    /// ignored.
+    #[arg(long)]
+    session_auth: bool,
+
+    /// Set the time in seconds a player who lost its connection during a remote game has to come back.
+    ///
+    /// The game is paused in the meantime. A value of 0 disables reconnections : losing the connection is a withdrawal.
+    #[arg(long, default_value = "30", value_name = "SECONDS")]
+    reconnection_window: u64,
+
+    /// Set the time in seconds the spectators of a game are shown it after its players.
+    ///
+    /// A delay keeps the players from watching their own game to get an edge.
+    #[arg(long, default_value = "0", value_name = "SECONDS")]
+    spectator_delay: u64,
+
+    /// Set the time in seconds the players paired by the match making queue have to confirm they are ready to play.
+    ///
+    /// A player who declines or doesn't confirm in time leaves the queue, and its opponent is put back at the front of
+    /// it. A value of 0 disables the ready check : the games start as soon as the players are paired.
+    #[arg(long, default_value = "0", value_name = "SECONDS")]
+    ready_check: u64,
+
+    /// Set the time limit in seconds of the tournament matches.
+    ///
+    /// A match still going when its clock runs out is won by the player leading on score, or by the next point scored
+    /// if the score is tied. A value of 0 disables the time limit : the matches are played to the target score.
+    #[arg(long, default_value = "300", value_name = "SECONDS")]
+    tournament_time_limit: u64,
+
+    /// Set what happens when a user opens a connection while it already has one.
+    ///
+    /// Replacing the existing connection drops it as if it was lost : a game it plays is paused until the player
+    /// resumes it, or withdraws.
+    #[arg(value_enum, long, default_value_t)]
+    session_policy: SessionPolicy,
+
+    /// Set the username of the account the games against the AI are recorded against.
+    ///
+    /// The account must exist in the database.
+    #[arg(long, default_value = "AI", value_name = "USERNAME")]
+    ai_username: String,
 }
 
 #[derive(Copy, Clone, ValueEnum, Default)]
@@ -77,14 +151,40 @@ async fn main() -> Result<(), ()> {
         .map_err(|e| eprintln!("Error while configuring logging : {e:?}"))?;
     let tls_acceptor = make_tls_acceptor(&cli.tls_private_key, &cli.tls_certificate)
         .map_err(|e| log::error!("Error while creating a TLS config for the server : {e}."))?;
+    let handshake_timeouts = HandshakeTimeouts {
+        tls: Duration::from_secs(cli.tls_handshake_timeout),
+        websocket: Duration::from_secs(cli.ws_handshake_timeout),
+    };
     let listen_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), cli.port);
     log::info!("Server started. Listening on {listen_address}.");
     let db_client = Arc::new(connect_to_db(&cli.socket_path).await?);
     match TcpListener::bind(&listen_address).await {
         Ok(tcp_listener) => {
-            let global_match_maker = Arc::new(MatchMaker::new());
-            let task_generator = OnAcceptGenerator::new(tcp_listener);
-            run_until_signaled(global_match_maker, tls_acceptor, task_generator, db_client).await
+            let ready_check = (cli.ready_check > 0).then(|| Duration::from_secs(cli.ready_check));
+            let (match_maker, queue_service) = MatchMaker::new(ready_check);
+            let context = Arc::new(ServerContext {
+                match_maker,
+                invitations: Invitations::new(),
+                challenges: Challenges::new(),
+                tournaments: Tournaments::new(),
+                sessions: Sessions::new(cli.session_policy),
+                tournament_rules: GameRules {
+                    time_limit: (cli.tournament_time_limit > 0)
+                        .then(|| Duration::from_secs(cli.tournament_time_limit)),
+                    ..GameRules::default()
+                },
+                reconnections: Reconnections::new(Duration::from_secs(cli.reconnection_window)),
+                live_games: LiveGames::new(Duration::from_secs(cli.spectator_delay)),
+                db_client: db_client.clone(),
+                ai_id: cli.ai_username,
+            });
+            let upgrade_checks = UpgradeChecks {
+                allowed_origins: cli.allowed_origins.into(),
+                session_db: cli.session_auth.then(|| db_client.clone()),
+            };
+            let task_generator =
+                OnAcceptGenerator::new(tcp_listener, handshake_timeouts, upgrade_checks);
+            run_until_signaled(context, queue_service, tls_acceptor, task_generator).await
         }
         Err(e) => {
             log::error!("Failed to bind to address {listen_address} with error : {e}.");
@@ -131,7 +231,7 @@ fn setup_logger(log_folder: String, console_channel: ConsoleChannel) -> io::Resu
         .level_for("tokio_postgres", log::LevelFilter::Debug)
         .level_for("tokio_tungstenite", log::LevelFilter::Debug)
         .format(format_log)
-        .chain(rotator as Box<(dyn io::Write + Send)>);
+        .chain(rotator as Box<dyn io::Write + Send>);
 
     // Finish the config. Can unwrap because we know we only set the logger once.
     fern::Dispatch::new()
@@ -215,13 +315,14 @@ async fn connect_to_db(socket_path: &str) -> Result<tokio_postgres::Client, ()>
     }
 }
 
-/// Create asynchronous tasks to handle connections until an interrupt or terminate signal is received.
-/// The return value of the tasks spawned are ignored.
+/// Run the match making queue, and create asynchronous tasks to handle connections until an interrupt or terminate
+/// signal is received. The tasks are then notified of the shutdown, and given [`SHUTDOWN_GRACE_PERIOD`] to close their
+/// connections before being aborted. The return value of the tasks spawned are ignored.
 async fn run_until_signaled(
-    match_maker: Arc<MatchMaker<TlsStream<TcpStream>>>,
+    context: Arc<ServerContext<SessionStream<TlsStream<TcpStream>>>>,
+    queue_service: QueueService<SessionStream<TlsStream<TcpStream>>>,
     tls_acceptor: TlsAcceptor,
     mut task_generator: OnAcceptGenerator,
-    db_client: Arc<tokio_postgres::Client>,
 ) -> Result<(), ()> {
     let (mut sigint_handler, mut sigterm_handler) = match signal(SignalKind::interrupt())
         .and_then(|si| signal(SignalKind::terminate()).map(|st| (si, st)))
@@ -232,7 +333,22 @@ async fn run_until_signaled(
             return Err(());
         }
     };
+    let (shutdown_sender, shutdown) = Shutdown::channel();
     let mut task_set = JoinSet::new();
+    task_set.spawn({
+        let (context, shutdown) = (context.clone(), shutdown.clone());
+        async move {
+            let start_game = {
+                let context = context.clone();
+                move |left, right, format| {
+                    protocol::play_match_made_games(left, right, format, context.clone())
+                }
+            };
+            queue_service
+                .run(&context.challenges, shutdown, start_game)
+                .await
+        }
+    });
     let res = loop {
         tokio::select! {
             biased;
@@ -259,12 +375,17 @@ async fn run_until_signaled(
             task_generation_result = task_generator.generate_next_task(
                 &tls_acceptor,
                 &mut task_set,
-                |websocket, id| protocol::execute_protocol_on_connection(
-                    websocket,
-                    id,
-                    match_maker.clone(),
-                    db_client.clone()
-                )
+                {
+                    let (context, shutdown) = (context.clone(), shutdown.clone());
+                    move |websocket, id, session_user, session| protocol::execute_protocol_on_connection(
+                        websocket,
+                        id,
+                        session_user,
+                        session,
+                        context,
+                        shutdown,
+                    )
+                }
             ) => {
                 if task_generation_result.is_err() {
                     break Err(());
@@ -273,6 +394,14 @@ async fn run_until_signaled(
         }
     };
     log::info!("Closing all connections and shutting down spawned tasks...");
+    let _: Result<_, _> = shutdown_sender.send(true);
+    let all_tasks_done = async { while task_set.join_next().await.is_some() {} };
+    if tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, all_tasks_done)
+        .await
+        .is_err()
+    {
+        log::info!("Some connections didn't close in time, aborting their tasks.");
+    }
     task_set.shutdown().await;
     log::info!("Done, exiting.");
     res
@@ -4,35 +4,28 @@
 //! same task, so they can play together.
 //!
 //! The implemented logics are :
-//! * Pairing incoming players together to play a game. This is done in [`join_opponents`] using a
-//!   server-wide [`Mutex`].
+//! * Pairing incoming players together to play a game. This is done by the [`QueueService`], a dedicated task owning
+//!   the queue, which the players are handed over to through the server-wide [`MatchMaker`]. Players are paired with
+//!   opponents of a close rating, the accepted difference widening as they wait. League members with an unplayed
+//!   fixture between them are paired together in priority. Paired players can be asked to confirm they are ready to
+//!   play before their game starts.
+//! * Pairing a player hosting a private game with the player joining it by its code. This is done in
+//!   [`host_private_game`] and [`join_private_game`], using the server-wide [`Invitations`].
+//! * Pairing a player with the friend it challenges, if the latter accepts. This is done in [`challenge_friend`] and
+//!   [`wait_in_lobby`], using the server-wide [`Challenges`].
+//! * Gathering the entrants of a tournament once it is full. This is done in [`register_for_tournament`], using the
+//!   server-wide [`Tournaments`].
 
-use std::sync::Mutex;
-
-use tokio::sync::oneshot;
-
-pub use opponents_joining::join_opponents;
-
-use crate::match_making::opponents_joining::GiverToExecutorData;
+pub use challenges::{challenge_friend, wait_in_lobby, ChallengeError, Challenges};
+pub use invitations::{host_private_game, join_private_game, Invitations};
+pub use queue::{MatchMaker, QueueService, QueuedPlayer};
+pub use search::{MatchFormat, SearchCriteria};
+pub use tournaments::{register_for_tournament, Registrant, Tournaments};
 
+mod challenges;
+mod invitations;
 mod opponents_joining;
-
-/// A server-wide structure, opaque from outside this mod but easily created from the main function, containing the
-/// necessary resources to match-make and join connections handled from the many asynchronous tasks.
-///
-/// # Safety
-///
-/// The structure can't be copied nor cloned. It must be stored in an [`Arc`]. It is [`Send`], and has all the
-/// inter-task synchronization primitives necessary for the mod to do its job.
-pub struct MatchMaker<S> {
-    mutex: Mutex<Option<oneshot::Sender<GiverToExecutorData<S>>>>,
-}
-
-impl<S> MatchMaker<S> {
-    /// Creates a new [`MatchMaker`] instance.
-    pub fn new() -> MatchMaker<S> {
-        MatchMaker {
-            mutex: Mutex::new(None),
-        }
-    }
-}
+mod queue;
+mod ready_check;
+mod search;
+mod tournaments;
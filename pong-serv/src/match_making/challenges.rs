@@ -0,0 +1,297 @@
+//! Pairing of two friends, one challenging the other.
+//!
+//! Players waiting for an opponent, in the match-making queue or in the lobby, enter the server-wide [`Challenges`].
+//! A player can then challenge one of its friends waiting there, bypassing the [`MatchMaker`] queue. The friend is told
+//! about the challenge, and accepts or declines it. This is implemented in [`challenge_friend`] and [`wait_in_lobby`].
+//!
+//! [`MatchMaker`]: super::MatchMaker
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use tokio_postgres::types::ToSql;
+
+use crate::game::Player;
+use crate::match_making::opponents_joining::{
+    wait_for_giver_data, GiverToExecutorData, WaitError, WaitOutcome,
+};
+use crate::protocol::{ChallengeMessage, ChallengeStatus, CloseReason};
+
+/// Time a challenger waits for its friend to answer.
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A server-wide structure, shared in the [`ServerContext`], through which the players waiting for an opponent can be
+/// challenged by their friends.
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct Challenges<S> {
+    waiting: Mutex<HashMap<String, mpsc::UnboundedSender<Challenge<S>>>>,
+}
+
+/// A challenge sent to a waiting player. Accepting it means sending itself to the challenger through `reply`, and
+/// declining it dropping `reply`.
+pub(super) struct Challenge<S> {
+    pub(super) challenger_id: String,
+    pub(super) reply: oneshot::Sender<GiverToExecutorData<S>>,
+}
+
+impl<S> Challenges<S> {
+    /// Creates a new [`Challenges`] instance.
+    pub fn new() -> Challenges<S> {
+        Challenges {
+            waiting: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Make the player with the given id reachable by challenges, until the returned [`Lobby`] is dropped.
+    pub(super) fn enter(&self, id: &str) -> Lobby<'_, S> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        self.waiting
+            .lock()
+            .unwrap()
+            .insert(String::from(id), sender.clone());
+        Lobby {
+            challenges: self,
+            id: String::from(id),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Send a challenge to the waiting player with the given id. The challenge is given back if the player isn't
+    /// waiting.
+    fn send(&self, id: &str, challenge: Challenge<S>) -> Result<(), Challenge<S>> {
+        match self.waiting.lock().unwrap().get(id) {
+            Some(sender) => sender.send(challenge).map_err(|e| e.0),
+            None => Err(challenge),
+        }
+    }
+}
+
+/// The registration of a waiting player in the [`Challenges`], through which it receives the challenges of its friends.
+/// The player stops being reachable once it is dropped.
+pub(super) struct Lobby<'a, S> {
+    challenges: &'a Challenges<S>,
+    id: String,
+    sender: mpsc::UnboundedSender<Challenge<S>>,
+    receiver: mpsc::UnboundedReceiver<Challenge<S>>,
+}
+
+impl<S> Lobby<'_, S> {
+    /// Wait for the next challenge whose challenger is still waiting for an answer.
+    pub(super) async fn next_challenge(&mut self) -> Challenge<S> {
+        loop {
+            match self.receiver.recv().await {
+                Some(challenge) if !challenge.reply.is_closed() => return challenge,
+                Some(_) => {}
+                // The lobby holds a sender, the channel can't be closed.
+                None => future::pending().await,
+            }
+        }
+    }
+}
+
+impl<S> Drop for Lobby<'_, S> {
+    fn drop(&mut self) {
+        let mut waiting = self.challenges.waiting.lock().unwrap();
+        // The player may have entered the lobby again from another connection in the meantime.
+        if waiting
+            .get(&self.id)
+            .is_some_and(|sender| sender.same_channel(&self.sender))
+        {
+            waiting.remove(&self.id);
+        }
+    }
+}
+
+/// Errors encountered while challenging a friend.
+#[derive(thiserror::Error, Debug)]
+pub enum ChallengeError {
+    /// The challenged user is not a friend of the challenger.
+    #[error("the challenged user is not a friend")]
+    NotFriends,
+
+    /// The challenged friend isn't waiting for an opponent.
+    #[error("the challenged friend isn't waiting for an opponent")]
+    Unavailable,
+
+    /// The challenged friend declined, or didn't answer in time.
+    #[error("the challenged friend declined")]
+    Declined,
+
+    /// The challenger disconnected or the server started shutting down while waiting for an answer.
+    #[error("{0}")]
+    Wait(#[from] WaitError),
+
+    /// This error happens when checking the friendship fails.
+    #[error("an error with the database occurred : {0}")]
+    DatabaseError(#[from] tokio_postgres::Error),
+}
+
+impl ChallengeError {
+    /// The reason to give the challenger when closing its connection, if it can still be told anything.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::NotFriends => Some(CloseReason::NotFriends),
+            Self::Unavailable => Some(CloseReason::OpponentUnavailable),
+            Self::Declined => Some(CloseReason::ChallengeDeclined),
+            Self::Wait(e) => e.close_reason(),
+            Self::DatabaseError(_) => Some(CloseReason::InternalError),
+        }
+    }
+}
+
+/// Challenge the friend with the given username, and wait for it to accept. Returns the challenger then its friend.
+///
+/// The two must be friends on the website, and the friend must be waiting for an opponent. While waiting for an
+/// answer, the connection is kept alive as in the [`QueueService`]. The challenge is withdrawn if the challenger closes
+/// its connection, and declined if the friend doesn't answer within [`CHALLENGE_TIMEOUT`].
+///
+/// [`QueueService`]: super::QueueService
+pub async fn challenge_friend<S, D>(
+    mut player: Player<S>,
+    opponent: &str,
+    challenges: &Challenges<S>,
+    db_client: &tokio_postgres::Client,
+    log_id: &D,
+) -> Result<(Player<S>, Player<S>), (ChallengeError, Player<S>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    match are_friends(db_client, player.id(), opponent).await {
+        Ok(true) => {}
+        Ok(false) => return Err((ChallengeError::NotFriends, player)),
+        Err(e) => return Err((e.into(), player)),
+    }
+    let (reply, mut receiver) = oneshot::channel();
+    let challenge = Challenge {
+        challenger_id: String::from(player.id()),
+        reply,
+    };
+    if challenges.send(opponent, challenge).is_err() {
+        return Err((ChallengeError::Unavailable, player));
+    }
+    log::trace!("{log_id}: {opponent} has been challenged.");
+    let deadline = Instant::now() + CHALLENGE_TIMEOUT;
+    let version = player.version();
+    let (websocket, shutdown) = player.connection_mut();
+    let wait_result = tokio::select! {
+        wait_result = wait_for_giver_data(&mut receiver, websocket, shutdown, version, None, None) => wait_result,
+        _ = tokio::time::sleep_until(deadline) => Err(WaitError::Abandoned),
+    };
+    // The friend may have accepted right before the challenger stopped waiting.
+    receiver.close();
+    let wait_result = match (wait_result, receiver.try_recv()) {
+        (Err(WaitError::Abandoned), Ok(data)) => Ok(WaitOutcome::Joined(data)),
+        (Err(e), Ok(GiverToExecutorData { giver: mut friend })) => {
+            withdraw_accepted_challenge(&mut friend, player.id()).await;
+            friend.close(CloseReason::Done).await;
+            Err(e)
+        }
+        (wait_result, _) => wait_result,
+    };
+    match wait_result {
+        Ok(WaitOutcome::Joined(GiverToExecutorData { giver })) => Ok((player, giver)),
+        Ok(WaitOutcome::ChallengeAccepted(_) | WaitOutcome::LeftQueue) => {
+            unreachable!("The challenger is neither in the lobby nor in the queue.")
+        }
+        Err(WaitError::Abandoned) => Err((ChallengeError::Declined, player)),
+        Err(e) => Err((e.into(), player)),
+    }
+}
+
+/// Tell the player the challenge it accepted has been withdrawn, as the challenger left in the meantime.
+///
+/// A failure to send is ignored : it will be detected as the player goes on waiting.
+pub(super) async fn withdraw_accepted_challenge<S>(player: &mut Player<S>, challenger_id: &str)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let message = ChallengeMessage::new(ChallengeStatus::Withdrawn, challenger_id);
+    let _: Result<_, _> = player.send(message).await;
+}
+
+/// Wait in the lobby until the player accepts the challenge of a friend, then hand it over to the challenger.
+///
+/// While waiting, the connection is kept alive as in the [`QueueService`]. If the client disconnects or the server
+/// starts shutting down, the connection is closed if it can be, and the error is returned.
+///
+/// [`QueueService`]: super::QueueService
+pub async fn wait_in_lobby<S, D>(
+    mut player: Player<S>,
+    challenges: &Challenges<S>,
+    log_id: &D,
+) -> Result<(), WaitError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    // Nobody joins a player in the lobby other than through a challenge.
+    let (_never_sent, mut receiver) = oneshot::channel::<GiverToExecutorData<S>>();
+    loop {
+        let mut lobby = challenges.enter(player.id());
+        let version = player.version();
+        let (websocket, shutdown) = player.connection_mut();
+        let wait_result = wait_for_giver_data(
+            &mut receiver,
+            websocket,
+            shutdown,
+            version,
+            Some(&mut lobby),
+            None,
+        )
+        .await;
+        drop(lobby);
+        let challenge = match wait_result {
+            Ok(WaitOutcome::ChallengeAccepted(challenge)) => challenge,
+            Ok(WaitOutcome::Joined(_)) => unreachable!("Nobody joins a player in the lobby."),
+            Ok(WaitOutcome::LeftQueue) => unreachable!("The lobby is not the queue."),
+            Err(e) => {
+                if let Some(reason) = e.close_reason() {
+                    player.close(reason).await;
+                }
+                return Err(e);
+            }
+        };
+        log::trace!(
+            "{log_id}: The challenge of {} has been accepted.",
+            challenge.challenger_id
+        );
+        let Challenge {
+            challenger_id,
+            reply,
+        } = challenge;
+        match reply.send(GiverToExecutorData { giver: player }) {
+            Ok(()) => return Ok(()),
+            Err(GiverToExecutorData { giver }) => {
+                log::trace!("{log_id}: The challenger left. Back to the lobby.");
+                player = giver;
+                withdraw_accepted_challenge(&mut player, &challenger_id).await;
+            }
+        }
+    }
+}
+
+/// Check in the database that the two users are friends.
+async fn are_friends(
+    db_client: &tokio_postgres::Client,
+    username: &str,
+    other_username: &str,
+) -> Result<bool, tokio_postgres::Error> {
+    let query = "select exists( \
+                     select 1 from account_relationship \
+                     join account_player first_user on first_user.id = account_relationship.first_user_id \
+                     join account_player second_user on second_user.id = account_relationship.second_user_id \
+                     where (first_user.username = $1 and second_user.username = $2) \
+                        or (first_user.username = $2 and second_user.username = $1));";
+    let parameters: [&(dyn ToSql + Sync); 2] = [&username, &other_username];
+    Ok(db_client.query_one(query, &parameters).await?.get(0))
+}
@@ -0,0 +1,168 @@
+//! Pairing of two players through the code of a private game.
+//!
+//! A host opens a private game and is given a code, which it shares with the player it wants to play against. That
+//! player joins the game with the code, bypassing the [`MatchMaker`] queue. This is implemented in
+//! [`host_private_game`] and [`join_private_game`].
+//!
+//! [`MatchMaker`]: super::MatchMaker
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::game::Player;
+use crate::match_making::opponents_joining::{
+    wait_for_giver_data, GiverToExecutorData, WaitOutcome,
+};
+use crate::protocol::{CloseReason, InviteCodeMessage};
+
+/// Time a host waits for someone to join its private game.
+const INVITE_TIMEOUT: Duration = Duration::from_secs(120);
+/// Number of characters of a private game code.
+const CODE_LENGTH: usize = 6;
+/// The characters a private game code is made of. Those easily mistaken for one another are left out.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// A server-wide structure, shared in the [`ServerContext`], holding the private games waiting for a guest.
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct Invitations<S> {
+    hosts: Mutex<HashMap<String, Invitation<S>>>,
+}
+
+/// A private game waiting for a guest.
+struct Invitation<S> {
+    host_id: String,
+    sender: oneshot::Sender<GiverToExecutorData<S>>,
+}
+
+impl<S> Invitations<S> {
+    /// Creates a new [`Invitations`] instance.
+    pub fn new() -> Invitations<S> {
+        Invitations {
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Open a private game for the host with the given id. Returns its code, and the receiver the guest will come
+    /// through.
+    fn open(&self, host_id: &str) -> (String, oneshot::Receiver<GiverToExecutorData<S>>) {
+        let (sender, receiver) = oneshot::channel();
+        let mut rng = rand::thread_rng();
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        let mut hosts = self.hosts.lock().unwrap();
+        let code = loop {
+            let code: String = (0..CODE_LENGTH)
+                .map(|_| char::from(CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())]))
+                .collect();
+            if !hosts.contains_key(&code) {
+                break code;
+            }
+        };
+        let invitation = Invitation {
+            host_id: String::from(host_id),
+            sender,
+        };
+        hosts.insert(code.clone(), invitation);
+        (code, receiver)
+    }
+
+    /// Stop waiting for a guest in the private game with the given code.
+    fn close(&self, code: &str) {
+        self.hosts.lock().unwrap().remove(code);
+    }
+}
+
+/// Open a private game for the player, send it the code, and wait for a guest to join with it. Returns the host then
+/// the guest.
+///
+/// While waiting, the connection is kept alive as in the [`QueueService`]. The host cancels the game by closing its
+/// connection. If nobody joins within [`INVITE_TIMEOUT`], if the host disconnects, or if the server starts shutting
+/// down, the connection is closed and nothing is returned.
+///
+/// [`QueueService`]: super::QueueService
+pub async fn host_private_game<S, D>(
+    mut player: Player<S>,
+    invitations: &Invitations<S>,
+    log_id: &D,
+) -> Option<(Player<S>, Player<S>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    let (code, mut receiver) = invitations.open(player.id());
+    log::trace!("{log_id}: Hosting the private game {code}.");
+    let deadline = Instant::now() + INVITE_TIMEOUT;
+    let message = InviteCodeMessage::new(&code, SystemTime::now() + INVITE_TIMEOUT);
+    let wait_result = match player.send(message).await {
+        Ok(()) => {
+            let version = player.version();
+            let (websocket, shutdown) = player.connection_mut();
+            tokio::select! {
+                wait_result = wait_for_giver_data(&mut receiver, websocket, shutdown, version, None, None) => {
+                    wait_result.map_err(|e| (e.close_reason(), e.to_string()))
+                },
+                _ = tokio::time::sleep_until(deadline) => {
+                    Err((Some(CloseReason::InviteExpired), String::from("nobody joined in time")))
+                },
+            }
+        }
+        Err(e) => Err((None, e.to_string())),
+    };
+    let (close_reason, cause) = match wait_result {
+        Ok(WaitOutcome::Joined(GiverToExecutorData { giver })) => {
+            log::trace!("{log_id}: A guest joined the private game.");
+            return Some((player, giver));
+        }
+        Ok(WaitOutcome::ChallengeAccepted(_) | WaitOutcome::LeftQueue) => {
+            unreachable!("The host is neither in the lobby nor in the queue.")
+        }
+        Err(error) => error,
+    };
+    invitations.close(&code);
+    // A guest may have joined right before the game was closed.
+    match (close_reason, receiver.try_recv().ok()) {
+        (Some(CloseReason::InviteExpired), Some(GiverToExecutorData { giver })) => {
+            log::trace!("{log_id}: A guest joined the private game at the last moment.");
+            Some((player, giver))
+        }
+        (close_reason, late_guest) => {
+            log::info!("{log_id}: The private game is closed : {cause}.");
+            if let Some(reason) = close_reason {
+                player.close(reason).await;
+            }
+            if let Some(GiverToExecutorData { giver: mut guest }) = late_guest {
+                guest.close(CloseReason::UnknownInviteCode).await;
+            }
+            None
+        }
+    }
+}
+
+/// Hand the player to the host of the private game with the given code. The player is given back if no game is
+/// hosted with this code, or if it is its own.
+#[allow(clippy::result_large_err, reason = "the player is handed back")]
+pub fn join_private_game<S>(
+    player: Player<S>,
+    code: &str,
+    invitations: &Invitations<S>,
+) -> Result<(), Player<S>> {
+    // The lock is held while sending, so that a host closing its game afterwards can find the guest received.
+    let mut hosts = invitations.hosts.lock().unwrap();
+    match hosts.get(code) {
+        Some(invitation) if invitation.host_id != player.id() => {
+            // The entry exists, the removal can't fail.
+            let Invitation { sender, .. } = hosts.remove(code).unwrap();
+            sender
+                .send(GiverToExecutorData { giver: player })
+                .map_err(|GiverToExecutorData { giver }| giver)
+        }
+        _ => Err(player),
+    }
+}
@@ -1,105 +1,77 @@
 //! Joining of two players/connections to a same task.
 //!
-//! This mod contains the logic for a task to send its connection to another task, or be sent another task's
-//! connection, depending on who contacted the matchmaker first. This is implemented in [`join_opponents`].
+//! This mod contains the logic for a player to wait for another task to send it its connection, keeping its own
+//! connection alive in the meantime. This is implemented in [`wait_for_giver_data`], on which the match making queue
+//! and the other ways of pairing players are built.
 
-use std::fmt::Display;
-use std::sync::{Arc, Mutex};
+use std::future;
 use std::time::Duration;
 
 use futures_util::{SinkExt, StreamExt};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, watch};
 use tokio::time::Instant;
 use tokio_tungstenite::tungstenite::{Error, Message};
 use tokio_tungstenite::WebSocketStream;
 
 use crate::game::Player;
-use crate::match_making::MatchMaker;
-
-/// Determine whether another task is waiting or not, then either send or receive the [`WebSocketStream`] and identity.
-///
-/// This function does the minimal amount of blocking work with the [`MatchMaker`] to learn about its role and get the
-/// [`oneshot`] channel needed. It then fulfills its role :
-/// * If it is first, it waits for the other's [`GiverToExecutorData`]. From the caller's point of view, it returns both
-///   [`WebSocketStream`]s. In reality, if this task's [`WebSocketStream`] closes while waiting for the other task's, it
-///   will wait for another task to send itself, and start over as if doing a re-entry in the function.
-/// * If it is second, it sends its own [`GiverToExecutorData`], and returns nothing.
-pub async fn join_opponents<S, D>(
-    mut websocket: WebSocketStream<S>,
-    mut id: String,
-    match_maker: &Arc<MatchMaker<S>>,
-    log_id: &D,
-) -> Option<(Player<S>, Player<S>)>
-where
-    D: Display,
-    S: AsyncRead + AsyncWrite + Unpin,
-{
-    loop {
-        return match GameRunRole::extract_from_mutex(&match_maker.mutex) {
-            GameRunRole::Giver(giver_to_executor_sender) => {
-                log::trace!("{log_id}: GameRunRole is Giver.");
-                let data = GiverToExecutorData {
-                    giver_websocket: websocket,
-                    giver_id: id,
-                };
-                //Sending cannot fail, as we make sure the receiving end is not dropped.
-                giver_to_executor_sender.send(data).map_err(|_| ()).unwrap();
-                None
-            }
-            GameRunRole::Executor(mut giver_to_executor_receiver) => {
-                log::trace!("{log_id}: GameRunRole is Executor. Waiting for Giver data.");
-                let GiverToExecutorData {
-                    giver_websocket,
-                    giver_id,
-                } = match wait_for_giver_data(&mut giver_to_executor_receiver, &mut websocket).await
-                {
-                    Ok(data) => data,
-                    Err(e) => {
-                        log::info!("{log_id}: Disconnection detected");
-                        log::debug!("{log_id}: Disconnection cause : {e} | {e:?}.");
-                        log::info!("{log_id}: Waiting for a Giver to take over this task thread.");
-                        GiverToExecutorData {
-                            giver_websocket: websocket,
-                            giver_id: id,
-                        } = loop {
-                            //Only spurious errors can happen, as we make sure the sending end is not dropped.
-                            if let Ok(data) = (&mut giver_to_executor_receiver).await {
-                                break data;
-                            }
-                        };
-                        log::info!(
-                            "{log_id}: The task thread has been taken over by a new connection."
-                        );
-                        continue;
-                    }
-                };
-                log::trace!("{log_id}: Giver data received.");
-                Some((
-                    Player::new(giver_websocket, giver_id),
-                    Player::new(websocket, id),
-                ))
-            }
-        };
-    }
-}
+use crate::match_making::challenges::{Challenge, Lobby};
+use crate::protocol::{
+    is_leave_queue_request, parse_challenge_answer, send_message, ChallengeMessage,
+    ChallengeStatus, CloseReason, ProtocolVersion, QueueStatusMessage,
+};
+use crate::shutdown::Shutdown;
 
 /// Possible errors when waiting for Giver data.
 #[derive(thiserror::Error, Debug)]
 pub enum WaitError {
     /// This error happens when a poll to a [`WebSocketStream`] returns an error.
     #[error("Connection error or close while waiting for someone to join : {0}")]
-    ConnectionError(#[from] Error),
+    ConnectionError(#[source] Box<Error>),
 
     /// This error happens when a poll to a [`WebSocketStream`] returns [`None`], or that the connection has been
     /// closed.
     #[error("Connection lost while waiting for someone to join : closed or lost")]
     ConnectionLost,
 
+    /// This error happens when the client closes the connection.
+    #[error("The client closed the connection while waiting for someone to join")]
+    Left,
+
+    /// This error is given when the client doesn't answer a ping within [`PONG_TIMEOUT`] seconds.
+    #[error("No pong received in time while waiting for someone to join")]
+    Unresponsive,
+
     /// This error is given when the client sends any data other than a ping, or a pong if waiting for someone to
     /// join. The protocol is enforced strictly.
     #[error("Received an unexpected message from the client : {0}")]
     ProtocolViolation(Message),
+
+    /// This error happens when the server starts shutting down while waiting for someone to join.
+    #[error("The server is shutting down")]
+    ServerShutdown,
+
+    /// This error happens when nobody will join anymore, such as a challenged friend declining.
+    #[error("Nobody will join")]
+    Abandoned,
+}
+
+impl From<Error> for WaitError {
+    fn from(error: Error) -> Self {
+        Self::ConnectionError(Box::new(error))
+    }
+}
+
+impl WaitError {
+    /// The reason to give the client when closing its connection, if it can still be told anything.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::ConnectionError(_) | Self::ConnectionLost | Self::Left | Self::Abandoned => None,
+            Self::Unresponsive => Some(CloseReason::Unresponsive),
+            Self::ProtocolViolation(_) => Some(CloseReason::ProtocolViolation),
+            Self::ServerShutdown => Some(CloseReason::ServerShutdown),
+        }
+    }
 }
 
 /// Time between each keep-alive ping sent to the client.
@@ -109,14 +81,36 @@ const PONG_TIMEOUT: u64 = 5;
 /// The ping payload - `b2sum(wait_for_giver_data)`.
 const PING_PAYLOAD: [u8; 8] = [1, 3, 0, 7, 3, 15, 3, 4];
 
-/// Wait for the [`GiverToExecutorData`] to be received.
+/// What ended the wait of a player for an opponent, or for whatever `T` it waits for.
+pub(super) enum WaitOutcome<S, T = GiverToExecutorData<S>> {
+    /// The opponent joined.
+    Joined(T),
+    /// The player accepted the challenge of a friend, and stopped waiting. It is up to the caller to join the
+    /// challenger.
+    ChallengeAccepted(Challenge<S>),
+    /// The player asked to leave the match making queue.
+    LeftQueue,
+}
+
+/// Wait for the [`GiverToExecutorData`], or any other `T`, to be received.
 ///
 /// In the meantime, pings are answered and some pongs are sent on a regular basis. If the client disconnects or doesn't
-/// answer pings, return a [`WaitError`].
-async fn wait_for_giver_data<S>(
-    giver_to_executor_receiver: &mut oneshot::Receiver<GiverToExecutorData<S>>,
+/// answer pings, or if the server starts shutting down, return a [`WaitError`]. If the sending end is dropped, nobody
+/// will join : return [`WaitError::Abandoned`].
+///
+/// If the player is in the [`Lobby`], the challenges of its friends are forwarded to its client, one at a time, with
+/// the `version` it speaks. The wait ends if the client accepts one.
+///
+/// If the player is in the match making queue, the `queue_status` updates are forwarded to its client. The wait ends
+/// if the client asks to leave the queue, from the version of the protocol that lets it.
+pub(super) async fn wait_for_giver_data<S, T>(
+    giver_to_executor_receiver: &mut oneshot::Receiver<T>,
     executor_websocket: &mut WebSocketStream<S>,
-) -> Result<GiverToExecutorData<S>, WaitError>
+    shutdown: &mut Shutdown,
+    version: ProtocolVersion,
+    mut lobby: Option<&mut Lobby<'_, S>>,
+    mut queue_status: Option<&mut watch::Receiver<QueueStatusMessage>>,
+) -> Result<WaitOutcome<S, T>, WaitError>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
@@ -126,14 +120,13 @@ where
     );
     let mut waiting_for_pong = false;
     let mut pong_timeout = Instant::now();
+    let mut pending_challenge: Option<Challenge<S>> = None;
     loop {
         tokio::select! {
             receive_result = &mut *giver_to_executor_receiver => {
-                //All errors should be spurious and fine to ignore, as the program logic doesn't allow the sending end
-                //to close before the receiving end.
-                if let Ok(data) = receive_result {
-                    return Ok(data);
-                }
+                return receive_result
+                    .map(WaitOutcome::Joined)
+                    .map_err(|_| WaitError::Abandoned);
             },
             _ = ping_interval.tick() => {
                 //Time to send a ping.
@@ -142,17 +135,80 @@ where
                 pong_timeout = Instant::now() + Duration::from_secs(PONG_TIMEOUT);
             },
             _ = tokio::time::sleep_until(pong_timeout), if waiting_for_pong => {
-                //No ping received in time.
-                return Err(WaitError::ConnectionLost);
+                //No pong received in time.
+                return Err(WaitError::Unresponsive);
             },
-            msg = executor_websocket.next() => {
+            challenge = next_challenge(&mut lobby), if pending_challenge.is_none() => {
+                //Tell the client, and wait for its answer.
+                let message = ChallengeMessage::new(ChallengeStatus::Challenged, &challenge.challenger_id);
+                send_message(executor_websocket, version, message).await?;
+                pending_challenge = Some(challenge);
+            },
+            () = challenge_withdrawal(&mut pending_challenge) => {
+                //The challenger stopped waiting for an answer.
+                if let Some(challenge) = pending_challenge.take() {
+                    let message = ChallengeMessage::new(ChallengeStatus::Withdrawn, &challenge.challenger_id);
+                    send_message(executor_websocket, version, message).await?;
+                }
+            },
+            status = next_queue_status(&mut queue_status) => {
+                send_message(executor_websocket, version, status).await?;
+            },
+            msg = executor_websocket.next() => match msg {
+                Some(Ok(Message::Binary(b)))
+                    if queue_status.is_some() && version.leaves_queue() && is_leave_queue_request(&b) =>
+                {
+                    return Ok(WaitOutcome::LeftQueue);
+                }
+                //Answer to the pending challenge. Declining it means dropping it.
+                Some(Ok(Message::Binary(b))) if pending_challenge.is_some() => {
+                    match parse_challenge_answer(&b) {
+                        Ok(true) => {
+                            //The pending challenge was just checked to be there.
+                            return Ok(WaitOutcome::ChallengeAccepted(pending_challenge.take().unwrap()));
+                        }
+                        Ok(false) => pending_challenge = None,
+                        Err(_) => return Err(WaitError::ProtocolViolation(Message::Binary(b))),
+                    }
+                }
                 //Handle message, potentially clearing the ping timeout if we were waiting on one.
-                waiting_for_pong = handle_websocket_event(msg, waiting_for_pong)?;
+                msg => waiting_for_pong = handle_websocket_event(msg, waiting_for_pong)?,
             },
+            () = shutdown.notified() => return Err(WaitError::ServerShutdown),
         }
     }
 }
 
+/// Wait for the next challenge, if the player is in the lobby.
+async fn next_challenge<S>(lobby: &mut Option<&mut Lobby<'_, S>>) -> Challenge<S> {
+    match lobby {
+        Some(lobby) => lobby.next_challenge().await,
+        None => future::pending().await,
+    }
+}
+
+/// Wait for the next update of the queue status, if the player is in the match making queue.
+async fn next_queue_status(
+    queue_status: &mut Option<&mut watch::Receiver<QueueStatusMessage>>,
+) -> QueueStatusMessage {
+    match queue_status {
+        //The sending end is only dropped once the player leaves the queue.
+        Some(receiver) => match receiver.changed().await {
+            Ok(()) => receiver.borrow_and_update().clone(),
+            Err(_) => future::pending().await,
+        },
+        None => future::pending().await,
+    }
+}
+
+/// Wait for the challenger of the pending challenge, if any, to stop waiting for an answer.
+async fn challenge_withdrawal<S>(pending_challenge: &mut Option<Challenge<S>>) {
+    match pending_challenge {
+        Some(challenge) => challenge.reply.closed().await,
+        None => future::pending().await,
+    }
+}
+
 /// Handle a message received on the given websocket, expecting
 /// * a ping
 /// * a pong if `waiting_for_pong` is `true`
@@ -163,7 +219,7 @@ where
 ///
 /// The [`Ok`] return value will contain whether to keep waiting for a pong : if `waiting_for_pong` was `false` it will
 /// be false, and if the latter was `true` it will be turned to `false` if the expected pong is received.
-fn handle_websocket_event(
+pub(super) fn handle_websocket_event(
     message: Option<Result<Message, Error>>,
     waiting_for_pong: bool,
 ) -> Result<bool, WaitError> {
@@ -178,45 +234,17 @@ fn handle_websocket_event(
                 Err(WaitError::ProtocolViolation(Message::Pong(p)))
             }
         }
+        //The client is leaving.
+        Some(Ok(Message::Close(_))) => Err(WaitError::Left),
         //Received a message we're not expecting.
         Some(Ok(message)) => Err(WaitError::ProtocolViolation(message)),
         //Connection is closed, failed or anything like that.
         None | Some(Err(Error::ConnectionClosed)) => Err(WaitError::ConnectionLost),
-        Some(Err(e)) => Err(WaitError::ConnectionError(e)),
+        Some(Err(e)) => Err(WaitError::ConnectionError(Box::new(e))),
     }
 }
 
-/// Connection and identity sent from the second task contacting the matchmaker to one already waiting.
+/// Player sent from a task to another one waiting for it, such as the host of a private game or a challenger.
 pub(super) struct GiverToExecutorData<S> {
-    giver_websocket: WebSocketStream<S>,
-    giver_id: String,
-}
-
-/// The role of this task regarding who sends and who receives the other task's connection.
-enum GameRunRole<S> {
-    /// This task contacted the matchmaker first, and waits for another one to send its [`GiverToExecutorData`]. It will
-    /// execute whatever they want to do together.
-    Executor(oneshot::Receiver<GiverToExecutorData<S>>),
-    /// This task contacted the matchmaker second. It will send its data through a [`oneshot`] channel.
-    Giver(oneshot::Sender<GiverToExecutorData<S>>),
-}
-
-impl<S> GameRunRole<S> {
-    /// * If the [`Mutex`] contains a [`oneshot::Sender`], consume it and return as a [`Self::Giver`].
-    /// * If the [`Mutex`] is empty, create a [`oneshot::channel`], and return as a [`Self::Executor`].
-    fn extract_from_mutex(
-        mutex: &Mutex<Option<oneshot::Sender<GiverToExecutorData<S>>>>,
-    ) -> GameRunRole<S> {
-        // The lock cannot panic as nothing in the guard's scope can panic.
-        let mut maybe_giver_data_sender = mutex.lock().unwrap();
-        if maybe_giver_data_sender.is_some() {
-            //The take cannot be None and panic, as we just checked that the option is Some.
-            let giver_to_executor_sender = maybe_giver_data_sender.take().unwrap();
-            GameRunRole::Giver(giver_to_executor_sender)
-        } else {
-            let (giver_to_executor_sender, giver_to_executor_receiver) = oneshot::channel();
-            *maybe_giver_data_sender = Some(giver_to_executor_sender);
-            GameRunRole::Executor(giver_to_executor_receiver)
-        }
-    }
+    pub(super) giver: Player<S>,
 }
@@ -0,0 +1,486 @@
+//! The match making queue, owned by a dedicated task.
+//!
+//! The tasks handling the connections hand their players over to the [`QueueService`] through the [`MatchMaker`], and
+//! are done with them. The service keeps the connections of the waiting players alive, drops them as soon as they
+//! disconnect, pairs them according to their [`SearchCriteria`], and spawns a task for each game. If enabled, a ready
+//! check is run between the pairing of two players and the start of their game.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::game::Player;
+use crate::match_making::challenges::{withdraw_accepted_challenge, Challenge, Challenges};
+use crate::match_making::opponents_joining::{
+    wait_for_giver_data, GiverToExecutorData, WaitError, WaitOutcome,
+};
+use crate::match_making::ready_check::{ready_check, Matched, ReadyCheck};
+use crate::match_making::search::{self, MatchFormat, SearchCriteria, Seeker};
+use crate::protocol::{CloseReason, QueueStatusMessage};
+use crate::shutdown::Shutdown;
+
+/// Time between two pairings of the players waiting in the queue, as their searches widen.
+const PAIRING_INTERVAL: Duration = Duration::from_secs(1);
+/// Time between two queue status updates sent to the waiting players.
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A server-wide structure, shared in the [`ServerContext`], through which players are put in the match making queue
+/// of the [`QueueService`].
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct MatchMaker<S> {
+    commands: mpsc::UnboundedSender<Command<S>>,
+}
+
+/// A player handed over to the match making queue, along with the id its connection is logged with.
+pub struct QueuedPlayer<S> {
+    pub player: Player<S>,
+    pub log_id: String,
+}
+
+/// What the [`MatchMaker`] asks the [`QueueService`].
+enum Command<S> {
+    /// Put the player in the queue, to search for an opponent meeting the criteria.
+    Enqueue(QueuedPlayer<S>, SearchCriteria),
+}
+
+impl<S> MatchMaker<S> {
+    /// Creates a new [`MatchMaker`] instance, along with the [`QueueService`] to run for the players it queues to be
+    /// paired. If a `ready_check` timeout is given, paired players have that long to confirm they are ready to play.
+    pub fn new(ready_check: Option<Duration>) -> (MatchMaker<S>, QueueService<S>) {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let service = QueueService {
+            commands: receiver,
+            ready_check,
+        };
+        (MatchMaker { commands }, service)
+    }
+
+    /// Put the player in the queue, to search for an opponent meeting the criteria. The player is given back if the
+    /// [`QueueService`] has stopped, as the server is shutting down.
+    #[allow(clippy::result_large_err, reason = "the player is handed back")]
+    pub fn enqueue(
+        &self,
+        queued: QueuedPlayer<S>,
+        criteria: SearchCriteria,
+    ) -> Result<(), QueuedPlayer<S>> {
+        self.commands
+            .send(Command::Enqueue(queued, criteria))
+            .map_err(|mpsc::error::SendError(Command::Enqueue(queued, _))| queued)
+    }
+}
+
+/// The task owning the match making queue. See [`QueueService::run`].
+pub struct QueueService<S> {
+    commands: mpsc::UnboundedReceiver<Command<S>>,
+    ready_check: Option<Duration>,
+}
+
+/// A player waiting in the queue. Its connection is watched by the service until the `release` is sent, and its client
+/// is sent the updates of its `status`.
+struct Waiting {
+    ticket: u64,
+    seeker: Seeker,
+    release: oneshot::Sender<()>,
+    status: watch::Sender<QueueStatusMessage>,
+}
+
+/// A player that just entered the queue, whose connection is to be watched.
+struct Unwatched<S> {
+    ticket: u64,
+    queued: QueuedPlayer<S>,
+    release: oneshot::Receiver<()>,
+    status: watch::Receiver<QueueStatusMessage>,
+}
+
+/// A player paired with the holder of the `partner` ticket. Its connection is given back by its watch, unless it was
+/// paired right as it came in.
+struct Paired<S> {
+    partner: u64,
+    seeker: Seeker,
+    queued: Option<QueuedPlayer<S>>,
+}
+
+impl<S> QueueService<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Run the match making queue until the server starts shutting down.
+    ///
+    /// The connections of the waiting players are kept alive, and they can be challenged by their friends through the
+    /// [`Challenges`]. A player coming in is paired with the opponent it chooses among the waiting ones, see
+    /// [`search::choose_opponent`]. The waiting players are also paired together every [`PAIRING_INTERVAL`], as their
+    /// searches widen. Each pair is passed to `start_game` with the format its players want, and its future is spawned
+    /// on its own task.
+    ///
+    /// If the service has a ready check timeout, both players of a pair must confirm they are ready before their game
+    /// starts, see [`ready_check`]. The ones who were are put back at the front of the queue if the other wasn't.
+    ///
+    /// Once the server starts shutting down, the waiting players are disconnected, and the games are waited for.
+    pub async fn run<F, G>(
+        mut self,
+        challenges: &Challenges<S>,
+        mut shutdown: Shutdown,
+        mut start_game: F,
+    ) where
+        F: FnMut(QueuedPlayer<S>, QueuedPlayer<S>, MatchFormat) -> G,
+        G: Future<Output = ()> + Send + 'static,
+    {
+        let mut queue = Queue {
+            next_ticket: 0,
+            waiting: Vec::new(),
+            paired: HashMap::new(),
+            unwatched: Vec::new(),
+        };
+        let mut watches = FuturesUnordered::new();
+        let mut checks = FuturesUnordered::new();
+        let mut games = JoinSet::new();
+        let mut pairing_interval = tokio::time::interval(PAIRING_INTERVAL);
+        let mut status_interval = tokio::time::interval(STATUS_INTERVAL);
+        loop {
+            tokio::select! {
+                command = self.commands.recv() => match command {
+                    Some(Command::Enqueue(queued, criteria)) => {
+                        log::trace!("{}: Entering the match making queue.", queued.log_id);
+                        let seeker = Seeker {
+                            id: String::from(queued.player.id()),
+                            criteria,
+                            since: Instant::now(),
+                        };
+                        queue.admit(queued, seeker, false);
+                        queue.publish_status();
+                    }
+                    // The handle lives in the server context, which outlives this task.
+                    None => break,
+                },
+                Some((ticket, queued)) = watches.next() => {
+                    if let Some((left, right)) = queue.watch_ended(ticket, queued) {
+                        match self.ready_check {
+                            Some(timeout) => {
+                                log::trace!(
+                                    "{}: Paired with {}. Checking they are ready.",
+                                    left.queued.log_id,
+                                    right.queued.log_id
+                                );
+                                checks.push(ready_check(left, right, timeout));
+                            }
+                            None => {
+                                log::trace!(
+                                    "{}: Paired with {}. Playing a game.",
+                                    left.queued.log_id,
+                                    right.queued.log_id
+                                );
+                                games.spawn(start_game(left.queued, right.queued, left.seeker.criteria.format));
+                            }
+                        }
+                    }
+                },
+                Some(outcome) = checks.next() => match outcome {
+                    ReadyCheck::Ready(left, right) => {
+                        log::trace!(
+                            "{}: Both ready with {}. Playing a game.",
+                            left.queued.log_id,
+                            right.queued.log_id
+                        );
+                        games.spawn(start_game(left.queued, right.queued, left.seeker.criteria.format));
+                    }
+                    ReadyCheck::Cancelled(requeued) => {
+                        for Matched { queued, seeker } in requeued {
+                            queue.admit(queued, seeker, true);
+                        }
+                        queue.publish_status();
+                    }
+                },
+                _ = pairing_interval.tick() => queue.pair_waiting_players(),
+                _ = status_interval.tick() => queue.publish_status(),
+                Some(_) = games.join_next() => {},
+                () = shutdown.notified() => break,
+            }
+            for unwatched in queue.unwatched.drain(..) {
+                watches.push(watch(unwatched, challenges));
+            }
+        }
+
+        // The watches close the connections themselves, as they are notified of the shutdown too.
+        self.commands.close();
+        while let Some(Command::Enqueue(mut queued, _)) = self.commands.recv().await {
+            queued.player.close(CloseReason::ServerShutdown).await;
+        }
+        let held = queue.paired.drain().filter_map(|(_, paired)| paired.queued);
+        let unwatched = queue.unwatched.drain(..).map(|unwatched| unwatched.queued);
+        for mut queued in held.chain(unwatched) {
+            queued.player.close(CloseReason::ServerShutdown).await;
+        }
+        while let Some((_, released)) = watches.next().await {
+            if let Some(mut queued) = released {
+                queued.player.close(CloseReason::ServerShutdown).await;
+            }
+        }
+        // The ready checks close the connections of the players they wait for, but not of the ones they give back.
+        while let Some(outcome) = checks.next().await {
+            let matched = match outcome {
+                ReadyCheck::Ready(left, right) => vec![left, right],
+                ReadyCheck::Cancelled(requeued) => requeued,
+            };
+            for mut matched in matched {
+                matched
+                    .queued
+                    .player
+                    .close(CloseReason::ServerShutdown)
+                    .await;
+            }
+        }
+        while games.join_next().await.is_some() {}
+    }
+}
+
+/// The state of the match making queue, in the [`QueueService`] task.
+struct Queue<S> {
+    next_ticket: u64,
+    /// The waiting players, in order of arrival, save for the ones put back at the front after a cancelled match.
+    waiting: Vec<Waiting>,
+    /// The paired players, by ticket, until both of a pair have been given back by their watches.
+    paired: HashMap<u64, Paired<S>>,
+    /// The players that just entered the queue.
+    unwatched: Vec<Unwatched<S>>,
+}
+
+impl<S> Queue<S> {
+    /// Pair the player with the waiting player it chooses, or queue it if it chooses none. It is queued at the `front`
+    /// if asked to, or else by its arrival.
+    fn admit(&mut self, queued: QueuedPlayer<S>, seeker: Seeker, front: bool) {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        let waiting = self.waiting.iter().map(|waiting| &waiting.seeker);
+        match search::choose_opponent(&seeker, waiting, Instant::now()) {
+            Some(position) => {
+                let opponent = self.waiting.remove(position);
+                let paired = Paired {
+                    partner: opponent.ticket,
+                    seeker,
+                    queued: Some(queued),
+                };
+                self.paired.insert(ticket, paired);
+                self.release(opponent, ticket);
+            }
+            None => {
+                let (release, release_receiver) = oneshot::channel();
+                let (status, status_receiver) = watch::channel(QueueStatusMessage::new(
+                    Duration::ZERO,
+                    0,
+                    seeker.range(Instant::now()),
+                ));
+                // A player that was paired keeps its place, in case its opponent left.
+                let position = if front {
+                    0
+                } else {
+                    self.waiting
+                        .partition_point(|waiting| waiting.seeker.since <= seeker.since)
+                };
+                let waiting = Waiting {
+                    ticket,
+                    seeker,
+                    release,
+                    status,
+                };
+                self.waiting.insert(position, waiting);
+                self.unwatched.push(Unwatched {
+                    ticket,
+                    queued,
+                    release: release_receiver,
+                    status: status_receiver,
+                });
+            }
+        }
+    }
+
+    /// Update the queue status of every waiting player : the time it waited for, the number of players in the queue,
+    /// and the rating difference it accepts.
+    fn publish_status(&self) {
+        let now = Instant::now();
+        for waiting in &self.waiting {
+            let waited = now.saturating_duration_since(waiting.seeker.since);
+            let status =
+                QueueStatusMessage::new(waited, self.waiting.len(), waiting.seeker.range(now));
+            // Sending fails only if the watch already ended, which is handled once it is polled.
+            let _: Result<_, _> = waiting.status.send(status);
+        }
+    }
+
+    /// Take the connection of the waiting player back from its watch, to pair it with the holder of the `partner`
+    /// ticket.
+    fn release(&mut self, waiting: Waiting, partner: u64) {
+        // Sending fails only if the watch already ended, which is handled once it is polled.
+        let _: Result<_, _> = waiting.release.send(());
+        let paired = Paired {
+            partner,
+            seeker: waiting.seeker,
+            queued: None,
+        };
+        self.paired.insert(waiting.ticket, paired);
+    }
+
+    /// Pair the waiting players whose searches have widened enough to accept each other.
+    fn pair_waiting_players(&mut self) {
+        let seekers: Vec<&Seeker> = self.waiting.iter().map(|waiting| &waiting.seeker).collect();
+        let pairs = search::pair_up(&seekers, Instant::now());
+        if pairs.is_empty() {
+            return;
+        }
+        let mut waiting: Vec<_> = self.waiting.drain(..).map(Some).collect();
+        for (first, second) in pairs {
+            // Each player is in one pair at most, so it is still there to take.
+            let first = waiting[first].take().unwrap();
+            let second = waiting[second].take().unwrap();
+            let (first_ticket, second_ticket) = (first.ticket, second.ticket);
+            self.release(first, second_ticket);
+            self.release(second, first_ticket);
+        }
+        self.waiting.extend(waiting.into_iter().flatten());
+    }
+
+    /// Handle the end of the watch of the player holding the ticket, which gives the player back if it was released.
+    /// Returns the players of a match, left then right, once both of a pair have been given back.
+    ///
+    /// A player that left the queue is forgotten. If it was paired, its partner is put back in the queue.
+    fn watch_ended(
+        &mut self,
+        ticket: u64,
+        queued: Option<QueuedPlayer<S>>,
+    ) -> Option<(Matched<S>, Matched<S>)> {
+        let Some(queued) = queued else {
+            self.waiting.retain(|waiting| waiting.ticket != ticket);
+            let partner = self.paired.remove(&ticket)?.partner;
+            if let Some(Paired {
+                queued: Some(_), ..
+            }) = self.paired.get(&partner)
+            {
+                let Paired { seeker, queued, .. } = self.paired.remove(&partner)?;
+                self.admit(queued?, seeker, false);
+            }
+            return None;
+        };
+        // A watch only gives its player back once released, which pairs it.
+        let paired = self.paired.get_mut(&ticket)?;
+        paired.queued = Some(queued);
+        let partner = paired.partner;
+        match self.paired.get(&partner) {
+            Some(Paired {
+                queued: Some(_), ..
+            }) => {
+                let first = self.paired.remove(&ticket)?;
+                let second = self.paired.remove(&partner)?;
+                // The latest to come in plays on the left.
+                let (left, right) = if first.seeker.since >= second.seeker.since {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                let left = Matched {
+                    queued: left.queued?,
+                    seeker: left.seeker,
+                };
+                let right = Matched {
+                    queued: right.queued?,
+                    seeker: right.seeker,
+                };
+                Some((left, right))
+            }
+            Some(_) => None,
+            None => {
+                let Paired { seeker, queued, .. } = self.paired.remove(&ticket)?;
+                self.admit(queued?, seeker, false);
+                None
+            }
+        }
+    }
+}
+
+/// Keep the connection of the waiting player alive until it is released, forwarding it its queue status and the
+/// challenges of its friends. Returns the ticket of the player, along with the player if it has been released. Nothing
+/// is given back if it left the queue : it asked to, disconnected, accepted a challenge, or the server is shutting
+/// down.
+async fn watch<S>(
+    unwatched: Unwatched<S>,
+    challenges: &Challenges<S>,
+) -> (u64, Option<QueuedPlayer<S>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Unwatched {
+        ticket,
+        mut queued,
+        mut release,
+        mut status,
+    } = unwatched;
+    loop {
+        let version = queued.player.version();
+        // Friends can only challenge the players whose version has the challenges.
+        let mut lobby = version
+            .has_challenges()
+            .then(|| challenges.enter(queued.player.id()));
+        let (websocket, shutdown) = queued.player.connection_mut();
+        let wait_result = wait_for_giver_data(
+            &mut release,
+            websocket,
+            shutdown,
+            version,
+            lobby.as_mut(),
+            Some(&mut status),
+        )
+        .await;
+        drop(lobby);
+        let log_id = &queued.log_id;
+        match wait_result {
+            Ok(WaitOutcome::Joined(())) => return (ticket, Some(queued)),
+            Ok(WaitOutcome::LeftQueue) => {
+                log::info!("{log_id}: Left the match making queue.");
+                queued.player.close(CloseReason::Done).await;
+                return (ticket, None);
+            }
+            Ok(WaitOutcome::ChallengeAccepted(Challenge {
+                challenger_id,
+                reply,
+            })) => {
+                log::trace!("{log_id}: The challenge of {challenger_id} has been accepted.");
+                let QueuedPlayer { player, log_id } = queued;
+                match reply.send(GiverToExecutorData { giver: player }) {
+                    Ok(()) => {
+                        log::info!(
+                            "{log_id}: Connection has been given away to the challenger's task."
+                        );
+                        return (ticket, None);
+                    }
+                    Err(GiverToExecutorData { giver }) => {
+                        log::trace!("{log_id}: The challenger left. Back to the queue.");
+                        queued = QueuedPlayer {
+                            player: giver,
+                            log_id,
+                        };
+                        withdraw_accepted_challenge(&mut queued.player, &challenger_id).await;
+                    }
+                }
+            }
+            Err(WaitError::ServerShutdown) => {
+                log::info!("{log_id}: Server shutdown while waiting for an opponent.");
+                queued.player.close(CloseReason::ServerShutdown).await;
+                return (ticket, None);
+            }
+            Err(e) => {
+                log::info!("{log_id}: Disconnection detected while waiting for an opponent.");
+                log::debug!("{log_id}: Disconnection cause : {e} | {e:?}.");
+                if let Some(reason) = e.close_reason() {
+                    queued.player.close(reason).await;
+                }
+                return (ticket, None);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Confirmation by two paired players that they are ready to play, before their game starts.
+//!
+//! Both clients are told the match was found, along with the name of their opponent, and must confirm within the
+//! configured time. A player who declines or doesn't answer is removed from the queue, and the other one is put back
+//! at the front of it.
+
+use std::time::{Duration, SystemTime};
+
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::match_making::opponents_joining::{handle_websocket_event, WaitError};
+use crate::match_making::queue::QueuedPlayer;
+use crate::match_making::search::Seeker;
+use crate::protocol::{parse_ready_answer, BackInQueueMessage, CloseReason, MatchFoundMessage};
+
+/// A player paired by the queue, along with what it searched for, to put it back in the queue if need be.
+pub(super) struct Matched<S> {
+    pub(super) queued: QueuedPlayer<S>,
+    pub(super) seeker: Seeker,
+}
+
+/// The outcome of a ready check.
+#[allow(clippy::large_enum_variant, reason = "the players are handed back")]
+pub(super) enum ReadyCheck<S> {
+    /// Both players confirmed : the game can start. The players are given left then right.
+    Ready(Matched<S>, Matched<S>),
+    /// A player was not ready. The players who were, and who have been told they are back in the queue, are given
+    /// back to be queued at its front.
+    Cancelled(Vec<Matched<S>>),
+}
+
+/// Ask both players to confirm they are ready to play against each other within the `timeout`.
+///
+/// Both are waited for, so that a player is only put back in the queue once it answered. The connections of the
+/// players who are not ready are closed.
+pub(super) async fn ready_check<S>(
+    mut left: Matched<S>,
+    mut right: Matched<S>,
+    timeout: Duration,
+) -> ReadyCheck<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (left_ready, right_ready) = tokio::join!(
+        confirm(&mut left.queued, &right.seeker.id, timeout),
+        confirm(&mut right.queued, &left.seeker.id, timeout),
+    );
+    if left_ready && right_ready {
+        return ReadyCheck::Ready(left, right);
+    }
+    let mut requeued = Vec::new();
+    for (mut matched, ready) in [(left, left_ready), (right, right_ready)] {
+        if !ready {
+            continue;
+        }
+        let log_id = &matched.queued.log_id;
+        match matched.queued.player.send(BackInQueueMessage).await {
+            Ok(()) => {
+                log::trace!("{log_id}: The opponent is not ready. Back to the front of the queue.");
+                requeued.push(matched);
+            }
+            Err(e) => {
+                log::info!("{log_id}: Disconnection detected after a cancelled match.");
+                log::debug!("{log_id}: Disconnection cause : {e} | {e:?}.");
+            }
+        }
+    }
+    ReadyCheck::Cancelled(requeued)
+}
+
+/// Tell the player a match was found against the `opponent`, and wait for it to confirm it is ready within the
+/// `timeout`. Returns whether it is. Otherwise, its connection is closed. A player whose version of the protocol has no
+/// ready check is ready right away.
+async fn confirm<S>(queued: &mut QueuedPlayer<S>, opponent: &str, timeout: Duration) -> bool
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let deadline = Instant::now() + timeout;
+    let log_id = &queued.log_id;
+    if !queued.player.version().has_ready_check() {
+        log::trace!("{log_id}: No ready check in the version of the client. Ready to play against {opponent}.");
+        return true;
+    }
+    let message = MatchFoundMessage::new(opponent, SystemTime::now() + timeout);
+    let answer = match queued.player.send(message).await {
+        Ok(()) => {
+            let (websocket, shutdown) = queued.player.connection_mut();
+            loop {
+                tokio::select! {
+                    msg = websocket.next() => match msg {
+                        Some(Ok(Message::Binary(b))) => match parse_ready_answer(&b) {
+                            Ok(ready) => break Ok(Some(ready)),
+                            Err(_) => break Err(WaitError::ProtocolViolation(Message::Binary(b))),
+                        },
+                        //No ping is sent, so no pong is expected.
+                        msg => if let Err(e) = handle_websocket_event(msg, false) {
+                            break Err(e);
+                        },
+                    },
+                    () = tokio::time::sleep_until(deadline) => break Ok(None),
+                    () = shutdown.notified() => break Err(WaitError::ServerShutdown),
+                }
+            }
+        }
+        Err(e) => Err(WaitError::ConnectionError(Box::new(e))),
+    };
+    match answer {
+        Ok(Some(true)) => {
+            log::trace!("{log_id}: Ready to play against {opponent}.");
+            return true;
+        }
+        Ok(Some(false)) => {
+            log::info!("{log_id}: Declined to play against {opponent}. Leaving the queue.");
+            queued.player.close(CloseReason::Done).await;
+        }
+        Ok(None) => {
+            log::info!("{log_id}: Did not confirm the match in time. Leaving the queue.");
+            queued.player.close(CloseReason::NotReady).await;
+        }
+        Err(WaitError::ServerShutdown) => {
+            log::info!("{log_id}: Server shutdown while confirming a match.");
+            queued.player.close(CloseReason::ServerShutdown).await;
+        }
+        Err(e) => {
+            log::info!("{log_id}: Disconnection detected while confirming a match.");
+            log::debug!("{log_id}: Disconnection cause : {e} | {e:?}.");
+            if let Some(reason) = e.close_reason() {
+                queued.player.close(reason).await;
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,201 @@
+//! Choice of the opponents of the players waiting in the match making queue.
+//!
+//! Players are paired with opponents of a close rating. The rating difference each of them accepts widens the longer
+//! it waits, so that nobody waits forever. League members with an unplayed fixture between them are paired whatever
+//! their ratings. Only players wanting the same game rules, and the same series length if any, are ever paired.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::game::{GameRules, SeriesLength};
+
+/// Rating difference accepted by a player as soon as it enters the queue.
+const INITIAL_RANGE: u32 = 100;
+/// Widening of the accepted rating difference for every second waited.
+const RANGE_GROWTH_PER_SECOND: u32 = 20;
+
+/// The games a player wants to play : their rules, and the length of the series they make up, if any.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MatchFormat {
+    pub rules: GameRules,
+    pub series: Option<SeriesLength>,
+}
+
+/// What a player entering the queue looks for in an opponent.
+#[derive(Clone)]
+pub struct SearchCriteria {
+    /// The games it wants to play.
+    pub format: MatchFormat,
+    pub rating: i32,
+    /// The usernames of the players it has an unplayed league fixture against.
+    pub fixture_opponents: HashSet<String>,
+}
+
+/// A player searching for an opponent, since a given instant.
+#[derive(Clone)]
+pub(super) struct Seeker {
+    pub(super) id: String,
+    pub(super) criteria: SearchCriteria,
+    pub(super) since: Instant,
+}
+
+impl Seeker {
+    /// The rating difference this player accepts at the given instant.
+    pub(super) fn range(&self, now: Instant) -> u32 {
+        search_range(now.saturating_duration_since(self.since))
+    }
+}
+
+/// The rating difference accepted by a player that has waited for the given time.
+pub(super) fn search_range(waited: Duration) -> u32 {
+    let seconds = u32::try_from(waited.as_secs()).unwrap_or(u32::MAX);
+    INITIAL_RANGE.saturating_add(RANGE_GROWTH_PER_SECOND.saturating_mul(seconds))
+}
+
+/// Whether the two players have an unplayed fixture between them.
+fn have_fixture(first: &Seeker, second: &Seeker) -> bool {
+    first.criteria.fixture_opponents.contains(&second.id)
+        || second.criteria.fixture_opponents.contains(&first.id)
+}
+
+/// Whether the two players accept each other : their rating difference is within the widest of their ranges.
+fn within_range(first: &Seeker, second: &Seeker, now: Instant) -> bool {
+    let difference = first.criteria.rating.abs_diff(second.criteria.rating);
+    difference <= first.range(now).max(second.range(now))
+}
+
+/// Choose the opponent of the seeker among the waiting players, given in order of arrival. That is the first one it
+/// has a fixture against, or else the first one within range, and never a player with the same id or wanting another
+/// format. Returns its position.
+pub(super) fn choose_opponent<'a>(
+    seeker: &Seeker,
+    waiting: impl IntoIterator<Item = &'a Seeker>,
+    now: Instant,
+) -> Option<usize> {
+    let candidates: Vec<(usize, &Seeker)> = waiting
+        .into_iter()
+        .enumerate()
+        .filter(|(_, waiting)| {
+            waiting.id != seeker.id && waiting.criteria.format == seeker.criteria.format
+        })
+        .collect();
+    candidates
+        .iter()
+        .find(|(_, waiting)| have_fixture(seeker, waiting))
+        .or_else(|| {
+            candidates
+                .iter()
+                .find(|(_, waiting)| within_range(seeker, waiting, now))
+        })
+        .map(|&(position, _)| position)
+}
+
+/// Pair the waiting players, given in order of arrival, who accept each other now. Each player, from the one waiting
+/// the longest, is paired with the opponent it would choose among those who came after it. Returns the positions of
+/// the pairs, the earlier player first.
+pub(super) fn pair_up(waiting: &[&Seeker], now: Instant) -> Vec<(usize, usize)> {
+    let mut paired = vec![false; waiting.len()];
+    let mut pairs = Vec::new();
+    for first in 0..waiting.len() {
+        if paired[first] {
+            continue;
+        }
+        let later: Vec<usize> = (first + 1..waiting.len()).filter(|&i| !paired[i]).collect();
+        let candidates = later.iter().map(|&i| waiting[i]);
+        if let Some(chosen) = choose_opponent(waiting[first], candidates, now) {
+            let second = later[chosen];
+            paired[first] = true;
+            paired[second] = true;
+            pairs.push((first, second));
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeker(id: &str, rating: i32, since: Instant, fixture_opponents: &[&str]) -> Seeker {
+        Seeker {
+            id: String::from(id),
+            criteria: SearchCriteria {
+                format: MatchFormat {
+                    rules: GameRules::default(),
+                    series: None,
+                },
+                rating,
+                fixture_opponents: fixture_opponents
+                    .iter()
+                    .map(|&id| String::from(id))
+                    .collect(),
+            },
+            since,
+        }
+    }
+
+    #[test]
+    fn range_widens_with_time() {
+        assert_eq!(search_range(Duration::ZERO), INITIAL_RANGE);
+        assert_eq!(
+            search_range(Duration::from_millis(10_500)),
+            INITIAL_RANGE + 10 * RANGE_GROWTH_PER_SECOND
+        );
+        assert_eq!(search_range(Duration::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn opponent_choice() {
+        let now = Instant::now();
+        let newcomer = seeker("ann", 1500, now, &["cid"]);
+        let bob = seeker("bob", 1550, now, &[]);
+        let cid = seeker("cid", 2500, now, &[]);
+        let dan = seeker("dan", 1900, now - Duration::from_secs(20), &[]);
+        let ann = seeker("ann", 1500, now, &[]);
+
+        // Fixture opponents come first, whatever their rating.
+        assert_eq!(choose_opponent(&newcomer, [&bob, &cid], now), Some(1));
+        // Then the first within range, the longest waiting widening it.
+        assert_eq!(choose_opponent(&newcomer, [&dan, &bob], now), Some(0));
+        assert_eq!(choose_opponent(&bob, [&ann], now), Some(0));
+        // Nobody is paired with itself or out of range.
+        assert_eq!(choose_opponent(&newcomer, [&ann], now), None);
+        assert_eq!(choose_opponent(&newcomer, [&ann, &bob], now), Some(1));
+        assert_eq!(choose_opponent(&bob, [&cid], now), None);
+        // Nor with a player wanting other rules, even for a fixture.
+        let mut short_cid = cid.clone();
+        short_cid.criteria.format.rules.target_score = 5;
+        assert_eq!(choose_opponent(&newcomer, [&short_cid, &bob], now), Some(1));
+        // Or a series.
+        let mut series_bob = bob.clone();
+        series_bob.criteria.format.series = Some(SeriesLength::BestOf3);
+        assert_eq!(choose_opponent(&newcomer, [&series_bob], now), None);
+        assert_eq!(choose_opponent(&series_bob, [&ann], now), None);
+    }
+
+    #[test]
+    fn waiting_players_pairing() {
+        let start = Instant::now();
+        let waiting = [
+            seeker("ann", 1000, start, &[]),
+            seeker("bob", 2000, start, &[]),
+            seeker("cid", 1350, start, &[]),
+            seeker("dan", 2100, start, &[]),
+        ];
+        let waiting: Vec<&Seeker> = waiting.iter().collect();
+        assert_eq!(pair_up(&waiting, start), vec![(1, 3)]);
+        assert_eq!(
+            pair_up(&waiting, start + Duration::from_secs(15)),
+            vec![(0, 2), (1, 3)]
+        );
+        // Two connections of a same player are never paired together.
+        let twins = [
+            seeker("eve", 1500, start, &[]),
+            seeker("eve", 1500, start, &[]),
+        ];
+        let twins: Vec<&Seeker> = twins.iter().collect();
+        assert_eq!(pair_up(&twins, start + Duration::from_secs(60)), vec![]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Gathering of the entrants of a tournament in a same task.
+//!
+//! Players register for a tournament of a given number of entrants. Each one waits in its own task, its connection kept
+//! alive, until the tournament is full. The task of the last entrant then summons the others, which send themselves to
+//! it, and runs the tournament. This is implemented in [`register_for_tournament`].
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::mem;
+use std::sync::Mutex;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::game::Player;
+use crate::match_making::opponents_joining::{wait_for_giver_data, WaitOutcome};
+use crate::protocol::CloseReason;
+
+/// A server-wide structure, shared in the [`ServerContext`], holding the tournaments waiting for entrants, by number of
+/// entrants.
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct Tournaments<S> {
+    forming: Mutex<HashMap<u8, Vec<Registration<S>>>>,
+}
+
+/// A player waiting for its tournament to be full.
+struct Registration<S> {
+    id: String,
+    summons: oneshot::Sender<Summons<S>>,
+}
+
+/// Sent to the waiting entrants once their tournament is full, for them to send themselves to the task running it.
+struct Summons<S> {
+    entrants: mpsc::UnboundedSender<Player<S>>,
+}
+
+/// An entrant of a full tournament. Its player is [`None`] if it left before the tournament started.
+pub struct Registrant<S> {
+    pub id: String,
+    pub player: Option<Player<S>>,
+}
+
+/// What registering for a tournament led to.
+enum RegistrationOutcome<S> {
+    /// The tournament waits for more entrants. The summons will come through the receiver.
+    Waiting(oneshot::Receiver<Summons<S>>),
+    /// The player completes the tournament, and runs it with the given entrants.
+    Full(Vec<Registration<S>>),
+    /// The player is already waiting for a tournament.
+    AlreadyRegistered,
+}
+
+impl<S> Tournaments<S> {
+    /// Creates a new [`Tournaments`] instance.
+    pub fn new() -> Tournaments<S> {
+        Tournaments {
+            forming: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register the player with the given id for the tournament of `entrants` entrants.
+    fn register(&self, id: &str, entrants: u8) -> RegistrationOutcome<S> {
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        let mut forming = self.forming.lock().unwrap();
+        // Entrants that stopped waiting leave their place.
+        for registrations in forming.values_mut() {
+            registrations.retain(|registration| !registration.summons.is_closed());
+        }
+        if forming
+            .values()
+            .flatten()
+            .any(|registration| registration.id == id)
+        {
+            return RegistrationOutcome::AlreadyRegistered;
+        }
+        let registrations = forming.entry(entrants).or_default();
+        if registrations.len() + 1 >= usize::from(entrants) {
+            return RegistrationOutcome::Full(mem::take(registrations));
+        }
+        let (summons, receiver) = oneshot::channel();
+        registrations.push(Registration {
+            id: String::from(id),
+            summons,
+        });
+        RegistrationOutcome::Waiting(receiver)
+    }
+}
+
+/// Register the player for a tournament of `entrants` entrants, and wait for it to be full.
+///
+/// The last entrant to register gets all the [`Registrant`]s of the tournament returned, itself included, for its task
+/// to run the tournament. The others are sent to that task, and get nothing returned. Entrants that left in the
+/// meantime are returned without their player.
+///
+/// While waiting, the connection is kept alive as in the [`QueueService`]. The player leaves the tournament by closing
+/// its connection. If it disconnects, if the server starts shutting down, or if it is already waiting for a tournament,
+/// the connection is closed if it can be, and nothing is returned.
+///
+/// [`QueueService`]: super::QueueService
+pub async fn register_for_tournament<S, D>(
+    mut player: Player<S>,
+    entrants: u8,
+    tournaments: &Tournaments<S>,
+    log_id: &D,
+) -> Option<Vec<Registrant<S>>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    let mut receiver = match tournaments.register(player.id(), entrants) {
+        RegistrationOutcome::Waiting(receiver) => receiver,
+        RegistrationOutcome::Full(registrations) => {
+            log::trace!("{log_id}: The tournament is full. Summoning its entrants.");
+            return Some(summon_entrants(player, registrations).await);
+        }
+        RegistrationOutcome::AlreadyRegistered => {
+            log::info!("{log_id}: Already registered for a tournament.");
+            player.close(CloseReason::AlreadyRegistered).await;
+            return None;
+        }
+    };
+    log::trace!("{log_id}: Registered for a tournament of {entrants} entrants.");
+    let version = player.version();
+    let (websocket, shutdown) = player.connection_mut();
+    match wait_for_giver_data(&mut receiver, websocket, shutdown, version, None, None).await {
+        Ok(WaitOutcome::Joined(Summons { entrants })) => {
+            // The running task waits for every summoned entrant, the send can't fail.
+            if let Err(mpsc::error::SendError(mut player)) = entrants.send(player) {
+                player.close(CloseReason::InternalError).await;
+            }
+            log::info!("{log_id}: Connection has been given away to the tournament's task.");
+        }
+        Ok(WaitOutcome::ChallengeAccepted(_) | WaitOutcome::LeftQueue) => {
+            unreachable!("The entrant is neither in the lobby nor in the queue.")
+        }
+        Err(e) => {
+            log::info!("{log_id}: Left the tournament : {e}.");
+            if let Some(reason) = e.close_reason() {
+                player.close(reason).await;
+            }
+        }
+    }
+    None
+}
+
+/// Summon the waiting entrants, and gather them with the player completing the tournament.
+async fn summon_entrants<S>(
+    player: Player<S>,
+    registrations: Vec<Registration<S>>,
+) -> Vec<Registrant<S>> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut registrants: Vec<Registrant<S>> = registrations
+        .into_iter()
+        .map(|Registration { id, summons }| {
+            // An entrant that stopped waiting in the meantime is left without its player.
+            let _: Result<_, _> = summons.send(Summons {
+                entrants: sender.clone(),
+            });
+            Registrant { id, player: None }
+        })
+        .collect();
+    // The channel closes once every summoned entrant has sent itself or dropped its summons.
+    drop(sender);
+    while let Some(entrant) = receiver.recv().await {
+        if let Some(registrant) = registrants
+            .iter_mut()
+            .find(|registrant| registrant.id == entrant.id())
+        {
+            registrant.player = Some(entrant);
+        }
+    }
+    registrants.push(Registrant {
+        id: String::from(player.id()),
+        player: Some(player),
+    });
+    registrants
+}
@@ -4,160 +4,895 @@
 //! function that runs the protocol on a given [`WebSocketStream`] connection : [`execute_protocol_on_connection`].
 //!
 //! The structures are :
-//! * Serializable : [`GameCompletedMessage`], [`PointScoredMessage`] and [`PositionUpdateMessage`] wrapped in the enum
-//!   [`ServerToClientMessage`], and [`GameMode0StartMessage`].
-//! * Deserializable : [`HelloMessage`].
+//! * Serializable : [`GameCompletedMessage`], [`PointScoredMessage`], [`PositionUpdateMessage`],
+//!   [`GameMode0StartMessage`], [`GameStartStatusMessage`], [`ErrorMessage`] and the other server-to-client messages,
+//!   all wrapped in the enum [`ServerToClientMessage`]. They are serialized by the [`ProtocolVersion`] spoken with the
+//!   client.
+//! * Deserializable : [`HelloMessage`], and the game mode parameters it carries, such as
+//!   [`MatchMadeRemote1v1Parameters`].
 //!
 //! The messages received from the client are processed through the helper functions [`parse_gm0_input_message`] and
-//! [`receive_hello_message`].
+//! [`receive_hello_message`]. The latter reads the version requested by the client, and lets it decode the rest of
+//! the message.
+//!
+//! Every connection is ended by the server with [`close_connection`], giving the client a [`CloseReason`].
 
 use std::fmt::Display;
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures_util::SinkExt;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::{self, Message};
 use tokio_tungstenite::WebSocketStream;
 
+pub use messages::challenge::{parse_challenge_answer, ChallengeMessage, ChallengeStatus};
+pub use messages::error::{CloseReason, ErrorMessage};
 pub use messages::game_running::{
-    parse_gm0_input_message, parse_gm1_input_message, GameAbortedMessage, GameCompletedMessage,
-    PointScoredMessage, PositionUpdateMessage, ServerToClientMessage,
+    parse_gm0_input_message, parse_gm1_input_message, ClientInput, GameAbortedMessage,
+    GameCompletedMessage, OpponentConnectionMessage, OpponentConnectionStatus, PointScoredMessage,
+    PositionUpdateMessage, ServeMessage,
+};
+pub use messages::game_start::{
+    GameMode0StartMessage, GameMode1StartMessage, GameResumeMessage, GameStartStatus,
+    GameStartStatusMessage,
 };
-pub use messages::game_start::{GameMode0StartMessage, GameMode1StartMessage};
 use messages::hello::GameModes;
 use messages::hello::{receive_hello_message, HelloMessage};
+pub use messages::invite::InviteCodeMessage;
+use messages::parameters::{
+    AiOpponentParameters, FriendChallengeParameters, LobbyParameters, Local1v1Parameters,
+    LocalTournamentParameters, MatchMadeRemote1v1Parameters, ParametersError,
+    PrivateRemote1v1Parameters, ResumeParameters, SeriesParameters, SpectateParameters,
+    TournamentParameters,
+};
+pub use messages::post_game::{
+    parse_post_game_message, PostGameRequest, PostGameStatus, PostGameStatusMessage,
+};
+pub use messages::queue::{
+    is_leave_queue_request, parse_ready_answer, BackInQueueMessage, MatchFoundMessage,
+    QueueStatusMessage,
+};
+pub use messages::rating::RatingUpdateMessage;
+pub use messages::series::SeriesScoreMessage;
+pub use messages::spectating::{LiveGamesMessage, SpectatorSnapshotMessage};
+pub use messages::tournament::{
+    LocalTournamentMatchMessage, LocalTournamentWinnerMessage, TournamentMatchMessage,
+};
+pub use messages::ServerToClientMessage;
+pub use version::ProtocolVersion;
 
-use crate::game::{play_game_mode_0, play_game_mode_1, PlayingError};
-use crate::match_making;
+use crate::authentication::{redeem_ticket, AuthenticationError};
+use crate::game::{
+    play_game_mode_0, play_game_mode_1, play_game_mode_4, play_series, spectate_game, Difficulty,
+    GameRules, LiveGames, Player, PlayingError, Reconnections, SeriesLength,
+};
+use crate::match_making::{ChallengeError, MatchFormat, QueuedPlayer, SearchCriteria};
+use crate::sessions::{SessionHandle, Sessions};
+use crate::shutdown::Shutdown;
+use crate::tournament::{run_local_tournament, run_tournament};
+use crate::{league, match_making, rating};
 
 pub mod constants;
 mod messages;
 mod side;
+mod version;
 
-/// The current maximum version of the protocol supported.
-const SUPPORTED_PROTO_VERSION: u8 = 3;
+/// Time allowed to redeem the ticket a client is authenticated with.
+const AUTHENTICATION_TIMEOUT: Duration = Duration::from_secs(5);
 
-/// Receives a [`HelloMessage`], and runs the combination of match-making and game type requested.
+/// Server-wide resources shared by the tasks handling the connections.
+pub struct ServerContext<S> {
+    pub match_maker: match_making::MatchMaker<S>,
+    pub invitations: match_making::Invitations<S>,
+    pub challenges: match_making::Challenges<S>,
+    pub tournaments: match_making::Tournaments<S>,
+    pub reconnections: Reconnections<S>,
+    pub live_games: LiveGames,
+    pub sessions: Sessions,
+    /// The rules the matches of the tournaments, remote or local, are played with.
+    pub tournament_rules: GameRules,
+    pub db_client: Arc<tokio_postgres::Client>,
+    /// Username of the account the games against the AI are recorded against.
+    pub ai_id: String,
+}
+
+/// Receives a [`HelloMessage`], authenticates the client as the player signed in with the website session of the
+/// upgrade request if any, or with the ticket the Hello carries, opens the session of the user on the connection, and
+/// runs the combination of match-making and game type requested.
 pub async fn execute_protocol_on_connection<S, D>(
     mut websocket: WebSocketStream<S>,
     log_id: D,
-    match_maker: Arc<match_making::MatchMaker<S>>,
-    db_client: Arc<tokio_postgres::Client>,
+    session_user: Option<String>,
+    session: SessionHandle,
+    context: Arc<ServerContext<S>>,
+    mut shutdown: Shutdown,
 ) where
     S: AsyncRead + AsyncWrite + Unpin,
     D: Display,
 {
     log::info!("{log_id}: Beginning to unroll the protocol with a client.");
-    match receive_hello_message(&mut websocket).await {
+    match receive_hello_message(&mut websocket, &mut shutdown).await {
         Ok(hello_message) => {
-            if is_id_valid(&db_client, &hello_message.id).await {
-                dispatch_requested_game_mode(
-                    websocket,
-                    &log_id,
-                    match_maker,
-                    db_client,
-                    hello_message,
-                )
-                .await;
-            } else {
-                log::info!(
-                    "{log_id}: The client sent an id that doesn't exist in the users database."
-                )
+            match authenticate(&context.db_client, session_user, &hello_message).await {
+                Ok(id) => {
+                    log::info!("{log_id}: Authenticated as {id}.");
+                    if let Err(e) = context.sessions.open(&id, &session) {
+                        log::info!("{log_id}: Rejecting the connection : {e}.");
+                        let reason = CloseReason::AlreadyConnected;
+                        close_connection(&mut websocket, hello_message.version, reason).await;
+                        log::info!("{log_id}: Protocol done.");
+                        return;
+                    }
+                    dispatch_requested_game_mode(
+                        websocket,
+                        &log_id,
+                        &context,
+                        shutdown,
+                        id,
+                        session,
+                        hello_message,
+                    )
+                    .await;
+                }
+                Err(e) => {
+                    let reason = match e {
+                        AuthenticationError::InvalidTicket
+                        | AuthenticationError::InvalidSession
+                        | AuthenticationError::UnverifiedUsername => {
+                            log::info!("{log_id}: Authentication failed : {e}.");
+                            CloseReason::AuthenticationFailed
+                        }
+                        AuthenticationError::DatabaseError(_) | AuthenticationError::Timeout(_) => {
+                            log::error!("{log_id}: Authentication failed : {e}.");
+                            CloseReason::InternalError
+                        }
+                    };
+                    close_connection(&mut websocket, hello_message.version, reason).await;
+                }
+            }
+        }
+        Err(e) => {
+            log::info!("{log_id}: Error while receiving a hello message : {e}.");
+            if let Some(reason) = e.close_reason() {
+                close_connection(&mut websocket, ProtocolVersion::latest(), reason).await;
             }
         }
-        Err(e) => log::info!("{log_id}: Error while receiving a hello message : {e}."),
     }
     log::info!("{log_id}: Protocol done.");
 }
 
-/// Check in the database if the id given by the remote client exists.
-async fn is_id_valid(db_client: &Arc<tokio_postgres::Client>, id: &str) -> bool {
-    match db_client
-        .query_one("select * from account_player where username = $1", &[&id])
+/// Return the user the connection is authenticated as : the player signed in with the website session of the upgrade
+/// request if any, or else the owner of the ticket of the [`HelloMessage`], redeemed within [`AUTHENTICATION_TIMEOUT`].
+/// Version 3 clients name their user instead : only the website session can vouch for it.
+async fn authenticate(
+    db_client: &Arc<tokio_postgres::Client>,
+    session_user: Option<String>,
+    hello_message: &HelloMessage,
+) -> Result<String, AuthenticationError> {
+    if hello_message.version == ProtocolVersion::V3 {
+        return session_user
+            .filter(|username| *username == hello_message.token)
+            .ok_or(AuthenticationError::UnverifiedUsername);
+    }
+    if let Some(username) = session_user {
+        return Ok(username);
+    }
+    let lookup = redeem_ticket(db_client, &hello_message.token);
+    tokio::time::timeout(AUTHENTICATION_TIMEOUT, lookup)
         .await
-    {
-        Ok(_) => true,
-        Err(_) => false,
+        .map_err(|_| AuthenticationError::Timeout(AUTHENTICATION_TIMEOUT))?
+}
+
+/// End the connection : send the client an [`ErrorMessage`] if the reason is an error, then close the websocket with
+/// the matching close frame. The reason is first given as it is in the version spoken by the client.
+///
+/// Failures are ignored, as the connection is being dropped anyway.
+pub async fn close_connection<S>(
+    websocket: &mut WebSocketStream<S>,
+    version: ProtocolVersion,
+    reason: CloseReason,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let reason = version.close_reason(reason);
+    if let Some(message) = ErrorMessage::new(reason) {
+        let _: Result<_, _> = send_message(websocket, version, message).await;
     }
+    let _: Result<_, _> = websocket.close(Some(reason.close_frame())).await;
 }
 
-/// Try to launch the requested game mode, if the requested protocol version is supported and if the game exists for
-/// this version.
+/// Serialize the message with the given version, and send it. A message that doesn't exist in this version is not sent.
+pub async fn send_message<S, M>(
+    websocket: &mut WebSocketStream<S>,
+    version: ProtocolVersion,
+    message: M,
+) -> Result<(), tungstenite::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    M: Into<ServerToClientMessage>,
+{
+    match version.encode(message) {
+        Some(bytes) => websocket.send(Message::Binary(bytes)).await,
+        None => Ok(()),
+    }
+}
+
+/// Launch the requested game mode for the authenticated player, if its parameters are valid. The version and the game
+/// mode have already been checked while decoding the [`HelloMessage`].
 async fn dispatch_requested_game_mode<S, D>(
     websocket: WebSocketStream<S>,
     log_id: &D,
-    match_maker: Arc<match_making::MatchMaker<S>>,
-    db_client: Arc<tokio_postgres::Client>,
+    context: &ServerContext<S>,
+    shutdown: Shutdown,
+    id: String,
+    session: SessionHandle,
     hello_message: HelloMessage,
 ) where
     S: AsyncRead + AsyncWrite + Unpin,
     D: Display,
 {
-    match hello_message {
-        HelloMessage { proto_version, .. } if proto_version != SUPPORTED_PROTO_VERSION => {
-            log::info!("{log_id}: Received a request for protocol version {proto_version}, but is not supported.");
+    let HelloMessage {
+        version,
+        game_mode,
+        parameters,
+        ..
+    } = hello_message;
+    let player = Player::new(websocket, id, version, session, shutdown);
+    match game_mode {
+        GameModes::MatchMadeRemote1v1 => {
+            match MatchMadeRemote1v1Parameters::try_from(parameters.as_slice()) {
+                Ok(MatchMadeRemote1v1Parameters { rules }) => {
+                    launch_game_mode_0(player, rules, context, log_id).await
+                }
+                Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+            }
         }
-        HelloMessage { id, game_mode, .. } if game_mode == GameModes::MatchMadeRemote1v1.into() => {
-            launch_game_mode_0(websocket, id, &match_maker, &db_client, log_id).await;
+        GameModes::Local1v1 => match Local1v1Parameters::try_from(parameters.as_slice()) {
+            Ok(Local1v1Parameters { rules }) => launch_game_mode_1(player, rules, log_id).await,
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
+        GameModes::PrivateRemote1v1 => {
+            match PrivateRemote1v1Parameters::try_from(parameters.as_slice()) {
+                Ok(PrivateRemote1v1Parameters::Host(rules)) => {
+                    launch_game_mode_2_host(player, rules, context, log_id).await
+                }
+                Ok(PrivateRemote1v1Parameters::Join(code)) => {
+                    launch_game_mode_2_guest(player, &code, &context.invitations, log_id).await
+                }
+                Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+            }
         }
-        HelloMessage { game_mode, .. } if game_mode == GameModes::Local1v1.into() => {
-            launch_game_mode_1(websocket, log_id).await;
+        GameModes::FriendChallenge => {
+            match FriendChallengeParameters::try_from(parameters.as_slice()) {
+                Ok(FriendChallengeParameters { opponent, rules }) => {
+                    launch_game_mode_5(player, &opponent, rules, context, log_id).await
+                }
+                Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+            }
         }
-        HelloMessage { game_mode: gm, .. } => {
-            log::info!(
-                "{log_id}: Received a request for game mode {gm}, but requested protocol version \
-                {SUPPORTED_PROTO_VERSION} does not support it."
-            );
+        GameModes::Lobby => match LobbyParameters::try_from(parameters.as_slice()) {
+            Ok(LobbyParameters) => launch_game_mode_6(player, &context.challenges, log_id).await,
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
+        GameModes::Tournament => match TournamentParameters::try_from(parameters.as_slice()) {
+            Ok(TournamentParameters { entrants }) => {
+                launch_game_mode_7(player, entrants, context, log_id).await
+            }
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
+        GameModes::LocalTournament => {
+            match LocalTournamentParameters::try_from(parameters.as_slice()) {
+                Ok(LocalTournamentParameters { aliases }) => {
+                    launch_game_mode_8(player, aliases, context.tournament_rules, log_id).await
+                }
+                Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+            }
         }
+        GameModes::Spectate => match SpectateParameters::try_from(parameters.as_slice()) {
+            Ok(parameters) => {
+                launch_game_mode_9(player, parameters, &context.live_games, log_id).await
+            }
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
+        GameModes::Resume => match ResumeParameters::try_from(parameters.as_slice()) {
+            Ok(ResumeParameters) => launch_resume(player, &context.reconnections, log_id).await,
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
+        GameModes::AiOpponent => match AiOpponentParameters::try_from(parameters.as_slice()) {
+            Ok(AiOpponentParameters { difficulty, rules }) => {
+                launch_game_mode_4(player, difficulty, rules, context, log_id).await
+            }
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
+        GameModes::Series => match SeriesParameters::try_from(parameters.as_slice()) {
+            Ok(SeriesParameters { length, rules }) => {
+                launch_game_mode_10(player, length, rules, context, log_id).await
+            }
+            Err(e) => reject_invalid_parameters(player, log_id, game_mode, e).await,
+        },
     }
 }
 
-/// Answer to a game mode 0 request : join this task's connection with another task's connections chosen by the
-/// [`match_making::MatchMaker`], then make them play together. Failed startups lead to a return of a player to the
-/// match making queue.
+/// Reject a game mode request whose parameters are invalid. This is a protocol violation.
+async fn reject_invalid_parameters<S, D>(
+    mut player: Player<S>,
+    log_id: &D,
+    game_mode: GameModes,
+    error: ParametersError,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::info!("{log_id}: Protocol violation, the parameters for game mode {game_mode:?} were rejected : {error}.");
+    player.close(CloseReason::InvalidParameters).await;
+}
+
+/// Answer to a game mode 0 request : hand the player over to the match making queue, whose
+/// [`match_making::QueueService`] pairs it with an opponent wanting the same rules and makes them play together in
+/// [`play_match_made_games`].
 async fn launch_game_mode_0<S, D>(
-    mut websocket: WebSocketStream<S>,
-    mut id: String,
-    match_maker: &Arc<match_making::MatchMaker<S>>,
-    db_client: &Arc<tokio_postgres::Client>,
+    player: Player<S>,
+    rules: GameRules,
+    context: &ServerContext<S>,
     log_id: &D,
 ) where
     S: AsyncRead + AsyncWrite + Unpin,
     D: Display,
 {
-    log::trace!("{log_id}: [Version {SUPPORTED_PROTO_VERSION}]-[Game mode 0] request received.");
-    'new_match_making_attempt: loop {
-        match match_making::join_opponents(websocket, id, &match_maker, log_id).await {
-            Some((pl, pr)) => {
-                log::trace!("{log_id}: Two connections have been joined. Playing a game.");
-                match play_game_mode_0(pl, pr, db_client).await {
-                    Ok(_) => log::trace!("{log_id}: The game has been played to completion."),
-                    Err(PlayingError::ClientError(e, (new_websocket, new_id))) => {
-                        log::info!("{log_id}: Game startup failed : {e}.");
-                        (websocket, id) = (new_websocket, new_id);
-                        continue 'new_match_making_attempt;
-                    }
-                    Err(PlayingError::DatabaseError(e)) => {
-                        log::error!("{log_id}: Database error during game : {e}.");
-                    }
-                }
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 0] request received.",
+        u8::from(player.version())
+    );
+    let format = MatchFormat {
+        rules,
+        series: None,
+    };
+    queue_player(player, log_id.to_string(), format, context).await;
+}
+
+/// Answer to a game mode 10 request : hand the player over to the match making queue, whose
+/// [`match_making::QueueService`] pairs it with an opponent wanting a series of the same length and rules, and makes
+/// them play it in [`play_match_made_games`].
+async fn launch_game_mode_10<S, D>(
+    player: Player<S>,
+    length: SeriesLength,
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 10] request received.",
+        u8::from(player.version())
+    );
+    let format = MatchFormat {
+        rules,
+        series: Some(length),
+    };
+    queue_player(player, log_id.to_string(), format, context).await;
+}
+
+/// Put the players back into the match making queue, searching for single games with the rules they just played with.
+async fn requeue_players<S, D>(
+    players: [Option<Player<S>>; 2],
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    let format = MatchFormat {
+        rules,
+        series: None,
+    };
+    for player in players.into_iter().flatten() {
+        log::trace!("{log_id}: A player goes back into the match making queue.");
+        queue_player(player, log_id.to_string(), format, context).await;
+    }
+}
+
+/// Hand the player over to the match making queue, to search for an opponent wanting the same format with its rating
+/// and its league fixtures. On a database error, or if the server is shutting down, the connection is closed.
+async fn queue_player<S>(
+    mut player: Player<S>,
+    log_id: String,
+    format: MatchFormat,
+    context: &ServerContext<S>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let criteria = match fetch_search_criteria(&context.db_client, player.id(), format).await {
+        Ok(criteria) => criteria,
+        Err(e) => {
+            log::error!("{log_id}: Database error while setting up the opponent search : {e}.");
+            player.close(CloseReason::InternalError).await;
+            return;
+        }
+    };
+    let queued = QueuedPlayer { player, log_id };
+    if let Err(QueuedPlayer { mut player, log_id }) = context.match_maker.enqueue(queued, criteria)
+    {
+        log::info!("{log_id}: Server shutdown before entering the match making queue.");
+        player.close(CloseReason::ServerShutdown).await;
+    }
+}
+
+/// Make two players paired by the match making queue play together in the format they queued for, on a task of their
+/// own. Single games go on until the players part ways, the first one counting for their pending league fixture, if
+/// any. A series is played to its end. Failed startups, as well as players asking for it once their single game is
+/// over, lead to a return of a player to the queue, in the same format.
+pub async fn play_match_made_games<S>(
+    left: QueuedPlayer<S>,
+    right: QueuedPlayer<S>,
+    format: MatchFormat,
+    context: Arc<ServerContext<S>>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let QueuedPlayer {
+        player: left_player,
+        log_id,
+    } = left;
+    let QueuedPlayer {
+        player: right_player,
+        log_id: right_log_id,
+    } = right;
+    let left_id = String::from(left_player.id());
+    let outcome = match format.series {
+        Some(length) => {
+            log::trace!(
+                "{log_id}: Joined with {right_log_id}. Playing a best-of-{} series.",
+                length.games()
+            );
+            play_remote_series(
+                left_player,
+                right_player,
+                length,
+                format.rules,
+                &context,
+                &log_id,
+            )
+            .await
+        }
+        None => {
+            let fixture =
+                league::pending_fixture(&context.db_client, left_player.id(), right_player.id())
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::error!(
+                            "{log_id}: Database error while looking for a league fixture : {e}."
+                        );
+                        None
+                    });
+            match fixture {
+                Some(fixture_id) => log::trace!(
+                    "{log_id}: Joined with {right_log_id} for league fixture {fixture_id}. Playing a game."
+                ),
+                None => log::trace!("{log_id}: Joined with {right_log_id}. Playing a game."),
             }
-            None => {
-                log::info!("{log_id}: Connection has been given away to another task.");
+            let rules = format.rules;
+            play_remote_games(left_player, right_player, fixture, rules, &context, &log_id).await
+        }
+    };
+    let requeued_players = match outcome {
+        Ok(requeued_players) => requeued_players,
+        Err(remaining_player) if remaining_player.id() == left_id => [Some(remaining_player), None],
+        Err(remaining_player) => [None, Some(remaining_player)],
+    };
+    for (player, log_id) in requeued_players.into_iter().zip([log_id, right_log_id]) {
+        if let Some(player) = player {
+            log::trace!("{log_id}: Going back into the match making queue.");
+            queue_player(player, log_id, format, &context).await;
+        }
+    }
+}
+
+/// Get the rating of the player and its league fixture opponents, to search for an opponent wanting the same format
+/// with.
+async fn fetch_search_criteria(
+    db_client: &Arc<tokio_postgres::Client>,
+    username: &str,
+    format: MatchFormat,
+) -> Result<SearchCriteria, tokio_postgres::Error> {
+    Ok(SearchCriteria {
+        format,
+        rating: rating::fetch_rating(db_client, username).await?,
+        fixture_opponents: league::fixture_opponents(db_client, username).await?,
+    })
+}
+
+/// Make the two players play together with the given rules until they part ways, the first game counting for the league
+/// `fixture` if any. Returns the players asking to go back into the match making queue, or the remaining player if the
+/// game startup failed.
+async fn play_remote_games<S, D>(
+    left_player: Player<S>,
+    right_player: Player<S>,
+    fixture: Option<i64>,
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) -> Result<[Option<Player<S>>; 2], Player<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    match play_game_mode_0(
+        left_player,
+        right_player,
+        fixture,
+        rules,
+        &context.db_client,
+        &context.reconnections,
+        &context.live_games,
+    )
+    .await
+    {
+        Ok(requeued_players) => {
+            log::trace!("{log_id}: The games have been played to completion.");
+            Ok(requeued_players)
+        }
+        Err(PlayingError::ClientError(e, remaining_player)) => {
+            log::info!("{log_id}: Game startup failed : {e}.");
+            Err(remaining_player)
+        }
+        Err(PlayingError::ConnectionLost(e)) => {
+            log::info!("{log_id}: Game startup failed : {e}.");
+            Ok([None, None])
+        }
+        Err(PlayingError::DatabaseError(e)) => {
+            log::error!("{log_id}: Database error during game : {e}.");
+            Ok([None, None])
+        }
+        Err(PlayingError::ServerShutdown) => {
+            log::info!("{log_id}: The game has been interrupted by the server shutdown.");
+            Ok([None, None])
+        }
+    }
+}
+
+/// Make the two players play a series of the given length and rules. Once it is over, the connections of the players
+/// still there are closed. Returns the remaining player if the startup of the first game failed, the series not having
+/// begun.
+async fn play_remote_series<S, D>(
+    left_player: Player<S>,
+    right_player: Player<S>,
+    length: SeriesLength,
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) -> Result<[Option<Player<S>>; 2], Player<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    match play_series(
+        left_player,
+        right_player,
+        length,
+        rules,
+        &context.db_client,
+        &context.reconnections,
+        &context.live_games,
+    )
+    .await
+    {
+        Ok(players) => {
+            log::trace!("{log_id}: The series has been played to completion.");
+            for mut player in players.into_iter().flatten() {
+                player.close(CloseReason::Done).await;
             }
+            Ok([None, None])
+        }
+        Err(PlayingError::ClientError(e, remaining_player)) => {
+            log::info!("{log_id}: Series startup failed : {e}.");
+            Err(remaining_player)
+        }
+        Err(PlayingError::ConnectionLost(e)) => {
+            log::info!("{log_id}: Series startup failed : {e}.");
+            Ok([None, None])
+        }
+        Err(PlayingError::DatabaseError(e)) => {
+            log::error!("{log_id}: Database error during series : {e}.");
+            Ok([None, None])
+        }
+        Err(PlayingError::ServerShutdown) => {
+            log::info!("{log_id}: The series has been interrupted by the server shutdown.");
+            Ok([None, None])
         }
-        break;
     }
 }
 
-/// Answer to a game mode 1 request.
-async fn launch_game_mode_1<S, D>(websocket: WebSocketStream<S>, log_id: &D)
+/// Answer to a game mode 2 request hosting a private game : wait for a guest to join with the code sent to the host,
+/// then make them play together with the host's rules. If the game startup fails, the remaining player is put in the
+/// match making queue.
+async fn launch_game_mode_2_host<S, D>(
+    player: Player<S>,
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 2] request received, hosting a private game.",
+        u8::from(player.version())
+    );
+    let Some((host, guest)) =
+        match_making::host_private_game(player, &context.invitations, log_id).await
+    else {
+        return;
+    };
+    log::trace!("{log_id}: The guest joined. Playing a game.");
+    let requeued_players = match play_remote_games(host, guest, None, rules, context, log_id).await
+    {
+        Ok(requeued_players) => requeued_players,
+        Err(remaining_player) => [Some(remaining_player), None],
+    };
+    requeue_players(requeued_players, rules, context, log_id).await;
+}
+
+/// Answer to a game mode 2 request joining a private game : hand the player over to the host of the game.
+async fn launch_game_mode_2_guest<S, D>(
+    player: Player<S>,
+    code: &str,
+    invitations: &match_making::Invitations<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 2] request received, joining the private game {code}.",
+        u8::from(player.version())
+    );
+    match match_making::join_private_game(player, code, invitations) {
+        Ok(()) => log::info!("{log_id}: Connection has been given away to the host's task."),
+        Err(mut player) => {
+            log::info!("{log_id}: No private game is hosted with the code {code}.");
+            player.close(CloseReason::UnknownInviteCode).await;
+        }
+    }
+}
+
+/// Answer to a game mode 1 request, with the requested rules.
+async fn launch_game_mode_1<S, D>(player: Player<S>, rules: GameRules, log_id: &D)
 where
     S: AsyncRead + AsyncWrite + Unpin,
     D: Display,
 {
-    log::trace!("{log_id}: [Version {SUPPORTED_PROTO_VERSION}]-[Game mode 1] request received.");
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 1] request received.",
+        u8::from(player.version())
+    );
     log::trace!("{log_id}: Playing the requested game.");
-    match play_game_mode_1(websocket).await {
-        Ok(_) => log::trace!("{log_id} The game has been played to completion."),
+    match play_game_mode_1(player, rules).await {
+        Ok(mut player) => {
+            log::trace!("{log_id} The game has been played to completion.");
+            player.close(CloseReason::Done).await;
+        }
         Err(e) => log::info!("{log_id} Error encountered while playing the game : {e}."),
     }
 }
+
+/// Answer to a game mode 4 request : play against the AI, at the requested difficulty and with the requested rules.
+async fn launch_game_mode_4<S, D>(
+    player: Player<S>,
+    difficulty: Difficulty,
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 4] request received, difficulty {difficulty:?}.",
+        u8::from(player.version())
+    );
+    log::trace!("{log_id}: Playing the requested game.");
+    match play_game_mode_4(
+        player,
+        difficulty,
+        rules,
+        &context.ai_id,
+        &context.db_client,
+    )
+    .await
+    {
+        Ok(mut player) => {
+            log::trace!("{log_id}: The game has been played to completion.");
+            player.close(CloseReason::Done).await;
+        }
+        Err(PlayingError::DatabaseError(e)) => {
+            log::error!("{log_id}: Database error during game : {e}.");
+        }
+        Err(e) => log::info!("{log_id}: Error encountered while playing the game : {e}."),
+    }
+}
+
+/// Answer to a game mode 5 request : challenge a friend waiting for an opponent, then make them play together with the
+/// challenger's rules if it accepts. If the game startup fails, the remaining player is put in the match making queue.
+async fn launch_game_mode_5<S, D>(
+    player: Player<S>,
+    opponent: &str,
+    rules: GameRules,
+    context: &ServerContext<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 5] request received, challenging {opponent}.",
+        u8::from(player.version())
+    );
+    let challenge_result = match_making::challenge_friend(
+        player,
+        opponent,
+        &context.challenges,
+        &context.db_client,
+        log_id,
+    )
+    .await;
+    let (challenger, friend) = match challenge_result {
+        Ok(players) => players,
+        Err((e, mut player)) => {
+            match e {
+                ChallengeError::DatabaseError(_) => {
+                    log::error!("{log_id}: The challenge failed : {e}.")
+                }
+                _ => log::info!("{log_id}: The challenge failed : {e}."),
+            }
+            if let Some(reason) = e.close_reason() {
+                player.close(reason).await;
+            }
+            return;
+        }
+    };
+    log::trace!("{log_id}: The challenge has been accepted. Playing a game.");
+    let requeued_players =
+        match play_remote_games(challenger, friend, None, rules, context, log_id).await {
+            Ok(requeued_players) => requeued_players,
+            Err(remaining_player) => [Some(remaining_player), None],
+        };
+    requeue_players(requeued_players, rules, context, log_id).await;
+}
+
+/// Answer to a game mode 6 request : wait in the lobby until a friend's challenge is accepted, then hand the player
+/// over to the challenger.
+async fn launch_game_mode_6<S, D>(
+    player: Player<S>,
+    challenges: &match_making::Challenges<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 6] request received.",
+        u8::from(player.version())
+    );
+    match match_making::wait_in_lobby(player, challenges, log_id).await {
+        Ok(()) => log::info!("{log_id}: Connection has been given away to the challenger's task."),
+        Err(e) => log::info!("{log_id}: Left the lobby : {e}."),
+    }
+}
+
+/// Answer to a game mode 7 request : register for a tournament of the requested number of entrants. The task of the
+/// entrant completing the tournament runs it, the others are handed over to it.
+async fn launch_game_mode_7<S, D>(
+    player: Player<S>,
+    entrants: u8,
+    context: &ServerContext<S>,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 7] request received, {entrants} entrants.",
+        u8::from(player.version())
+    );
+    let Some(registrants) =
+        match_making::register_for_tournament(player, entrants, &context.tournaments, log_id).await
+    else {
+        return;
+    };
+    log::trace!("{log_id}: The entrants have been gathered. Running the tournament.");
+    run_tournament(
+        registrants,
+        context.tournament_rules,
+        &context.db_client,
+        &context.reconnections,
+        &context.live_games,
+        log_id,
+    )
+    .await;
+}
+
+/// Answer to a game mode 8 request : run a local tournament between the players sharing the client's keyboard, with the
+/// tournament rules.
+async fn launch_game_mode_8<S, D>(
+    player: Player<S>,
+    aliases: Vec<String>,
+    rules: GameRules,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 8] request received, {} players.",
+        u8::from(player.version()),
+        aliases.len()
+    );
+    match run_local_tournament(player, aliases, rules, log_id).await {
+        Ok(mut player) => {
+            log::trace!("{log_id}: The local tournament has been played to completion.");
+            player.close(CloseReason::Done).await;
+        }
+        Err(e) => log::info!("{log_id}: Error encountered while playing the tournament : {e}."),
+    }
+}
+
+/// Answer to a game mode 9 request : send the list of the running games, or make the player watch the selected one
+/// until its end.
+async fn launch_game_mode_9<S, D>(
+    mut player: Player<S>,
+    parameters: SpectateParameters,
+    live_games: &LiveGames,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Game mode 9] request received.",
+        u8::from(player.version())
+    );
+    let selector = match parameters {
+        SpectateParameters::List => {
+            let _: Result<_, _> = player.send(LiveGamesMessage::new(live_games.list())).await;
+            player.close(CloseReason::Done).await;
+            return;
+        }
+        SpectateParameters::Watch(selector) => selector,
+    };
+    log::trace!("{log_id}: Watching the game selected by {selector:?}.");
+    match spectate_game(&mut player, &selector, live_games).await {
+        Ok(()) => {
+            log::trace!("{log_id}: The game has been watched to completion.");
+            player.close(CloseReason::Done).await;
+        }
+        Err(e) => {
+            log::info!("{log_id}: Stopped watching the game : {e}.");
+            if let Some(reason) = e.close_reason() {
+                player.close(reason).await;
+            }
+        }
+    }
+}
+
+/// Answer to a resume request : hand the player over to the running game waiting for it, which resynchronizes it.
+async fn launch_resume<S, D>(player: Player<S>, reconnections: &Reconnections<S>, log_id: &D)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    log::trace!(
+        "{log_id}: [Version {}]-[Resume] request received.",
+        u8::from(player.version())
+    );
+    match reconnections.resume(player) {
+        Ok(()) => log::info!("{log_id}: Connection has been given back to its game."),
+        Err(mut player) => {
+            log::info!("{log_id}: No game is waiting for this player.");
+            player.close(CloseReason::NoGameToResume).await;
+        }
+    }
+}
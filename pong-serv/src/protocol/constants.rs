@@ -12,7 +12,6 @@ pub const PAD_HEIGHT: f64 = 0.100;
 
 pub const BALL_MOVEMENT_PER_SECOND: f64 = 1.15;
 pub const PAD_MOVEMENT_PER_SECOND: f64 = 1.5;
-pub const BALL_MOVEMENT_PER_TICK: f64 = BALL_MOVEMENT_PER_SECOND / TICKS_PER_SECOND as f64;
 pub const PAD_MOVEMENT_PER_TICK: f64 = PAD_MOVEMENT_PER_SECOND / TICKS_PER_SECOND as f64;
 
 pub const MAX_SERVICE_ANGLE_AMPL: f64 = FRAC_PI_6;
@@ -20,5 +19,16 @@ pub const HALF_SERVICE_ANGLE_AMPL: f64 = MAX_SERVICE_ANGLE_AMPL / 2.0;
 pub const PAD_BOUNCE_ANGLE_AMPL: f64 = FRAC_PI_3;
 pub const HALF_BOUNCE_ANGLE_AMPL: f64 = FRAC_PI_6;
 
+pub const TARGET_SCORE: u32 = 10;
+pub const MAX_TARGET_SCORE: u32 = 21;
+/// Whatever the rules, the first player to reach this score wins, so that a game won by two points ends.
+pub const MAX_SCORE: u32 = 99;
+pub const MIN_BALL_MOVEMENT_PER_SECOND: f64 = 0.5;
+pub const MAX_BALL_MOVEMENT_PER_SECOND: f64 = 2.5;
+pub const MIN_PAD_HEIGHT: f64 = 0.05;
+pub const MAX_PAD_HEIGHT: f64 = 0.3;
+
 pub const TICKS_PER_SECOND: u64 = 100;
 pub const MAX_CLIENT_UPDATES_PER_SECOND: u64 = 20;
+/// The ticks the serving side is given to serve, before the ball is served automatically.
+pub const SERVE_TIMEOUT_TICKS: u64 = 3 * TICKS_PER_SECOND;
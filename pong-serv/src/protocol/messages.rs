@@ -1,5 +1,211 @@
 //! Structures modeling the protocol messages, and easy conversions to and from cbor.
+//!
+//! The server-to-client structures only hold the data of the messages. They are all wrapped in
+//! [`ServerToClientMessage`], which is serialized by the version of the protocol spoken with the client, see
+//! [`super::version`].
 
+use challenge::ChallengeMessage;
+use error::ErrorMessage;
+use game_running::{
+    GameAbortedMessage, GameCompletedMessage, OpponentConnectionMessage, PointScoredMessage,
+    PositionUpdateMessage, ServeMessage,
+};
+use game_start::{
+    GameMode0StartMessage, GameMode1StartMessage, GameResumeMessage, GameStartStatusMessage,
+};
+use invite::InviteCodeMessage;
+use post_game::PostGameStatusMessage;
+use queue::{BackInQueueMessage, MatchFoundMessage, QueueStatusMessage};
+use rating::RatingUpdateMessage;
+use series::SeriesScoreMessage;
+use spectating::{LiveGamesMessage, SpectatorSnapshotMessage};
+use tournament::{
+    LocalTournamentMatchMessage, LocalTournamentWinnerMessage, TournamentMatchMessage,
+};
+
+pub mod challenge;
+pub mod error;
 pub mod game_running;
 pub mod game_start;
 pub mod hello;
+pub mod invite;
+pub mod parameters;
+pub mod post_game;
+pub mod queue;
+pub mod rating;
+pub mod series;
+pub mod spectating;
+pub mod tournament;
+
+/// Enum wrapping the various server-to-client messages.
+#[derive(Clone)]
+pub enum ServerToClientMessage {
+    PositionUpdate(PositionUpdateMessage),
+    PointScored(PointScoredMessage),
+    GameDone(GameCompletedMessage),
+    GameAborted(GameAbortedMessage),
+    OpponentConnection(OpponentConnectionMessage),
+    PostGameStatus(PostGameStatusMessage),
+    GameMode0Start(GameMode0StartMessage),
+    GameMode1Start(GameMode1StartMessage),
+    GameResume(GameResumeMessage),
+    GameStartStatus(GameStartStatusMessage),
+    InviteCode(InviteCodeMessage),
+    Challenge(ChallengeMessage),
+    TournamentMatch(TournamentMatchMessage),
+    LocalTournamentMatch(LocalTournamentMatchMessage),
+    LocalTournamentWinner(LocalTournamentWinnerMessage),
+    LiveGames(LiveGamesMessage),
+    SpectatorSnapshot(SpectatorSnapshotMessage),
+    RatingUpdate(RatingUpdateMessage),
+    QueueStatus(QueueStatusMessage),
+    MatchFound(MatchFoundMessage),
+    BackInQueue(BackInQueueMessage),
+    SeriesScore(SeriesScoreMessage),
+    Serve(ServeMessage),
+    Error(ErrorMessage),
+}
+
+impl From<PositionUpdateMessage> for ServerToClientMessage {
+    fn from(value: PositionUpdateMessage) -> Self {
+        Self::PositionUpdate(value)
+    }
+}
+
+impl From<PointScoredMessage> for ServerToClientMessage {
+    fn from(value: PointScoredMessage) -> Self {
+        Self::PointScored(value)
+    }
+}
+
+impl From<GameCompletedMessage> for ServerToClientMessage {
+    fn from(value: GameCompletedMessage) -> Self {
+        Self::GameDone(value)
+    }
+}
+
+impl From<GameAbortedMessage> for ServerToClientMessage {
+    fn from(value: GameAbortedMessage) -> Self {
+        Self::GameAborted(value)
+    }
+}
+
+impl From<OpponentConnectionMessage> for ServerToClientMessage {
+    fn from(value: OpponentConnectionMessage) -> Self {
+        Self::OpponentConnection(value)
+    }
+}
+
+impl From<PostGameStatusMessage> for ServerToClientMessage {
+    fn from(value: PostGameStatusMessage) -> Self {
+        Self::PostGameStatus(value)
+    }
+}
+
+impl From<GameMode0StartMessage> for ServerToClientMessage {
+    fn from(value: GameMode0StartMessage) -> Self {
+        Self::GameMode0Start(value)
+    }
+}
+
+impl From<GameMode1StartMessage> for ServerToClientMessage {
+    fn from(value: GameMode1StartMessage) -> Self {
+        Self::GameMode1Start(value)
+    }
+}
+
+impl From<GameResumeMessage> for ServerToClientMessage {
+    fn from(value: GameResumeMessage) -> Self {
+        Self::GameResume(value)
+    }
+}
+
+impl From<GameStartStatusMessage> for ServerToClientMessage {
+    fn from(value: GameStartStatusMessage) -> Self {
+        Self::GameStartStatus(value)
+    }
+}
+
+impl From<InviteCodeMessage> for ServerToClientMessage {
+    fn from(value: InviteCodeMessage) -> Self {
+        Self::InviteCode(value)
+    }
+}
+
+impl From<ChallengeMessage> for ServerToClientMessage {
+    fn from(value: ChallengeMessage) -> Self {
+        Self::Challenge(value)
+    }
+}
+
+impl From<TournamentMatchMessage> for ServerToClientMessage {
+    fn from(value: TournamentMatchMessage) -> Self {
+        Self::TournamentMatch(value)
+    }
+}
+
+impl From<LocalTournamentMatchMessage> for ServerToClientMessage {
+    fn from(value: LocalTournamentMatchMessage) -> Self {
+        Self::LocalTournamentMatch(value)
+    }
+}
+
+impl From<LocalTournamentWinnerMessage> for ServerToClientMessage {
+    fn from(value: LocalTournamentWinnerMessage) -> Self {
+        Self::LocalTournamentWinner(value)
+    }
+}
+
+impl From<LiveGamesMessage> for ServerToClientMessage {
+    fn from(value: LiveGamesMessage) -> Self {
+        Self::LiveGames(value)
+    }
+}
+
+impl From<SpectatorSnapshotMessage> for ServerToClientMessage {
+    fn from(value: SpectatorSnapshotMessage) -> Self {
+        Self::SpectatorSnapshot(value)
+    }
+}
+
+impl From<RatingUpdateMessage> for ServerToClientMessage {
+    fn from(value: RatingUpdateMessage) -> Self {
+        Self::RatingUpdate(value)
+    }
+}
+
+impl From<QueueStatusMessage> for ServerToClientMessage {
+    fn from(value: QueueStatusMessage) -> Self {
+        Self::QueueStatus(value)
+    }
+}
+
+impl From<MatchFoundMessage> for ServerToClientMessage {
+    fn from(value: MatchFoundMessage) -> Self {
+        Self::MatchFound(value)
+    }
+}
+
+impl From<BackInQueueMessage> for ServerToClientMessage {
+    fn from(value: BackInQueueMessage) -> Self {
+        Self::BackInQueue(value)
+    }
+}
+
+impl From<SeriesScoreMessage> for ServerToClientMessage {
+    fn from(value: SeriesScoreMessage) -> Self {
+        Self::SeriesScore(value)
+    }
+}
+
+impl From<ServeMessage> for ServerToClientMessage {
+    fn from(value: ServeMessage) -> Self {
+        Self::Serve(value)
+    }
+}
+
+impl From<ErrorMessage> for ServerToClientMessage {
+    fn from(value: ErrorMessage) -> Self {
+        Self::Error(value)
+    }
+}
@@ -0,0 +1,61 @@
+//! Protocol-compliant (de)serializable structures to communicate with a client challenged by a friend.
+
+/// What happened to the challenge a client is told about, as described in the Protocol.
+#[derive(Copy, Clone)]
+pub enum ChallengeStatus {
+    /// A friend challenges the client, and waits for its answer.
+    Challenged,
+    /// The friend withdrew its challenge before the client answered.
+    Withdrawn,
+}
+
+impl From<ChallengeStatus> for u8 {
+    fn from(value: ChallengeStatus) -> Self {
+        match value {
+            ChallengeStatus::Challenged => 0,
+            ChallengeStatus::Withdrawn => 1,
+        }
+    }
+}
+
+/// Structure representing the Challenge Message as introduced in the Protocol Version 9.
+#[derive(Clone)]
+pub struct ChallengeMessage {
+    pub(in crate::protocol) status: u8,
+    pub(in crate::protocol) challenger_username: String,
+}
+
+impl ChallengeMessage {
+    pub fn new(status: ChallengeStatus, challenger_username: &str) -> Self {
+        Self {
+            status: status.into(),
+            challenger_username: String::from(challenger_username),
+        }
+    }
+}
+
+/// Decode the answer of a client to a challenge : {accept: bool}. Returns whether the challenge is accepted.
+pub fn parse_challenge_answer(
+    bytes: &[u8],
+) -> Result<bool, ciborium::de::Error<<&'static [u8] as ciborium_io::Read>::Error>> {
+    let (accept,): (bool,) = ciborium::from_reader(bytes)?;
+    Ok(accept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenge_answer_parsing() {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&(true,), &mut bytes).unwrap();
+        assert!(parse_challenge_answer(&bytes).unwrap());
+        bytes.clear();
+        ciborium::into_writer(&(false,), &mut bytes).unwrap();
+        assert!(!parse_challenge_answer(&bytes).unwrap());
+        bytes.clear();
+        ciborium::into_writer(&(1u8,), &mut bytes).unwrap();
+        assert!(parse_challenge_answer(&bytes).is_err());
+    }
+}
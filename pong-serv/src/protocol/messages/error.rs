@@ -0,0 +1,119 @@
+//! Protocol-compliant serializable structure and reasons for the server to end a connection.
+
+use std::borrow::Cow;
+
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+/// Reasons for the server to end a connection.
+///
+/// Each reason has the code and text of the WebSocket Close frame ending the connection. All of them but
+/// [`CloseReason::Done`] are also sent beforehand in an [`ErrorMessage`], with their code as described in the
+/// Protocol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CloseReason {
+    /// The protocol ran to completion.
+    Done,
+    /// The client didn't send the Hello message in time.
+    HelloTimeout,
+    /// The ticket given by the client doesn't authenticate any user.
+    AuthenticationFailed,
+    /// The client requested a version of the protocol this server doesn't support.
+    UnsupportedVersion,
+    /// The client requested a game mode that doesn't exist in the version it speaks.
+    UnsupportedGameMode,
+    /// The client gave parameters the requested game mode doesn't accept.
+    InvalidParameters,
+    /// The client sent an unexpected message.
+    ProtocolViolation,
+    /// The client didn't answer a keep-alive ping in time.
+    Unresponsive,
+    /// The server is shutting down.
+    ServerShutdown,
+    /// The server encountered an error it can't recover from, such as a database failure.
+    InternalError,
+    /// The client asked to resume a game, but none is waiting for it.
+    NoGameToResume,
+    /// Nobody joined the private game hosted by the client in time.
+    InviteExpired,
+    /// The client asked to join a private game, but none is hosted with the given code.
+    UnknownInviteCode,
+    /// The client challenged a user it is not friends with.
+    NotFriends,
+    /// The client challenged a friend who is not waiting for a game.
+    OpponentUnavailable,
+    /// The challenged friend declined the challenge, or didn't answer in time.
+    ChallengeDeclined,
+    /// The client registered for a tournament while already registered for one.
+    AlreadyRegistered,
+    /// The client asked to watch a game that isn't running, or that it plays in.
+    GameNotFound,
+    /// The spectating client didn't keep up with the game it watches.
+    TooSlow,
+    /// The client didn't confirm in time that it is ready to play the match found for it.
+    NotReady,
+    /// The user already has a connection with the server.
+    AlreadyConnected,
+}
+
+impl CloseReason {
+    /// Make the WebSocket Close frame corresponding to this reason.
+    pub fn close_frame(self) -> CloseFrame<'static> {
+        let (code, reason) = match self {
+            Self::Done => (CloseCode::Normal, "Done"),
+            Self::HelloTimeout => (
+                CloseCode::Library(4000),
+                "Hello message not received in time",
+            ),
+            Self::AuthenticationFailed => (CloseCode::Policy, "Authentication failed"),
+            Self::UnsupportedVersion => (CloseCode::Library(4001), "Unsupported protocol version"),
+            Self::UnsupportedGameMode => (CloseCode::Library(4002), "Unsupported game mode"),
+            Self::InvalidParameters => (CloseCode::Library(4003), "Invalid game mode parameters"),
+            Self::ProtocolViolation => (CloseCode::Protocol, "Protocol violation"),
+            Self::Unresponsive => (CloseCode::Library(4004), "Keep-alive ping not answered"),
+            Self::ServerShutdown => (CloseCode::Away, "Server shutting down"),
+            Self::InternalError => (CloseCode::Error, "Internal server error"),
+            Self::NoGameToResume => (CloseCode::Library(4005), "No game to resume"),
+            Self::InviteExpired => (CloseCode::Library(4006), "Private game invite expired"),
+            Self::UnknownInviteCode => (CloseCode::Library(4007), "Unknown private game code"),
+            Self::NotFriends => (
+                CloseCode::Library(4008),
+                "Not friends with the challenged user",
+            ),
+            Self::OpponentUnavailable => (CloseCode::Library(4009), "Challenged user unavailable"),
+            Self::ChallengeDeclined => (CloseCode::Library(4010), "Challenge declined"),
+            Self::AlreadyRegistered => (
+                CloseCode::Library(4011),
+                "Already registered for a tournament",
+            ),
+            Self::GameNotFound => (CloseCode::Library(4012), "No such running game"),
+            Self::TooSlow => (
+                CloseCode::Library(4013),
+                "Too slow to keep up with the game",
+            ),
+            Self::NotReady => (CloseCode::Library(4014), "Match not confirmed in time"),
+            Self::AlreadyConnected => (CloseCode::Library(4015), "Already connected"),
+        };
+        CloseFrame {
+            code,
+            reason: Cow::Borrowed(reason),
+        }
+    }
+}
+
+/// Structure representing the Error Message as introduced in the Protocol Version 4. Its error code is the one each
+/// version gives to the reason.
+#[derive(Copy, Clone)]
+pub struct ErrorMessage {
+    pub(in crate::protocol) reason: CloseReason,
+}
+
+impl ErrorMessage {
+    /// Create a new [`ErrorMessage`]. Returns [`None`] for [`CloseReason::Done`], which is not an error.
+    pub fn new(reason: CloseReason) -> Option<Self> {
+        match reason {
+            CloseReason::Done => None,
+            reason => Some(Self { reason }),
+        }
+    }
+}
@@ -1,23 +1,32 @@
 //! Protocol-compliant (de)serializable structures and helper functions to communicate with clients about running
 //! games.
 
+use std::time::{Duration, SystemTime};
+
 use tokio_tungstenite::tungstenite;
 use tokio_tungstenite::tungstenite::Message;
 
 use crate::game::Side;
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::game_start::starting_time_from_system_time;
+use crate::protocol::version::ProtocolVersion;
 
 /// Errors encountered while receiving an update message from the client.
 #[derive(thiserror::Error, Debug)]
 pub enum ClientUpdateError {
     /// This error happens when a poll to a [`WebSocketStream`] returns an error.
     #[error("Error at the websocket layer : {0}")]
-    ConnectionError(#[from] tungstenite::Error),
+    ConnectionError(#[source] Box<tungstenite::Error>),
 
     /// This error happens when a poll to a [`WebSocketStream`] returns [`None`], or that the connection has been
     /// closed.
     #[error("Connection closed or lost")]
     ConnectionLost,
 
+    /// This error happens when the client closes the connection.
+    #[error("The client closed the connection")]
+    Left,
+
     /// This error happens when the deserialization of the binary data received failed.
     #[error("Parsing failed : {0:?}")]
     ParsingFailed(#[from] ciborium::de::Error<<&'static [u8] as ciborium_io::Read>::Error>),
@@ -27,123 +36,142 @@ pub enum ClientUpdateError {
     ProtocolViolation,
 }
 
+impl From<tungstenite::Error> for ClientUpdateError {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::ConnectionError(Box::new(error))
+    }
+}
+
+impl ClientUpdateError {
+    /// Whether the connection was lost, rather than closed by the client or misused. A client that lost its
+    /// connection may come back.
+    pub fn is_connection_loss(&self) -> bool {
+        matches!(self, Self::ConnectionError(_) | Self::ConnectionLost)
+    }
+
+    /// The reason to give the client when closing its connection, if it can still be told anything.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::ConnectionError(_) | Self::ConnectionLost | Self::Left => None,
+            Self::ParsingFailed(_) | Self::ProtocolViolation => {
+                Some(CloseReason::ProtocolViolation)
+            }
+        }
+    }
+}
+
+/// An input of a client during a running game : the movement of the pads it drives, or the serve of the ball.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ClientInput<M> {
+    Movement(M),
+    Serve,
+}
+
 /// Process the output of a poll on the given [`WebSocketStream`]. Handle [`ClientUpdateError`]s, and - if it was not a
-/// ping - return the deserialized part of the update message that matters : the player pad's movement.
+/// ping - return the deserialized part of the update message that matters : the player pad's movement, or its serve.
+/// Only the clients whose `version` of the protocol has the serve message may send it.
 pub fn parse_gm0_input_message(
     msg: Option<Result<Message, tungstenite::Error>>,
-) -> Result<Option<i8>, ClientUpdateError> {
+    version: ProtocolVersion,
+) -> Result<Option<ClientInput<i8>>, ClientUpdateError> {
     match msg {
         Some(Ok(Message::Ping(_))) => Ok(None),
         Some(Ok(Message::Binary(b))) => match ciborium::from_reader(b.as_slice()) {
             Ok((delta,)) => {
-                if -1 <= delta && delta <= 1 {
-                    Ok(Some(delta))
+                if (-1..=1).contains(&delta) {
+                    Ok(Some(ClientInput::Movement(delta)))
                 } else {
                     Err(ClientUpdateError::ProtocolViolation)
                 }
             }
-            Err(e) => Err(e.into()),
+            Err(_) if version.serves() => parse_serve_message(&b).map(Some),
+            Err(_) => Err(ClientUpdateError::ProtocolViolation),
         },
+        Some(Ok(Message::Close(_))) => Err(ClientUpdateError::Left),
         Some(Ok(_)) => Err(ClientUpdateError::ProtocolViolation),
         Some(Err(tungstenite::Error::ConnectionClosed)) | None => {
             Err(ClientUpdateError::ConnectionLost)
         }
-        Some(Err(e)) => Err(ClientUpdateError::ConnectionError(e)),
+        Some(Err(e)) => Err(ClientUpdateError::ConnectionError(Box::new(e))),
     }
 }
 
+/// Same as [`parse_gm0_input_message`] for local games : the movement is the one of both pads, left then right.
 pub fn parse_gm1_input_message(
     msg: Option<Result<Message, tungstenite::Error>>,
-) -> Result<Option<(i8, i8)>, ClientUpdateError> {
+    version: ProtocolVersion,
+) -> Result<Option<ClientInput<(i8, i8)>>, ClientUpdateError> {
     match msg {
         Some(Ok(Message::Ping(_))) => Ok(None),
         Some(Ok(Message::Binary(b))) => match ciborium::from_reader(b.as_slice()) {
             Ok((left_movement, right_movement))
-                if -1 <= left_movement
-                    && left_movement <= 1
-                    && -1 <= right_movement
-                    && right_movement <= 1 =>
+                if (-1..=1).contains(&left_movement) && (-1..=1).contains(&right_movement) =>
             {
-                Ok(Some((left_movement, right_movement)))
+                Ok(Some(ClientInput::Movement((left_movement, right_movement))))
             }
             Ok(_) => Err(ClientUpdateError::ProtocolViolation),
-            Err(e) => Err(e.into()),
+            Err(_) if version.serves() => parse_serve_message(&b).map(Some),
+            Err(_) => Err(ClientUpdateError::ProtocolViolation),
         },
+        Some(Ok(Message::Close(_))) => Err(ClientUpdateError::Left),
         Some(Ok(_)) => Err(ClientUpdateError::ProtocolViolation),
         Some(Err(tungstenite::Error::ConnectionClosed)) | None => {
             Err(ClientUpdateError::ConnectionLost)
         }
-        Some(Err(e)) => Err(ClientUpdateError::ConnectionError(e)),
+        Some(Err(e)) => Err(ClientUpdateError::ConnectionError(Box::new(e))),
     }
 }
 
-/// Enum wrapping the various server-to-client messages introduced in the Protocol Version pre-1.
-#[derive(Copy, Clone)]
-pub enum ServerToClientMessage {
-    PositionUpdate(PositionUpdateMessage),
-    PointScored(PointScoredMessage),
-    GameDone(GameCompletedMessage),
-}
-
-impl From<ServerToClientMessage> for Vec<u8> {
-    fn from(value: ServerToClientMessage) -> Self {
-        match value {
-            ServerToClientMessage::PositionUpdate(msg) => msg.into(),
-            ServerToClientMessage::PointScored(msg) => msg.into(),
-            ServerToClientMessage::GameDone(msg) => msg.into(),
-        }
+/// Decode binary data that isn't a movement as the serve message : the empty structure `{}`.
+fn parse_serve_message<M>(bytes: &[u8]) -> Result<ClientInput<M>, ClientUpdateError> {
+    let fields: Vec<ciborium::Value> = ciborium::from_reader(bytes)?;
+    if fields.is_empty() {
+        Ok(ClientInput::Serve)
+    } else {
+        Err(ClientUpdateError::ProtocolViolation)
     }
 }
 
 /// Structure representing the Position Update Message as introduced in the Protocol Version pre-1.
 #[derive(Copy, Clone)]
 pub struct PositionUpdateMessage {
-    msg_id: u8,
-    l_pad_y: f64,
-    r_pad_y: f64,
-    ball_x: f64,
-    ball_y: f64,
+    pub(in crate::protocol) l_pad_y: f64,
+    pub(in crate::protocol) r_pad_y: f64,
+    pub(in crate::protocol) ball_x: f64,
+    pub(in crate::protocol) ball_y: f64,
+    /// The milliseconds left on the game clock, in time-limited games only. Sent from the Protocol Version 18 on.
+    pub(in crate::protocol) remaining_time: Option<u32>,
 }
 
 impl PositionUpdateMessage {
-    pub fn new(l_pad_y: f64, r_pad_y: f64, ball_x: f64, ball_y: f64) -> PositionUpdateMessage {
+    /// Create a new [`PositionUpdateMessage`], with the time left on the game clock if the game is time-limited.
+    pub fn new(
+        l_pad_y: f64,
+        r_pad_y: f64,
+        ball_x: f64,
+        ball_y: f64,
+        remaining_time: Option<Duration>,
+    ) -> PositionUpdateMessage {
         PositionUpdateMessage {
-            msg_id: 0,
             l_pad_y,
             r_pad_y,
             ball_x,
             ball_y,
+            remaining_time: remaining_time.map(|remaining_time| {
+                u32::try_from(remaining_time.as_millis()).unwrap_or(u32::MAX)
+            }),
         }
     }
 }
 
-impl From<PositionUpdateMessage> for Vec<u8> {
-    fn from(value: PositionUpdateMessage) -> Self {
-        let mut bytes = Vec::new();
-        ciborium::into_writer(
-            &(
-                value.msg_id,
-                value.l_pad_y,
-                value.r_pad_y,
-                value.ball_x,
-                value.ball_y,
-            ),
-            &mut bytes,
-        )
-        .expect("Could not serialize a PositionUpdateMessage instance.");
-        bytes
-    }
-}
-
 /// Structure representing the Point Scored Message as introduced in the Protocol Version pre-1.
 #[derive(Copy, Clone)]
 pub struct PointScoredMessage {
-    msg_id: u8,
-    side: u8,
-    l_pad_y: f64,
-    r_pad_y: f64,
-    ball_x: f64,
-    ball_y: f64,
+    pub(in crate::protocol) side: u8,
+    pub(in crate::protocol) l_pad_y: f64,
+    pub(in crate::protocol) r_pad_y: f64,
+    pub(in crate::protocol) ball_x: f64,
+    pub(in crate::protocol) ball_y: f64,
 }
 
 impl PointScoredMessage {
@@ -155,7 +183,6 @@ impl PointScoredMessage {
         ball_y: f64,
     ) -> PointScoredMessage {
         PointScoredMessage {
-            msg_id: 1,
             side: side.into(),
             l_pad_y,
             r_pad_y,
@@ -165,67 +192,100 @@ impl PointScoredMessage {
     }
 }
 
-impl From<PointScoredMessage> for Vec<u8> {
-    fn from(value: PointScoredMessage) -> Self {
-        let mut bytes = Vec::new();
-        ciborium::into_writer(
-            &(
-                value.msg_id,
-                value.side,
-                value.l_pad_y,
-                value.r_pad_y,
-                value.ball_x,
-                value.ball_y,
-            ),
-            &mut bytes,
-        )
-        .expect("Could not serialize a PointScoredMessage instance.");
-        bytes
-    }
-}
-
 /// Structure representing the Game Completed Message as introduced in the Protocol Version pre-1.
 #[derive(Copy, Clone)]
 pub struct GameCompletedMessage {
-    msg_id: u8,
-    side: u8,
+    pub(in crate::protocol) side: u8,
 }
 
 impl GameCompletedMessage {
     pub fn new(side: Side) -> GameCompletedMessage {
-        GameCompletedMessage {
-            msg_id: 2,
+        GameCompletedMessage { side: side.into() }
+    }
+}
+
+/// Structure representing the Serve Message as introduced in the Protocol Version 20.
+#[derive(Copy, Clone)]
+pub struct ServeMessage {
+    pub(in crate::protocol) side: u8,
+    pub(in crate::protocol) deadline: u64,
+}
+
+impl ServeMessage {
+    /// Create a new [`ServeMessage`] for the serving side. The ball is served automatically at the deadline.
+    pub fn new(side: Side, deadline: SystemTime) -> ServeMessage {
+        ServeMessage {
             side: side.into(),
+            deadline: starting_time_from_system_time(deadline),
         }
     }
 }
 
-impl From<GameCompletedMessage> for Vec<u8> {
-    fn from(value: GameCompletedMessage) -> Self {
-        let mut bytes = Vec::new();
-        ciborium::into_writer(&(value.msg_id, value.side), &mut bytes)
-            .expect("Could not serialize a GameDoneMessage instance.");
-        bytes
+/// Structure representing the Game Aborted Message as introduced in the Protocol Version pre-2.
+#[derive(Copy, Clone)]
+pub struct GameAbortedMessage;
+
+/// Connection status of the opponent during a running remote game, as described in the Protocol.
+#[derive(Copy, Clone)]
+pub enum OpponentConnectionStatus {
+    /// The opponent disconnected, the game is paused until it reconnects or the reconnection window ends.
+    Disconnected,
+    /// The opponent reconnected, the game resumes.
+    Reconnected,
+}
+
+impl From<OpponentConnectionStatus> for u8 {
+    fn from(value: OpponentConnectionStatus) -> Self {
+        match value {
+            OpponentConnectionStatus::Disconnected => 0,
+            OpponentConnectionStatus::Reconnected => 1,
+        }
     }
 }
 
-/// Structure representing the Game Aborted Message as introduced in the Protocol Version pre-2.
+/// Structure representing the Opponent Connection Message as introduced in the Protocol Version 5.
 #[derive(Copy, Clone)]
-pub struct GameAbortedMessage {
-    msg_id: u8,
+pub struct OpponentConnectionMessage {
+    pub(in crate::protocol) status: u8,
+    pub(in crate::protocol) time: u64,
 }
 
-impl GameAbortedMessage {
-    pub fn new() -> GameAbortedMessage {
-        GameAbortedMessage { msg_id: 3 }
+impl OpponentConnectionMessage {
+    /// Create a new [`OpponentConnectionMessage`]. The time is the end of the reconnection window for
+    /// [`OpponentConnectionStatus::Disconnected`], and the time the game resumes at for
+    /// [`OpponentConnectionStatus::Reconnected`].
+    pub fn new(status: OpponentConnectionStatus, time: SystemTime) -> Self {
+        Self {
+            status: status.into(),
+            time: starting_time_from_system_time(time),
+        }
     }
 }
 
-impl From<GameAbortedMessage> for Vec<u8> {
-    fn from(value: GameAbortedMessage) -> Self {
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serve_input_parsing() {
         let mut bytes = Vec::new();
-        ciborium::into_writer(&(value.msg_id,), &mut bytes)
-            .expect("Could not serialize a GameAbortedMessage instance.");
-        bytes
+        ciborium::into_writer(&Vec::<u8>::new(), &mut bytes).unwrap();
+        let serve = || Some(Ok(Message::Binary(bytes.clone())));
+        assert_eq!(
+            parse_gm0_input_message(serve(), ProtocolVersion::V20).unwrap(),
+            Some(ClientInput::Serve)
+        );
+        assert_eq!(
+            parse_gm1_input_message(serve(), ProtocolVersion::V20).unwrap(),
+            Some(ClientInput::Serve)
+        );
+        assert!(matches!(
+            parse_gm0_input_message(serve(), ProtocolVersion::V19),
+            Err(ClientUpdateError::ProtocolViolation)
+        ));
+        assert!(matches!(
+            parse_gm1_input_message(serve(), ProtocolVersion::V19),
+            Err(ClientUpdateError::ProtocolViolation)
+        ));
     }
 }
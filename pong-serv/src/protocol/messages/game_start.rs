@@ -2,66 +2,153 @@
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::game::Side;
+use crate::game::{GameRules, Side};
+
+/// The rules of a game, as described in the Protocol. The messages carry them from the Protocol Version 17 on.
+#[derive(Copy, Clone)]
+pub(in crate::protocol) struct RulesField {
+    pub(in crate::protocol) target_score: u8,
+    pub(in crate::protocol) win_by_two: bool,
+    pub(in crate::protocol) ball_speed: f64,
+    pub(in crate::protocol) pad_height: f64,
+}
+
+impl From<GameRules> for RulesField {
+    fn from(rules: GameRules) -> Self {
+        Self {
+            target_score: u8::try_from(rules.target_score).expect("Target score is beyond an u8."),
+            win_by_two: rules.win_by_two,
+            ball_speed: rules.ball_speed,
+            pad_height: rules.pad_height,
+        }
+    }
+}
 
 /// Structure representing the Game Mode 0 Start Message as introduced in the Protocol Version pre-1.
 #[derive(Clone)]
 pub struct GameMode0StartMessage {
-    enemy_username: String,
-    side: u8,
-    starting_time: u64,
+    pub(in crate::protocol) enemy_username: String,
+    pub(in crate::protocol) side: u8,
+    pub(in crate::protocol) starting_time: u64,
+    pub(in crate::protocol) rules: RulesField,
 }
 
 impl GameMode0StartMessage {
     /// Create a new [`GameMode0StartMessage`], by converting the given `starting_time` to what is described in the
     /// Protocol.
-    pub fn new(enemy_username: &str, side: Side, starting_time: SystemTime) -> Self {
+    pub fn new(
+        enemy_username: &str,
+        side: Side,
+        starting_time: SystemTime,
+        rules: GameRules,
+    ) -> Self {
         Self {
             enemy_username: String::from(enemy_username),
             side: u8::from(side),
             starting_time: starting_time_from_system_time(starting_time),
+            rules: rules.into(),
         }
     }
 }
 
-impl From<GameMode0StartMessage> for Vec<u8> {
-    fn from(value: GameMode0StartMessage) -> Self {
-        let mut bytes = Vec::new();
-        ciborium::into_writer(
-            &(value.enemy_username, value.side, value.starting_time),
-            &mut bytes,
-        )
-        .expect("Could not serialize a GameMode0StartMessage instance.");
-        bytes
-    }
-}
-
 /// Structure representing the Game Mode 1 Start Message as introduced in the Protocol Version pre-3.
+#[derive(Clone)]
 pub struct GameMode1StartMessage {
-    starting_time: u64,
+    pub(in crate::protocol) starting_time: u64,
+    pub(in crate::protocol) rules: RulesField,
 }
 
 impl GameMode1StartMessage {
     /// Create a new [`GameMode1StartMessage`], by converting the given `starting_time` to what is described in the
     /// Protocol.
-    pub fn new(starting_time: SystemTime) -> Self {
+    pub fn new(starting_time: SystemTime, rules: GameRules) -> Self {
         Self {
             starting_time: starting_time_from_system_time(starting_time),
+            rules: rules.into(),
         }
     }
 }
 
-impl From<GameMode1StartMessage> for Vec<u8> {
-    fn from(value: GameMode1StartMessage) -> Self {
-        let mut bytes = Vec::new();
-        ciborium::into_writer(&(value.starting_time,), &mut bytes)
-            .expect("Could not serialize a GameMode1StartMessage instance.");
-        bytes
+/// Structure representing the Game Resume Message as introduced in the Protocol Version 5. It resynchronizes a
+/// reconnecting client with the whole state of its game.
+#[derive(Clone)]
+pub struct GameResumeMessage {
+    pub(in crate::protocol) enemy_username: String,
+    pub(in crate::protocol) side: u8,
+    pub(in crate::protocol) left_score: u8,
+    pub(in crate::protocol) right_score: u8,
+    pub(in crate::protocol) l_pad_y: f64,
+    pub(in crate::protocol) r_pad_y: f64,
+    pub(in crate::protocol) ball_x: f64,
+    pub(in crate::protocol) ball_y: f64,
+    pub(in crate::protocol) resuming_time: u64,
+    pub(in crate::protocol) rules: RulesField,
+}
+
+impl GameResumeMessage {
+    /// Create a new [`GameResumeMessage`], by converting the given `resuming_time` to what is described in the
+    /// Protocol.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        enemy_username: &str,
+        side: Side,
+        scores: [u32; 2],
+        l_pad_y: f64,
+        r_pad_y: f64,
+        ball_x: f64,
+        ball_y: f64,
+        resuming_time: SystemTime,
+        rules: GameRules,
+    ) -> Self {
+        Self {
+            enemy_username: String::from(enemy_username),
+            side: u8::from(side),
+            left_score: u8::try_from(scores[0]).expect("Score is beyond an u8."),
+            right_score: u8::try_from(scores[1]).expect("Score is beyond an u8."),
+            l_pad_y,
+            r_pad_y,
+            ball_x,
+            ball_y,
+            resuming_time: starting_time_from_system_time(resuming_time),
+            rules: rules.into(),
+        }
+    }
+}
+
+/// Whether a game effectively starts at the end of its grace period, as described in the Protocol.
+#[derive(Copy, Clone)]
+pub enum GameStartStatus {
+    /// The game is starting.
+    Starting,
+    /// The game is aborted, the client is put back in queue.
+    Aborted,
+}
+
+impl From<GameStartStatus> for u8 {
+    fn from(value: GameStartStatus) -> Self {
+        match value {
+            GameStartStatus::Starting => 0,
+            GameStartStatus::Aborted => 1,
+        }
+    }
+}
+
+/// Structure representing the Game Start Status Message as introduced in the Protocol Version 3.
+#[derive(Copy, Clone)]
+pub struct GameStartStatusMessage {
+    pub(in crate::protocol) status: u8,
+}
+
+impl GameStartStatusMessage {
+    pub fn new(status: GameStartStatus) -> Self {
+        Self {
+            status: status.into(),
+        }
     }
 }
 
 /// Turn a system time to a u64 amount of milliseconds since the Unix Epoch, all in UTC.
-fn starting_time_from_system_time(system_time: SystemTime) -> u64 {
+pub(in crate::protocol) fn starting_time_from_system_time(system_time: SystemTime) -> u64 {
     system_time
         .duration_since(UNIX_EPOCH)
         .expect("Current system time is before the Unix Epoch.")
@@ -9,96 +9,225 @@ use tokio_tungstenite::tungstenite;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::version::ProtocolVersion;
+use crate::shutdown::Shutdown;
+
 /// Errors encountered while receiving the [`HelloMessage`].
 #[derive(thiserror::Error, Debug)]
 pub enum HelloUpdateError {
     /// This error happens when a poll to a [`WebSocketStream`] returns an error.
     #[error("Error at the websocket layer : {0}")]
-    ConnectionError(#[from] tungstenite::Error),
+    ConnectionError(#[source] Box<tungstenite::Error>),
 
     /// This error happens when a poll to a [`WebSocketStream`] returns [`None`], or that the connection has been
     /// closed.
     #[error("Connection closed or lost")]
     ConnectionLost,
 
+    /// This error happens when the client closes the connection before sending the [`HelloMessage`].
+    #[error("The client closed the connection")]
+    Left,
+
     /// This error happens when the deserialization of the binary data received fails.
     #[error("Parsing failed : {0:?}")]
     ParsingFailed(#[from] ciborium::de::Error<<&'static [u8] as ciborium_io::Read>::Error>),
 
-    /// This error happens when the client sends any message type other than [`Message::Ping`] and [`Message::Binary`].
-    #[error("Received a wrong websocket message type")]
+    /// This error happens when the client sends any message type other than [`Message::Ping`] and [`Message::Binary`],
+    /// or a binary message that doesn't start with a protocol version.
+    #[error("Received a wrong websocket message type or a message without a version")]
     ProtocolViolation,
 
+    /// This error happens when the client requests a version of the protocol not supported by this server.
+    #[error("Protocol version {0} is not supported")]
+    UnsupportedVersion(u8),
+
+    /// This error happens when the client requests a game mode that doesn't exist in the requested version.
+    #[error("Game mode {1} does not exist in protocol version {0}")]
+    UnsupportedGameMode(u8, u8),
+
     /// This error indicates that the client took long enough to send the expected [`HelloMessage`] that it is
     /// considered an error.
     #[error("Didn't receive a binary message within 5 seconds")]
     Timeout,
+
+    /// This error happens when the server starts shutting down before the [`HelloMessage`] is received.
+    #[error("The server is shutting down")]
+    ServerShutdown,
 }
 
-/// Names of the game modes for their [`u8`] values as described in the protocol specification.
-pub enum GameModes {
-    MatchMadeRemote1v1,
-    Local1v1,
+impl From<tungstenite::Error> for HelloUpdateError {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::ConnectionError(Box::new(error))
+    }
 }
 
-impl From<GameModes> for u8 {
-    fn from(value: GameModes) -> Self {
-        match value {
-            GameModes::MatchMadeRemote1v1 => 0,
-            GameModes::Local1v1 => 1,
+impl HelloUpdateError {
+    /// The reason to give the client when closing its connection, if it can still be told anything.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::ConnectionError(_) | Self::ConnectionLost | Self::Left => None,
+            Self::ParsingFailed(_) | Self::ProtocolViolation => {
+                Some(CloseReason::ProtocolViolation)
+            }
+            Self::UnsupportedVersion(_) => Some(CloseReason::UnsupportedVersion),
+            Self::UnsupportedGameMode(_, _) => Some(CloseReason::UnsupportedGameMode),
+            Self::Timeout => Some(CloseReason::HelloTimeout),
+            Self::ServerShutdown => Some(CloseReason::ServerShutdown),
         }
     }
 }
 
+/// Names of the game modes. Their [`u8`] values are given by the game mode table of each protocol version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GameModes {
+    MatchMadeRemote1v1,
+    Local1v1,
+    PrivateRemote1v1,
+    Resume,
+    AiOpponent,
+    FriendChallenge,
+    Lobby,
+    Tournament,
+    LocalTournament,
+    Spectate,
+    Series,
+}
+
 /// Structure representing the Hello Message as introduced in the Protocol Version 1.
+///
+/// The token is the one of a ticket issued by the website, to be redeemed for the username of the client. Version 3
+/// predates the tickets : the token is then the username the client claims.
+/// The parameters are kept encoded, as their type depends on the game mode. They are decoded into one of the
+/// structures of [`super::parameters`].
 pub struct HelloMessage {
-    pub proto_version: u8,
-    pub id: String,
-    pub game_mode: u8,
+    pub version: ProtocolVersion,
+    pub token: String,
+    pub game_mode: GameModes,
+    pub parameters: Vec<u8>,
 }
 
 impl HelloMessage {
-    fn new(proto_version: u8, id: String, game_mode: u8) -> Self {
+    pub(in crate::protocol) fn new(
+        version: ProtocolVersion,
+        token: String,
+        game_mode: GameModes,
+        parameters: Vec<u8>,
+    ) -> Self {
         HelloMessage {
-            proto_version,
-            id,
+            version,
+            token,
             game_mode,
+            parameters,
         }
     }
 }
 
 /// Wait for the client to send the [`HelloMessage`].
 ///
-/// Answers all pings with pongs, until either timing out, receiving some kind of error or erroneous message, or the
-/// expected [`HelloMessage`].
+/// Answers all pings with pongs, until either timing out, receiving some kind of error or erroneous message, the server
+/// shutting down, or the expected [`HelloMessage`].
 pub async fn receive_hello_message<S>(
     websocket: &mut WebSocketStream<S>,
+    shutdown: &mut Shutdown,
 ) -> Result<HelloMessage, HelloUpdateError>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     //Set up timing out after 5 secs.
     let timeout_instant = Instant::now() + Duration::from_secs(5);
-    let mut timeout_result = tokio::time::timeout_at(timeout_instant, websocket.next()).await;
-
-    //Answer all pings, until anything different happens.
-    while let Ok(Some(Ok(Message::Ping(_)))) = timeout_result {
-        timeout_result = tokio::time::timeout_at(timeout_instant, websocket.next()).await;
-    }
+    let timeout_result = tokio::select! {
+        timeout_result = tokio::time::timeout_at(timeout_instant, next_non_ping_message(websocket)) => timeout_result,
+        () = shutdown.notified() => return Err(HelloUpdateError::ServerShutdown),
+    };
 
-    //Process the different message.
+    //Process the non-ping message.
     match timeout_result {
-        Ok(Some(Ok(Message::Binary(msg)))) => match ciborium::from_reader(msg.as_slice()) {
-            Ok((proto_version, id, game_mode)) => {
-                Ok(HelloMessage::new(proto_version, id, game_mode))
-            }
-            Err(e) => Err(e.into()),
-        },
+        Ok(Some(Ok(Message::Binary(msg)))) => decode_hello_message(&msg),
+        Ok(Some(Ok(Message::Close(_)))) => Err(HelloUpdateError::Left),
         Ok(Some(Ok(_))) => Err(HelloUpdateError::ProtocolViolation),
         Ok(Some(Err(tungstenite::Error::ConnectionClosed))) | Ok(None) => {
             Err(HelloUpdateError::ConnectionLost)
         }
-        Ok(Some(Err(e))) => Err(HelloUpdateError::ConnectionError(e)),
+        Ok(Some(Err(e))) => Err(HelloUpdateError::ConnectionError(Box::new(e))),
         Err(_) => Err(HelloUpdateError::Timeout),
     }
 }
+
+/// Answer all pings, until anything different happens.
+async fn next_non_ping_message<S>(
+    websocket: &mut WebSocketStream<S>,
+) -> Option<Result<Message, tungstenite::Error>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        match websocket.next().await {
+            Some(Ok(Message::Ping(_))) => {}
+            other => return other,
+        }
+    }
+}
+
+/// Read the protocol version the Hello message starts with, and let that version decode the whole message.
+fn decode_hello_message(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    let fields: Vec<ciborium::Value> = ciborium::from_reader(bytes)?;
+    let requested_version = fields
+        .first()
+        .and_then(ciborium::Value::as_integer)
+        .and_then(|version| u8::try_from(version).ok())
+        .ok_or(HelloUpdateError::ProtocolViolation)?;
+    ProtocolVersion::try_from(requested_version)
+        .map_err(|_| HelloUpdateError::UnsupportedVersion(requested_version))?
+        .decode_hello(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use super::*;
+
+    fn encode(fields: Vec<Value>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&Value::Array(fields), &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn hello_decoding() {
+        let hello = decode_hello_message(&encode(vec![
+            Value::from(4),
+            Value::from("token"),
+            Value::from(1),
+            Value::Bytes(vec![0x80]),
+        ]))
+        .unwrap();
+        assert_eq!(hello.version, ProtocolVersion::V4);
+        assert_eq!(hello.token, "token");
+        assert_eq!(hello.game_mode, GameModes::Local1v1);
+        assert_eq!(hello.parameters, vec![0x80]);
+
+        assert!(matches!(
+            decode_hello_message(&encode(vec![Value::from(2)])),
+            Err(HelloUpdateError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            decode_hello_message(&encode(vec![
+                Value::from(4),
+                Value::from("token"),
+                Value::from(200),
+                Value::Bytes(vec![0x80]),
+            ])),
+            Err(HelloUpdateError::UnsupportedGameMode(4, 200))
+        ));
+        assert!(matches!(
+            decode_hello_message(&encode(vec![])),
+            Err(HelloUpdateError::ProtocolViolation)
+        ));
+        assert!(matches!(
+            decode_hello_message(&encode(vec![Value::from(4), Value::from(0)])),
+            Err(HelloUpdateError::ParsingFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,23 @@
+//! Protocol-compliant serializable structure to communicate with the host of a private game.
+
+use std::time::SystemTime;
+
+use crate::protocol::messages::game_start::starting_time_from_system_time;
+
+/// Structure representing the Invite Code Message as introduced in the Protocol Version 8.
+#[derive(Clone)]
+pub struct InviteCodeMessage {
+    pub(in crate::protocol) code: String,
+    pub(in crate::protocol) expiration_time: u64,
+}
+
+impl InviteCodeMessage {
+    /// Create a new [`InviteCodeMessage`], by converting the given `expiration_time` to what is described in the
+    /// Protocol.
+    pub fn new(code: &str, expiration_time: SystemTime) -> Self {
+        Self {
+            code: String::from(code),
+            expiration_time: starting_time_from_system_time(expiration_time),
+        }
+    }
+}
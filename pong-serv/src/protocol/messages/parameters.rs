@@ -0,0 +1,517 @@
+//! Typed parameters of the game modes, carried CBOR-encoded in the parameters field of the [`HelloMessage`].
+//!
+//! Each game mode gets its own structure, built from the raw bytes with [`TryFrom`]. The conversion decodes and
+//! validates the parameters, so that a structure existing means its content is usable as is.
+//!
+//! The game modes whose games are played on the server accept optional [`GameRules`] after their own parameters. The
+//! default rules apply when they are not given.
+//!
+//! [`HelloMessage`]: super::hello::HelloMessage
+
+use crate::game::{Difficulty, GameRules, GameSelector, SeriesLength};
+use crate::protocol::constants::{
+    MAX_BALL_MOVEMENT_PER_SECOND, MAX_PAD_HEIGHT, MAX_TARGET_SCORE, MIN_BALL_MOVEMENT_PER_SECOND,
+    MIN_PAD_HEIGHT,
+};
+
+/// Errors encountered while decoding the parameters of a game mode request.
+#[derive(thiserror::Error, Debug)]
+pub enum ParametersError {
+    /// This error happens when the deserialization of the parameters fails.
+    #[error("Parsing failed : {0:?}")]
+    ParsingFailed(#[from] ciborium::de::Error<<&'static [u8] as ciborium_io::Read>::Error>),
+
+    /// This error happens when the parameters are well-formed, but their values are not accepted by the game mode.
+    #[error("Invalid parameters : {0}")]
+    Invalid(&'static str),
+}
+
+/// The rules of a game as carried by the parameters : target score, win by two, ball speed and pad height.
+type RulesField = (u8, bool, f64, f64);
+
+/// Parameters of a game mode 0 request : the rules of the games to queue for, if any.
+pub struct MatchMadeRemote1v1Parameters {
+    pub rules: GameRules,
+}
+
+impl TryFrom<&[u8]> for MatchMadeRemote1v1Parameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let rules = decode_only_rules(value)?;
+        Ok(Self { rules })
+    }
+}
+
+/// Parameters of a game mode 1 request : the rules of the game, if any.
+pub struct Local1v1Parameters {
+    pub rules: GameRules,
+}
+
+impl TryFrom<&[u8]> for Local1v1Parameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let rules = decode_only_rules(value)?;
+        Ok(Self { rules })
+    }
+}
+
+/// Parameters of a game mode 2 request : whether to host a private game, with the rules of its games, or the code of
+/// the one to join. The rules are the host's to choose.
+pub enum PrivateRemote1v1Parameters {
+    Host(GameRules),
+    Join(String),
+}
+
+impl TryFrom<&[u8]> for PrivateRemote1v1Parameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if let Ok((code, rules)) = ciborium::from_reader::<(Option<String>, RulesField), _>(value) {
+            return match code {
+                None => Ok(Self::Host(validate_rules(rules)?)),
+                Some(_) => Err(ParametersError::Invalid("only the host chooses the rules")),
+            };
+        }
+        let (code,): (Option<String>,) = ciborium::from_reader(value)?;
+        Ok(match code {
+            None => Self::Host(GameRules::default()),
+            Some(code) => Self::Join(code),
+        })
+    }
+}
+
+/// Parameters of a game mode 5 request : the username of the friend to challenge, and the rules of the games, if any.
+pub struct FriendChallengeParameters {
+    pub opponent: String,
+    pub rules: GameRules,
+}
+
+impl TryFrom<&[u8]> for FriendChallengeParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if let Ok((opponent, rules)) = ciborium::from_reader::<(String, RulesField), _>(value) {
+            let rules = validate_rules(rules)?;
+            return Ok(Self { opponent, rules });
+        }
+        let (opponent,): (String,) = ciborium::from_reader(value)?;
+        Ok(Self {
+            opponent,
+            rules: GameRules::default(),
+        })
+    }
+}
+
+/// Parameters of a game mode 6 request, to wait in the lobby. There is none.
+pub struct LobbyParameters;
+
+impl TryFrom<&[u8]> for LobbyParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        decode_no_parameters(value).map(|()| Self)
+    }
+}
+
+/// Parameters of a game mode 7 request : the number of entrants of the tournament to register for.
+pub struct TournamentParameters {
+    pub entrants: u8,
+}
+
+impl TryFrom<&[u8]> for TournamentParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let (entrants,): (u8,) = ciborium::from_reader(value)?;
+        match entrants {
+            4 | 8 | 16 => Ok(Self { entrants }),
+            _ => Err(ParametersError::Invalid("unsupported number of entrants")),
+        }
+    }
+}
+
+/// Parameters of a game mode 8 request : the aliases of the players sharing the keyboard, to run a tournament between.
+/// There are 2 to [`MAX_LOCAL_ENTRANTS`] of them, all distinct and non-empty.
+pub struct LocalTournamentParameters {
+    pub aliases: Vec<String>,
+}
+
+/// Maximum number of players of a local tournament.
+const MAX_LOCAL_ENTRANTS: usize = 16;
+
+impl TryFrom<&[u8]> for LocalTournamentParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let (aliases,): (Vec<String>,) = ciborium::from_reader(value)?;
+        if !(2..=MAX_LOCAL_ENTRANTS).contains(&aliases.len()) {
+            return Err(ParametersError::Invalid("unsupported number of players"));
+        }
+        if aliases.iter().any(String::is_empty) {
+            return Err(ParametersError::Invalid("empty alias"));
+        }
+        if aliases
+            .iter()
+            .enumerate()
+            .any(|(i, alias)| aliases[..i].contains(alias))
+        {
+            return Err(ParametersError::Invalid("duplicate alias"));
+        }
+        Ok(Self { aliases })
+    }
+}
+
+/// Parameters of a game mode 9 request : nothing to list the running games, or the id of the game to watch, or the
+/// username of one of its players.
+pub enum SpectateParameters {
+    List,
+    Watch(GameSelector),
+}
+
+impl TryFrom<&[u8]> for SpectateParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let (target,): (ciborium::Value,) = ciborium::from_reader(value)?;
+        match target {
+            ciborium::Value::Null => Ok(Self::List),
+            ciborium::Value::Integer(id) => u32::try_from(id)
+                .map(|id| Self::Watch(GameSelector::Id(id)))
+                .map_err(|_| ParametersError::Invalid("game id out of range")),
+            ciborium::Value::Text(username) => Ok(Self::Watch(GameSelector::Player(username))),
+            _ => Err(ParametersError::Invalid("unknown game to watch")),
+        }
+    }
+}
+
+/// Parameters of a request to resume a remote game. There is none.
+pub struct ResumeParameters;
+
+impl TryFrom<&[u8]> for ResumeParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        decode_no_parameters(value).map(|()| Self)
+    }
+}
+
+/// Parameters of a game mode 4 request : the difficulty level of the AI opponent, and the rules of the game, if any.
+pub struct AiOpponentParameters {
+    pub difficulty: Difficulty,
+    pub rules: GameRules,
+}
+
+impl TryFrom<&[u8]> for AiOpponentParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let (difficulty, rules) = match ciborium::from_reader::<(u8, RulesField), _>(value) {
+            Ok((difficulty, rules)) => (difficulty, validate_rules(rules)?),
+            Err(_) => {
+                let (difficulty,): (u8,) = ciborium::from_reader(value)?;
+                (difficulty, GameRules::default())
+            }
+        };
+        let difficulty = match difficulty {
+            0 => Difficulty::Easy,
+            1 => Difficulty::Medium,
+            2 => Difficulty::Hard,
+            _ => return Err(ParametersError::Invalid("unknown difficulty level")),
+        };
+        Ok(Self { difficulty, rules })
+    }
+}
+
+/// Parameters of a game mode 10 request : the number of games of the series to queue for, and their rules, if any.
+pub struct SeriesParameters {
+    pub length: SeriesLength,
+    pub rules: GameRules,
+}
+
+impl TryFrom<&[u8]> for SeriesParameters {
+    type Error = ParametersError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let (games, rules) = match ciborium::from_reader::<(u8, RulesField), _>(value) {
+            Ok((games, rules)) => (games, validate_rules(rules)?),
+            Err(_) => {
+                let (games,): (u8,) = ciborium::from_reader(value)?;
+                (games, GameRules::default())
+            }
+        };
+        let length = match games {
+            3 => SeriesLength::BestOf3,
+            5 => SeriesLength::BestOf5,
+            _ => return Err(ParametersError::Invalid("unsupported series length")),
+        };
+        Ok(Self { length, rules })
+    }
+}
+
+/// Decode the parameters of a game mode which only takes optional rules : either the empty structure `{}`, or the
+/// rules alone.
+fn decode_only_rules(bytes: &[u8]) -> Result<GameRules, ParametersError> {
+    match ciborium::from_reader::<(RulesField,), _>(bytes) {
+        Ok((rules,)) => validate_rules(rules),
+        Err(_) => decode_no_parameters(bytes).map(|()| GameRules::default()),
+    }
+}
+
+/// Check that the rules are within the ranges of the Protocol.
+fn validate_rules(
+    (target_score, win_by_two, ball_speed, pad_height): RulesField,
+) -> Result<GameRules, ParametersError> {
+    if !(1..=MAX_TARGET_SCORE).contains(&u32::from(target_score)) {
+        return Err(ParametersError::Invalid("target score out of range"));
+    }
+    if !(MIN_BALL_MOVEMENT_PER_SECOND..=MAX_BALL_MOVEMENT_PER_SECOND).contains(&ball_speed) {
+        return Err(ParametersError::Invalid("ball speed out of range"));
+    }
+    if !(MIN_PAD_HEIGHT..=MAX_PAD_HEIGHT).contains(&pad_height) {
+        return Err(ParametersError::Invalid("pad height out of range"));
+    }
+    Ok(GameRules {
+        target_score: u32::from(target_score),
+        win_by_two,
+        ball_speed,
+        pad_height,
+        time_limit: None,
+    })
+}
+
+/// Check that the parameters of a game mode which doesn't take any are the empty structure `{}`.
+fn decode_no_parameters(bytes: &[u8]) -> Result<(), ParametersError> {
+    let fields: Vec<ciborium::Value> = ciborium::from_reader(bytes)?;
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(ParametersError::Invalid(
+            "this game mode takes no parameters",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use super::*;
+
+    fn encode(value: Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&value, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn no_parameters() {
+        let empty = encode(Value::Array(vec![]));
+        assert_eq!(
+            MatchMadeRemote1v1Parameters::try_from(empty.as_slice())
+                .unwrap()
+                .rules,
+            GameRules::default()
+        );
+        assert!(Local1v1Parameters::try_from(empty.as_slice()).is_ok());
+        assert!(MatchMadeRemote1v1Parameters::try_from(encode(Value::Null).as_slice()).is_err());
+        assert!(matches!(
+            Local1v1Parameters::try_from(encode(Value::Array(vec![Value::from(1)])).as_slice()),
+            Err(ParametersError::Invalid(_))
+        ));
+        assert!(matches!(
+            Local1v1Parameters::try_from([].as_slice()),
+            Err(ParametersError::ParsingFailed(_))
+        ));
+    }
+
+    #[test]
+    fn private_game_parameters() {
+        let host = encode(Value::Array(vec![Value::Null]));
+        assert!(matches!(
+            PrivateRemote1v1Parameters::try_from(host.as_slice()),
+            Ok(PrivateRemote1v1Parameters::Host(rules)) if rules == GameRules::default()
+        ));
+        let join = encode(Value::Array(vec![Value::from("K7X2QP")]));
+        assert!(matches!(
+            PrivateRemote1v1Parameters::try_from(join.as_slice()),
+            Ok(PrivateRemote1v1Parameters::Join(code)) if code == "K7X2QP"
+        ));
+        assert!(matches!(
+            PrivateRemote1v1Parameters::try_from(
+                encode(Value::Array(vec![Value::from(2)])).as_slice()
+            ),
+            Err(ParametersError::ParsingFailed(_))
+        ));
+    }
+
+    #[test]
+    fn ai_opponent_parameters() {
+        let hard = encode(Value::Array(vec![Value::from(2)]));
+        assert_eq!(
+            AiOpponentParameters::try_from(hard.as_slice())
+                .unwrap()
+                .difficulty,
+            Difficulty::Hard
+        );
+        assert!(matches!(
+            AiOpponentParameters::try_from(encode(Value::Array(vec![Value::from(3)])).as_slice()),
+            Err(ParametersError::Invalid(_))
+        ));
+        assert!(matches!(
+            AiOpponentParameters::try_from(encode(Value::Array(vec![])).as_slice()),
+            Err(ParametersError::ParsingFailed(_))
+        ));
+    }
+
+    #[test]
+    fn series_parameters() {
+        let best_of_5 = encode(Value::Array(vec![Value::from(5)]));
+        let series = SeriesParameters::try_from(best_of_5.as_slice()).unwrap();
+        assert_eq!(
+            (series.length, series.rules),
+            (SeriesLength::BestOf5, GameRules::default())
+        );
+        for games in [0, 1, 2, 4, 7] {
+            assert!(matches!(
+                SeriesParameters::try_from(
+                    encode(Value::Array(vec![Value::from(games)])).as_slice()
+                ),
+                Err(ParametersError::Invalid(_))
+            ));
+        }
+        assert!(matches!(
+            SeriesParameters::try_from(encode(Value::Array(vec![])).as_slice()),
+            Err(ParametersError::ParsingFailed(_))
+        ));
+    }
+
+    fn rules(target_score: u8, ball_speed: f64, pad_height: f64) -> Value {
+        Value::Array(vec![
+            Value::from(target_score),
+            Value::Bool(true),
+            Value::Float(ball_speed),
+            Value::Float(pad_height),
+        ])
+    }
+
+    #[test]
+    fn rules_parameters() {
+        let custom = GameRules {
+            target_score: 5,
+            win_by_two: true,
+            ball_speed: 2.0,
+            pad_height: 0.2,
+            time_limit: None,
+        };
+        let only_rules = encode(Value::Array(vec![rules(5, 2.0, 0.2)]));
+        assert_eq!(
+            Local1v1Parameters::try_from(only_rules.as_slice())
+                .unwrap()
+                .rules,
+            custom
+        );
+        let host = encode(Value::Array(vec![Value::Null, rules(5, 2.0, 0.2)]));
+        assert!(matches!(
+            PrivateRemote1v1Parameters::try_from(host.as_slice()),
+            Ok(PrivateRemote1v1Parameters::Host(rules)) if rules == custom
+        ));
+        let join = encode(Value::Array(vec![
+            Value::from("K7X2QP"),
+            rules(5, 2.0, 0.2),
+        ]));
+        assert!(matches!(
+            PrivateRemote1v1Parameters::try_from(join.as_slice()),
+            Err(ParametersError::Invalid(_))
+        ));
+        let ai = encode(Value::Array(vec![Value::from(0), rules(5, 2.0, 0.2)]));
+        let ai = AiOpponentParameters::try_from(ai.as_slice()).unwrap();
+        assert_eq!((ai.difficulty, ai.rules), (Difficulty::Easy, custom));
+        let series = encode(Value::Array(vec![Value::from(3), rules(5, 2.0, 0.2)]));
+        let series = SeriesParameters::try_from(series.as_slice()).unwrap();
+        assert_eq!(
+            (series.length, series.rules),
+            (SeriesLength::BestOf3, custom)
+        );
+        let challenge = encode(Value::Array(vec![Value::from("bob"), rules(5, 2.0, 0.2)]));
+        assert_eq!(
+            FriendChallengeParameters::try_from(challenge.as_slice())
+                .unwrap()
+                .rules,
+            custom
+        );
+        for invalid in [
+            rules(0, 2.0, 0.2),
+            rules(22, 2.0, 0.2),
+            rules(5, 3.0, 0.2),
+            rules(5, 2.0, 0.01),
+        ] {
+            assert!(matches!(
+                MatchMadeRemote1v1Parameters::try_from(
+                    encode(Value::Array(vec![invalid])).as_slice()
+                ),
+                Err(ParametersError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn local_tournament_parameters() {
+        let aliases = |aliases: &[&str]| {
+            encode(Value::Array(vec![Value::Array(
+                aliases.iter().map(|&alias| Value::from(alias)).collect(),
+            )]))
+        };
+        assert_eq!(
+            LocalTournamentParameters::try_from(aliases(&["Ann", "Bob", "Cid"]).as_slice())
+                .unwrap()
+                .aliases,
+            vec!["Ann", "Bob", "Cid"]
+        );
+        for invalid in [&["Ann"][..], &["Ann", ""], &["Ann", "Bob", "Ann"]] {
+            assert!(matches!(
+                LocalTournamentParameters::try_from(aliases(invalid).as_slice()),
+                Err(ParametersError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn spectate_parameters() {
+        let parse = |target: Value| {
+            SpectateParameters::try_from(encode(Value::Array(vec![target])).as_slice())
+        };
+        assert!(matches!(parse(Value::Null), Ok(SpectateParameters::List)));
+        assert!(matches!(
+            parse(Value::from(12)),
+            Ok(SpectateParameters::Watch(GameSelector::Id(12)))
+        ));
+        assert!(matches!(
+            parse(Value::from("marvin")),
+            Ok(SpectateParameters::Watch(GameSelector::Player(username))) if username == "marvin"
+        ));
+        for invalid in [Value::from(-1), Value::from(1u64 << 40), Value::Bool(true)] {
+            assert!(matches!(parse(invalid), Err(ParametersError::Invalid(_))));
+        }
+    }
+
+    #[test]
+    fn tournament_parameters() {
+        let eight = encode(Value::Array(vec![Value::from(8)]));
+        assert_eq!(
+            TournamentParameters::try_from(eight.as_slice())
+                .unwrap()
+                .entrants,
+            8
+        );
+        for entrants in [0, 2, 6, 32] {
+            assert!(matches!(
+                TournamentParameters::try_from(
+                    encode(Value::Array(vec![Value::from(entrants)])).as_slice()
+                ),
+                Err(ParametersError::Invalid(_))
+            ));
+        }
+    }
+}
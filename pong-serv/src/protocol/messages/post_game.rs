@@ -0,0 +1,117 @@
+//! Protocol-compliant (de)serializable structures and helper functions to communicate with clients once their game is
+//! over.
+
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::protocol::messages::game_running::ClientUpdateError;
+
+/// What a client asks for once its game is over, as described in the Protocol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PostGameRequest {
+    /// Play again against the same opponent.
+    Rematch,
+    /// Go back into the match-making queue.
+    Requeue,
+}
+
+impl TryFrom<u8> for PostGameRequest {
+    type Error = ClientUpdateError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Rematch),
+            1 => Ok(Self::Requeue),
+            _ => Err(ClientUpdateError::ProtocolViolation),
+        }
+    }
+}
+
+/// Process the output of a poll on the given [`WebSocketStream`] during the post-game phase. Handle
+/// [`ClientUpdateError`]s, and - if it was not a ping - return the request of the client.
+///
+/// [`WebSocketStream`]: tokio_tungstenite::WebSocketStream
+pub fn parse_post_game_message(
+    msg: Option<Result<Message, tungstenite::Error>>,
+) -> Result<Option<PostGameRequest>, ClientUpdateError> {
+    match msg {
+        Some(Ok(Message::Ping(_))) => Ok(None),
+        Some(Ok(Message::Binary(b))) => {
+            let (request,): (u8,) = ciborium::from_reader(b.as_slice())?;
+            PostGameRequest::try_from(request).map(Some)
+        }
+        Some(Ok(Message::Close(_))) => Err(ClientUpdateError::Left),
+        Some(Ok(_)) => Err(ClientUpdateError::ProtocolViolation),
+        Some(Err(tungstenite::Error::ConnectionClosed)) | None => {
+            Err(ClientUpdateError::ConnectionLost)
+        }
+        Some(Err(e)) => Err(ClientUpdateError::ConnectionError(Box::new(e))),
+    }
+}
+
+/// What the opponent did during the post-game phase, as described in the Protocol.
+#[derive(Copy, Clone)]
+pub enum PostGameStatus {
+    /// The opponent asks for a rematch.
+    RematchRequested,
+    /// The opponent went back into the queue or left : there won't be a rematch.
+    OpponentGone,
+}
+
+impl From<PostGameStatus> for u8 {
+    fn from(value: PostGameStatus) -> Self {
+        match value {
+            PostGameStatus::RematchRequested => 0,
+            PostGameStatus::OpponentGone => 1,
+        }
+    }
+}
+
+/// Structure representing the Post-Game Status Message as introduced in the Protocol Version 6.
+#[derive(Copy, Clone)]
+pub struct PostGameStatusMessage {
+    pub(in crate::protocol) status: u8,
+}
+
+impl PostGameStatusMessage {
+    pub fn new(status: PostGameStatus) -> Self {
+        Self {
+            status: status.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary(request: u8) -> Option<Result<Message, tungstenite::Error>> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&(request,), &mut bytes).unwrap();
+        Some(Ok(Message::Binary(bytes)))
+    }
+
+    #[test]
+    fn post_game_message_parsing() {
+        assert_eq!(
+            parse_post_game_message(binary(0)).unwrap(),
+            Some(PostGameRequest::Rematch)
+        );
+        assert_eq!(
+            parse_post_game_message(binary(1)).unwrap(),
+            Some(PostGameRequest::Requeue)
+        );
+        assert!(matches!(
+            parse_post_game_message(binary(2)),
+            Err(ClientUpdateError::ProtocolViolation)
+        ));
+        assert_eq!(
+            parse_post_game_message(Some(Ok(Message::Ping(vec![])))).unwrap(),
+            None
+        );
+        assert!(matches!(
+            parse_post_game_message(None),
+            Err(ClientUpdateError::ConnectionLost)
+        ));
+    }
+}
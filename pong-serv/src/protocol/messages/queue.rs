@@ -0,0 +1,90 @@
+//! Protocol-compliant (de)serializable structures to communicate with clients waiting in the match making queue.
+
+use std::time::{Duration, SystemTime};
+
+use crate::protocol::messages::game_start::starting_time_from_system_time;
+
+/// Structure representing the Queue Status Message as introduced in the Protocol Version 14.
+#[derive(Clone)]
+pub struct QueueStatusMessage {
+    pub(in crate::protocol) waited: u64,
+    pub(in crate::protocol) players: u32,
+    pub(in crate::protocol) search_range: u32,
+}
+
+impl QueueStatusMessage {
+    /// Create a new [`QueueStatusMessage`] out of the time the player has waited for, the number of players in the
+    /// queue, and the rating difference it currently accepts.
+    pub fn new(waited: Duration, players: usize, search_range: u32) -> Self {
+        Self {
+            waited: waited.as_secs(),
+            players: u32::try_from(players).unwrap_or(u32::MAX),
+            search_range,
+        }
+    }
+}
+
+/// Structure representing the Match Found Message as introduced in the Protocol Version 15.
+#[derive(Clone)]
+pub struct MatchFoundMessage {
+    pub(in crate::protocol) opponent_username: String,
+    pub(in crate::protocol) deadline: u64,
+}
+
+impl MatchFoundMessage {
+    /// Create a new [`MatchFoundMessage`], by converting the given `deadline` to what is described in the Protocol.
+    pub fn new(opponent_username: &str, deadline: SystemTime) -> Self {
+        Self {
+            opponent_username: String::from(opponent_username),
+            deadline: starting_time_from_system_time(deadline),
+        }
+    }
+}
+
+/// Structure representing the Back In Queue Message as introduced in the Protocol Version 15.
+#[derive(Copy, Clone)]
+pub struct BackInQueueMessage;
+
+/// Parse the answer of a client to the Match Found Message : {ready: bool}.
+pub fn parse_ready_answer(
+    bytes: &[u8],
+) -> Result<bool, ciborium::de::Error<<&'static [u8] as ciborium_io::Read>::Error>> {
+    let (ready,): (bool,) = ciborium::from_reader(bytes)?;
+    Ok(ready)
+}
+
+/// Check whether a message of a client waiting in the queue is a leave queue request : {}.
+pub fn is_leave_queue_request(bytes: &[u8]) -> bool {
+    matches!(ciborium::from_reader::<Vec<ciborium::Value>, _>(bytes), Ok(request) if request.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leave_queue_request_parsing() {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&(), &mut bytes).unwrap();
+        assert!(!is_leave_queue_request(&bytes));
+        bytes.clear();
+        ciborium::into_writer(&Vec::<u8>::new(), &mut bytes).unwrap();
+        assert!(is_leave_queue_request(&bytes));
+        bytes.clear();
+        ciborium::into_writer(&(true,), &mut bytes).unwrap();
+        assert!(!is_leave_queue_request(&bytes));
+    }
+
+    #[test]
+    fn ready_answer_parsing() {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&(true,), &mut bytes).unwrap();
+        assert!(parse_ready_answer(&bytes).unwrap());
+        bytes.clear();
+        ciborium::into_writer(&(false,), &mut bytes).unwrap();
+        assert!(!parse_ready_answer(&bytes).unwrap());
+        bytes.clear();
+        ciborium::into_writer(&Vec::<u8>::new(), &mut bytes).unwrap();
+        assert!(parse_ready_answer(&bytes).is_err());
+    }
+}
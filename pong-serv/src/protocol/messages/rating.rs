@@ -0,0 +1,15 @@
+//! Protocol-compliant serializable structures to tell clients about their rating.
+
+/// Structure representing the Rating Update Message as introduced in the Protocol Version 13.
+#[derive(Clone)]
+pub struct RatingUpdateMessage {
+    pub(in crate::protocol) rating: i32,
+    pub(in crate::protocol) change: i32,
+}
+
+impl RatingUpdateMessage {
+    /// Create a new [`RatingUpdateMessage`] out of the new rating of the player, and of how much it changed.
+    pub fn new(rating: i32, change: i32) -> Self {
+        Self { rating, change }
+    }
+}
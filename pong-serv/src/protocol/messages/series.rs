@@ -0,0 +1,21 @@
+//! Protocol-compliant serializable structures to tell clients about the series they play.
+
+/// Structure representing the Series Score Message as introduced in the Protocol Version 19.
+#[derive(Clone)]
+pub struct SeriesScoreMessage {
+    pub(in crate::protocol) own_wins: u8,
+    pub(in crate::protocol) opponent_wins: u8,
+    pub(in crate::protocol) best_of: u8,
+}
+
+impl SeriesScoreMessage {
+    /// Create a new [`SeriesScoreMessage`] out of the games won by the client and by its opponent, in a series played
+    /// over `best_of` games at most.
+    pub fn new(own_wins: u8, opponent_wins: u8, best_of: u8) -> Self {
+        Self {
+            own_wins,
+            opponent_wins,
+            best_of,
+        }
+    }
+}
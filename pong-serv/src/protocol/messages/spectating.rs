@@ -0,0 +1,42 @@
+//! Protocol-compliant serializable structures to communicate with a spectator.
+
+use crate::game::GameRules;
+use crate::protocol::messages::game_start::RulesField;
+
+/// Structure representing the Live Games Message as introduced in the Protocol Version 12.
+#[derive(Clone)]
+pub struct LiveGamesMessage {
+    pub(in crate::protocol) games: Vec<(u32, String, String)>,
+}
+
+impl LiveGamesMessage {
+    /// Create a new [`LiveGamesMessage`] out of the id and the usernames of the players, left then right, of each
+    /// running game.
+    pub fn new(games: Vec<(u32, String, String)>) -> Self {
+        Self { games }
+    }
+}
+
+/// Structure representing the Spectator Snapshot Message as introduced in the Protocol Version 12.
+#[derive(Clone)]
+pub struct SpectatorSnapshotMessage {
+    pub(in crate::protocol) game_id: u32,
+    pub(in crate::protocol) left_username: String,
+    pub(in crate::protocol) right_username: String,
+    pub(in crate::protocol) left_score: u32,
+    pub(in crate::protocol) right_score: u32,
+    pub(in crate::protocol) rules: RulesField,
+}
+
+impl SpectatorSnapshotMessage {
+    pub fn new(game_id: u32, usernames: &[String; 2], scores: [u32; 2], rules: GameRules) -> Self {
+        Self {
+            game_id,
+            left_username: usernames[0].clone(),
+            right_username: usernames[1].clone(),
+            left_score: scores[0],
+            right_score: scores[1],
+            rules: rules.into(),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Protocol-compliant serializable structures to communicate with a client playing in a tournament, remote or local.
+
+/// Structure representing the Tournament Match Message as introduced in the Protocol Version 10.
+#[derive(Clone)]
+pub struct TournamentMatchMessage {
+    pub(in crate::protocol) round: u8,
+    pub(in crate::protocol) rounds: u8,
+    pub(in crate::protocol) opponent_alias: String,
+}
+
+impl TournamentMatchMessage {
+    pub fn new(round: u8, rounds: u8, opponent_alias: &str) -> Self {
+        Self {
+            round,
+            rounds,
+            opponent_alias: String::from(opponent_alias),
+        }
+    }
+}
+
+/// Structure representing the Local Tournament Match Message as introduced in the Protocol Version 11.
+#[derive(Clone)]
+pub struct LocalTournamentMatchMessage {
+    pub(in crate::protocol) round: u8,
+    pub(in crate::protocol) rounds: u8,
+    pub(in crate::protocol) left_alias: String,
+    pub(in crate::protocol) right_alias: String,
+}
+
+impl LocalTournamentMatchMessage {
+    pub fn new(round: u8, rounds: u8, left_alias: &str, right_alias: &str) -> Self {
+        Self {
+            round,
+            rounds,
+            left_alias: String::from(left_alias),
+            right_alias: String::from(right_alias),
+        }
+    }
+}
+
+/// Structure representing the Local Tournament Winner Message as introduced in the Protocol Version 11.
+#[derive(Clone)]
+pub struct LocalTournamentWinnerMessage {
+    pub(in crate::protocol) winner_alias: String,
+}
+
+impl LocalTournamentWinnerMessage {
+    pub fn new(winner_alias: &str) -> Self {
+        Self {
+            winner_alias: String::from(winner_alias),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Registry of the protocol versions supported by this server.
+//!
+//! Each version has its own decoding of the [`HelloMessage`], its own game mode table and its own serialization of
+//! the [`ServerToClientMessage`]s, implemented in its sub-module. The version requested by a client in its Hello
+//! message is the one spoken for the whole connection. Clients speaking different versions can therefore be served
+//! side by side, and even play together, while a new version is being rolled out.
+//!
+//! A version is frozen once it is served : anything changing what goes over the wire belongs to a new version, whose
+//! module only implements the changes and hands everything else over to the previous version.
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+
+mod v10;
+mod v11;
+mod v12;
+mod v13;
+mod v14;
+mod v15;
+mod v16;
+mod v17;
+mod v18;
+mod v19;
+mod v20;
+mod v3;
+mod v4;
+mod v5;
+mod v6;
+mod v7;
+mod v8;
+mod v9;
+
+/// The versions of the protocol supported by this server, ordered from the oldest to the newest.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ProtocolVersion {
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    V10,
+    V11,
+    V12,
+    V13,
+    V14,
+    V15,
+    V16,
+    V17,
+    V18,
+    V19,
+    V20,
+}
+
+impl ProtocolVersion {
+    /// The newest version supported. It is used to talk to clients whose version is not known or not supported.
+    pub fn latest() -> Self {
+        Self::V20
+    }
+
+    /// Decode a whole [`HelloMessage`], as defined by this version.
+    pub(in crate::protocol) fn decode_hello(
+        self,
+        bytes: &[u8],
+    ) -> Result<HelloMessage, HelloUpdateError> {
+        match self {
+            Self::V3 => v3::decode_hello(bytes),
+            Self::V4 => v4::decode_hello(bytes),
+            Self::V5 => v5::decode_hello(bytes),
+            Self::V6 => v6::decode_hello(bytes),
+            Self::V7 => v7::decode_hello(bytes),
+            Self::V8 => v8::decode_hello(bytes),
+            Self::V9 => v9::decode_hello(bytes),
+            Self::V10 => v10::decode_hello(bytes),
+            Self::V11 => v11::decode_hello(bytes),
+            Self::V12 => v12::decode_hello(bytes),
+            Self::V13 => v13::decode_hello(bytes),
+            Self::V14 => v14::decode_hello(bytes),
+            Self::V15 => v15::decode_hello(bytes),
+            Self::V16 => v16::decode_hello(bytes),
+            Self::V17 => v17::decode_hello(bytes),
+            Self::V18 => v18::decode_hello(bytes),
+            Self::V19 => v19::decode_hello(bytes),
+            Self::V20 => v20::decode_hello(bytes),
+        }
+    }
+
+    /// Serialize a server-to-client message, as defined by this version. Returns [`None`] for a message this version
+    /// doesn't have, which its clients are not sent.
+    pub fn encode<M: Into<ServerToClientMessage>>(self, message: M) -> Option<Vec<u8>> {
+        match self {
+            Self::V3 => v3::encode(message.into()),
+            Self::V4 => v4::encode(message.into()),
+            Self::V5 => v5::encode(message.into()),
+            Self::V6 => v6::encode(message.into()),
+            Self::V7 => v7::encode(message.into()),
+            Self::V8 => v8::encode(message.into()),
+            Self::V9 => v9::encode(message.into()),
+            Self::V10 => v10::encode(message.into()),
+            Self::V11 => v11::encode(message.into()),
+            Self::V12 => v12::encode(message.into()),
+            Self::V13 => v13::encode(message.into()),
+            Self::V14 => v14::encode(message.into()),
+            Self::V15 => v15::encode(message.into()),
+            Self::V16 => v16::encode(message.into()),
+            Self::V17 => v17::encode(message.into()),
+            Self::V18 => v18::encode(message.into()),
+            Self::V19 => v19::encode(message.into()),
+            Self::V20 => v20::encode(message.into()),
+        }
+    }
+
+    /// Whether the clients of this version can resume the games paused by the loss of their connection.
+    pub fn resumes_games(self) -> bool {
+        self >= Self::V5
+    }
+
+    /// Whether the clients of this version have a post-game phase after their remote games.
+    pub fn has_post_game(self) -> bool {
+        self >= Self::V6
+    }
+
+    /// Whether the clients of this version can be challenged by their friends while waiting in the match-making queue.
+    pub fn has_challenges(self) -> bool {
+        self >= Self::V9
+    }
+
+    /// Whether the clients of this version can ask to leave the match-making queue.
+    pub fn leaves_queue(self) -> bool {
+        self >= Self::V14
+    }
+
+    /// Whether the clients of this version confirm they are ready to play the matches found for them.
+    pub fn has_ready_check(self) -> bool {
+        self >= Self::V15
+    }
+
+    /// Whether the clients of this version serve the ball for their side. The ball is served right away for the others.
+    pub fn serves(self) -> bool {
+        self >= Self::V20
+    }
+
+    /// The reason to close the connection of a client of this version with, in place of the given one. The clients of
+    /// the versions before the one session per user have their connection refused as for a failed authentication.
+    pub fn close_reason(self, reason: CloseReason) -> CloseReason {
+        match reason {
+            CloseReason::AlreadyConnected if self < Self::V16 => CloseReason::AuthenticationFailed,
+            reason => reason,
+        }
+    }
+}
+
+/// Errors encountered when making a [`ProtocolVersion`] out of a [`u8`].
+#[derive(thiserror::Error, Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum VersionCastError {
+    #[error("Protocol version {0} is not supported")]
+    Unsupported(u8),
+}
+
+impl TryFrom<u8> for ProtocolVersion {
+    type Error = VersionCastError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            3 => Ok(Self::V3),
+            4 => Ok(Self::V4),
+            5 => Ok(Self::V5),
+            6 => Ok(Self::V6),
+            7 => Ok(Self::V7),
+            8 => Ok(Self::V8),
+            9 => Ok(Self::V9),
+            10 => Ok(Self::V10),
+            11 => Ok(Self::V11),
+            12 => Ok(Self::V12),
+            13 => Ok(Self::V13),
+            14 => Ok(Self::V14),
+            15 => Ok(Self::V15),
+            16 => Ok(Self::V16),
+            17 => Ok(Self::V17),
+            18 => Ok(Self::V18),
+            19 => Ok(Self::V19),
+            20 => Ok(Self::V20),
+            n => Err(Self::Error::Unsupported(n)),
+        }
+    }
+}
+
+impl From<ProtocolVersion> for u8 {
+    fn from(value: ProtocolVersion) -> Self {
+        match value {
+            ProtocolVersion::V3 => 3,
+            ProtocolVersion::V4 => 4,
+            ProtocolVersion::V5 => 5,
+            ProtocolVersion::V6 => 6,
+            ProtocolVersion::V7 => 7,
+            ProtocolVersion::V8 => 8,
+            ProtocolVersion::V9 => 9,
+            ProtocolVersion::V10 => 10,
+            ProtocolVersion::V11 => 11,
+            ProtocolVersion::V12 => 12,
+            ProtocolVersion::V13 => 13,
+            ProtocolVersion::V14 => 14,
+            ProtocolVersion::V15 => 15,
+            ProtocolVersion::V16 => 16,
+            ProtocolVersion::V17 => 17,
+            ProtocolVersion::V18 => 18,
+            ProtocolVersion::V19 => 19,
+            ProtocolVersion::V20 => 20,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_to_u8() {
+        assert_eq!(u8::from(ProtocolVersion::V3), 3u8);
+        assert_eq!(u8::from(ProtocolVersion::V4), 4u8);
+        assert_eq!(u8::from(ProtocolVersion::V5), 5u8);
+        assert_eq!(u8::from(ProtocolVersion::V6), 6u8);
+        assert_eq!(u8::from(ProtocolVersion::V7), 7u8);
+        assert_eq!(u8::from(ProtocolVersion::V8), 8u8);
+        assert_eq!(u8::from(ProtocolVersion::V9), 9u8);
+        assert_eq!(u8::from(ProtocolVersion::V10), 10u8);
+        assert_eq!(u8::from(ProtocolVersion::V11), 11u8);
+        assert_eq!(u8::from(ProtocolVersion::V12), 12u8);
+        assert_eq!(u8::from(ProtocolVersion::V13), 13u8);
+        assert_eq!(u8::from(ProtocolVersion::V14), 14u8);
+        assert_eq!(u8::from(ProtocolVersion::V15), 15u8);
+        assert_eq!(u8::from(ProtocolVersion::V16), 16u8);
+        assert_eq!(u8::from(ProtocolVersion::V17), 17u8);
+        assert_eq!(u8::from(ProtocolVersion::V18), 18u8);
+        assert_eq!(u8::from(ProtocolVersion::V19), 19u8);
+        assert_eq!(u8::from(ProtocolVersion::V20), 20u8);
+    }
+
+    #[test]
+    fn u8_to_version() {
+        assert_eq!(ProtocolVersion::try_from(3u8), Ok(ProtocolVersion::V3));
+        assert_eq!(ProtocolVersion::try_from(4u8), Ok(ProtocolVersion::V4));
+        assert_eq!(ProtocolVersion::try_from(5u8), Ok(ProtocolVersion::V5));
+        assert_eq!(ProtocolVersion::try_from(6u8), Ok(ProtocolVersion::V6));
+        assert_eq!(ProtocolVersion::try_from(7u8), Ok(ProtocolVersion::V7));
+        assert_eq!(ProtocolVersion::try_from(8u8), Ok(ProtocolVersion::V8));
+        assert_eq!(ProtocolVersion::try_from(9u8), Ok(ProtocolVersion::V9));
+        assert_eq!(ProtocolVersion::try_from(10u8), Ok(ProtocolVersion::V10));
+        assert_eq!(ProtocolVersion::try_from(11u8), Ok(ProtocolVersion::V11));
+        assert_eq!(ProtocolVersion::try_from(12u8), Ok(ProtocolVersion::V12));
+        assert_eq!(ProtocolVersion::try_from(13u8), Ok(ProtocolVersion::V13));
+        assert_eq!(ProtocolVersion::try_from(14u8), Ok(ProtocolVersion::V14));
+        assert_eq!(ProtocolVersion::try_from(15u8), Ok(ProtocolVersion::V15));
+        assert_eq!(ProtocolVersion::try_from(16u8), Ok(ProtocolVersion::V16));
+        assert_eq!(ProtocolVersion::try_from(17u8), Ok(ProtocolVersion::V17));
+        assert_eq!(ProtocolVersion::try_from(18u8), Ok(ProtocolVersion::V18));
+        assert_eq!(ProtocolVersion::try_from(19u8), Ok(ProtocolVersion::V19));
+        assert_eq!(ProtocolVersion::try_from(20u8), Ok(ProtocolVersion::V20));
+        assert_eq!(
+            ProtocolVersion::try_from(2u8),
+            Err(VersionCastError::Unsupported(2u8))
+        );
+        assert_eq!(
+            ProtocolVersion::try_from(21u8),
+            Err(VersionCastError::Unsupported(21u8))
+        );
+    }
+}
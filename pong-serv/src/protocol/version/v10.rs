@@ -0,0 +1,72 @@
+//! Protocol Version 10 : single-elimination tournaments run by the server, as game mode 7, along with the tournament
+//! match message and the error code of a user already registered. The rest is the same as in
+//! [version 9](super::v9).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v4, v9, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 9, along with the tournaments.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        7 => Some(GameModes::Tournament),
+        code => v9::game_mode(code),
+    }
+}
+
+/// The error code table of this version : the one of version 9, along with the users already registered.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::AlreadyRegistered => Some(15),
+        reason => v9::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V10, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The tournament match message starts with its message id, the error message
+/// uses the error code table of this version, and the other messages are serialized as in version 9.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::TournamentMatch(m) => {
+            ciborium::into_writer(&(9u8, m.round, m.rounds, m.opponent_alias), &mut bytes)
+        }
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v9::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::ErrorMessage;
+
+    use super::*;
+
+    #[test]
+    fn tournament_game_mode_and_error_code() {
+        assert_eq!(v9::game_mode(7), None);
+        assert_eq!(game_mode(7), Some(GameModes::Tournament));
+        assert_eq!(game_mode(6), Some(GameModes::Lobby));
+
+        let registered = || {
+            ErrorMessage::new(CloseReason::AlreadyRegistered)
+                .unwrap()
+                .into()
+        };
+        assert_eq!(v9::encode(registered()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(registered()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(15)]);
+    }
+}
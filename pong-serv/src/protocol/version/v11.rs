@@ -0,0 +1,59 @@
+//! Protocol Version 11 : local single-elimination tournaments between guests, as game mode 8, along with the local
+//! tournament match and winner messages. The rest is the same as in [version 10](super::v10).
+
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v10, v4, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 10, along with the local tournaments.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        8 => Some(GameModes::LocalTournament),
+        code => v10::game_mode(code),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V11, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The local tournament messages start with their message id, the other
+/// messages are serialized as in version 10.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::LocalTournamentMatch(m) => ciborium::into_writer(
+            &(10u8, m.round, m.rounds, m.left_alias, m.right_alias),
+            &mut bytes,
+        ),
+        ServerToClientMessage::LocalTournamentWinner(m) => {
+            ciborium::into_writer(&(11u8, m.winner_alias), &mut bytes)
+        }
+        message => return v10::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::LocalTournamentWinnerMessage;
+
+    use super::*;
+
+    #[test]
+    fn local_tournament_game_mode_and_winner() {
+        assert_eq!(v10::game_mode(8), None);
+        assert_eq!(game_mode(8), Some(GameModes::LocalTournament));
+        assert_eq!(game_mode(7), Some(GameModes::Tournament));
+
+        let winner = || LocalTournamentWinnerMessage::new("ann").into();
+        assert_eq!(v10::encode(winner()), None);
+        let winner: Vec<Value> =
+            ciborium::from_reader(encode(winner()).unwrap().as_slice()).unwrap();
+        assert_eq!(winner, vec![Value::from(11), Value::from("ann")]);
+    }
+}
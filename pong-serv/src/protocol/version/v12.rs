@@ -0,0 +1,85 @@
+//! Protocol Version 12 : spectating the running remote games, as game mode 9, along with the live games and spectator
+//! snapshot messages, and the error codes of a game not found and of a spectator too slow. The rest is the same as in
+//! [version 11](super::v11).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v10, v11, v4, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 11, along with the spectating.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        9 => Some(GameModes::Spectate),
+        code => v11::game_mode(code),
+    }
+}
+
+/// The error code table of this version : the one of version 10, along with the games not found and the spectators too
+/// slow.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::GameNotFound => Some(16),
+        CloseReason::TooSlow => Some(17),
+        reason => v10::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V12, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The spectating messages start with their message id, the error message uses
+/// the error code table of this version, and the other messages are serialized as in version 11.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::LiveGames(m) => ciborium::into_writer(&(12u8, m.games), &mut bytes),
+        ServerToClientMessage::SpectatorSnapshot(m) => ciborium::into_writer(
+            &(
+                13u8,
+                m.game_id,
+                m.left_username,
+                m.right_username,
+                m.left_score,
+                m.right_score,
+            ),
+            &mut bytes,
+        ),
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v11::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::{ErrorMessage, LiveGamesMessage};
+
+    use super::*;
+
+    #[test]
+    fn spectating_game_mode_and_messages() {
+        assert_eq!(v11::game_mode(9), None);
+        assert_eq!(game_mode(9), Some(GameModes::Spectate));
+        assert_eq!(game_mode(8), Some(GameModes::LocalTournament));
+
+        let live_games = || LiveGamesMessage::new(Vec::new()).into();
+        assert_eq!(v11::encode(live_games()), None);
+        let bytes = encode(live_games()).unwrap();
+        let live_games: Vec<Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(live_games, vec![Value::from(12), Value::Array(Vec::new())]);
+
+        let too_slow = || ErrorMessage::new(CloseReason::TooSlow).unwrap().into();
+        assert_eq!(v11::encode(too_slow()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(too_slow()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(17)]);
+    }
+}
@@ -0,0 +1,45 @@
+//! Protocol Version 13 : the rating update message, sent after rated games. The rest is the same as in
+//! [version 12](super::v12).
+
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v4, ProtocolVersion};
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 12.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V13, v12::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The rating update message starts with its message id, the other messages are
+/// serialized as in version 12.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::RatingUpdate(m) => {
+            ciborium::into_writer(&(14u8, m.rating, m.change), &mut bytes)
+        }
+        message => return v12::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::RatingUpdateMessage;
+
+    use super::*;
+
+    #[test]
+    fn rating_update() {
+        assert_eq!(v12::encode(RatingUpdateMessage::new(1216, 16).into()), None);
+        let bytes = encode(RatingUpdateMessage::new(1216, 16).into()).unwrap();
+        let update: Vec<Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(
+            update,
+            vec![Value::from(14), Value::from(1216), Value::from(16)]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Protocol Version 14 : the queue status message, sent to the players waiting in the match-making queue, who can
+//! leave it with the leave queue message. The rest is the same as in [version 13](super::v13).
+
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v13, v4, ProtocolVersion};
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 12.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V14, v12::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The queue status message starts with its message id, the other messages are
+/// serialized as in version 13.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::QueueStatus(m) => {
+            ciborium::into_writer(&(15u8, m.waited, m.players, m.search_range), &mut bytes)
+        }
+        message => return v13::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ciborium::Value;
+
+    use crate::protocol::QueueStatusMessage;
+
+    use super::*;
+
+    #[test]
+    fn queue_status() {
+        let status = || QueueStatusMessage::new(Duration::from_secs(12), 3, 340).into();
+        assert_eq!(v13::encode(status()), None);
+        let bytes = encode(status()).unwrap();
+        let status: Vec<Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(
+            status,
+            vec![
+                Value::from(15),
+                Value::from(12),
+                Value::from(3),
+                Value::from(340)
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Protocol Version 15 : the ready check of the players paired by the match-making queue, with the match found and back
+//! in queue messages, and the error code of a match not confirmed in time. The rest is the same as in
+//! [version 14](super::v14).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v14, v4, ProtocolVersion};
+
+/// The error code table of this version : the one of version 12, along with the matches not confirmed in time.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::NotReady => Some(18),
+        reason => v12::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 12.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V15, v12::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The ready check messages start with their message id, the error message uses
+/// the error code table of this version, and the other messages are serialized as in version 14.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::MatchFound(m) => {
+            ciborium::into_writer(&(16u8, m.opponent_username, m.deadline), &mut bytes)
+        }
+        ServerToClientMessage::BackInQueue(_) => ciborium::into_writer(&(17u8,), &mut bytes),
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v14::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::{BackInQueueMessage, ErrorMessage};
+
+    use super::*;
+
+    #[test]
+    fn ready_check_messages() {
+        assert_eq!(v14::encode(BackInQueueMessage.into()), None);
+        let bytes = encode(BackInQueueMessage.into()).unwrap();
+        let back_in_queue: Vec<Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(back_in_queue, vec![Value::from(17)]);
+
+        let not_ready = || ErrorMessage::new(CloseReason::NotReady).unwrap().into();
+        assert_eq!(v14::encode(not_ready()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(not_ready()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(18)]);
+    }
+}
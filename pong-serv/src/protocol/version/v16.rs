@@ -0,0 +1,56 @@
+//! Protocol Version 16 : the error code of a user already connected. The rest is the same as in
+//! [version 15](super::v15).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v15, v4, ProtocolVersion};
+
+/// The error code table of this version : the one of version 15, along with the users already connected.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::AlreadyConnected => Some(19),
+        reason => v15::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 12.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V16, v12::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The error message uses the error code table of this version, the other
+/// messages are serialized as in version 15.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v15::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::ErrorMessage;
+
+    use super::*;
+
+    #[test]
+    fn already_connected_error_code() {
+        let connected = || {
+            ErrorMessage::new(CloseReason::AlreadyConnected)
+                .unwrap()
+                .into()
+        };
+        assert_eq!(v15::encode(connected()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(connected()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(19)]);
+    }
+}
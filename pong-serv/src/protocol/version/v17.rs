@@ -0,0 +1,102 @@
+//! Protocol Version 17 : the game start, game resume and spectator snapshot messages carry the rules of the game. The
+//! rest is the same as in [version 16](super::v16).
+
+use crate::protocol::messages::game_start::RulesField;
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v16, v4, ProtocolVersion};
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 12.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V17, v12::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The messages carrying the rules of the game end with them, the others are
+/// serialized as in version 16.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::GameMode0Start(m) => {
+            let rules = rules(m.rules);
+            ciborium::into_writer(
+                &(m.enemy_username, m.side, m.starting_time, rules),
+                &mut bytes,
+            )
+        }
+        ServerToClientMessage::GameMode1Start(m) => {
+            ciborium::into_writer(&(m.starting_time, rules(m.rules)), &mut bytes)
+        }
+        ServerToClientMessage::GameResume(m) => ciborium::into_writer(
+            &(
+                m.enemy_username,
+                m.side,
+                m.left_score,
+                m.right_score,
+                m.l_pad_y,
+                m.r_pad_y,
+                m.ball_x,
+                m.ball_y,
+                m.resuming_time,
+                rules(m.rules),
+            ),
+            &mut bytes,
+        ),
+        ServerToClientMessage::SpectatorSnapshot(m) => ciborium::into_writer(
+            &(
+                13u8,
+                m.game_id,
+                m.left_username,
+                m.right_username,
+                m.left_score,
+                m.right_score,
+                rules(m.rules),
+            ),
+            &mut bytes,
+        ),
+        message => return v16::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+/// Serialize the rules of a game : {target_score: u8, win_by_two: bool, ball_speed: f64, pad_height: f64}.
+fn rules(rules: RulesField) -> (u8, bool, f64, f64) {
+    (
+        rules.target_score,
+        rules.win_by_two,
+        rules.ball_speed,
+        rules.pad_height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use ciborium::Value;
+
+    use crate::game::{GameRules, Side};
+    use crate::protocol::GameMode0StartMessage;
+
+    use super::*;
+
+    fn decode(bytes: Option<Vec<u8>>) -> Vec<Value> {
+        ciborium::from_reader(bytes.unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn game_start_carries_the_rules() {
+        let start = || {
+            GameMode0StartMessage::new(
+                "enemy",
+                Side::Left,
+                SystemTime::UNIX_EPOCH,
+                GameRules::default(),
+            )
+        };
+        assert_eq!(decode(v4::encode(start().into())).len(), 3);
+        let with_rules = decode(encode(start().into()));
+        assert_eq!(with_rules.len(), 4);
+        assert_eq!(with_rules[3].as_array().map(Vec::len), Some(4));
+    }
+}
@@ -0,0 +1,64 @@
+//! Protocol Version 18 : the position update messages of time-limited games carry the time left on the game clock. The
+//! rest is the same as in [version 17](super::v17).
+
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v17, v4, ProtocolVersion};
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 12.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V18, v12::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The position updates of time-limited games end with the remaining time, the
+/// other messages are serialized as in version 17.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::PositionUpdate(m) => match m.remaining_time {
+            Some(remaining_time) => ciborium::into_writer(
+                &(
+                    0u8,
+                    m.l_pad_y,
+                    m.r_pad_y,
+                    m.ball_x,
+                    m.ball_y,
+                    remaining_time,
+                ),
+                &mut bytes,
+            ),
+            None => return v17::encode(m.into()),
+        },
+        message => return v17::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ciborium::Value;
+
+    use crate::protocol::PositionUpdateMessage;
+
+    use super::*;
+
+    fn decode(bytes: Option<Vec<u8>>) -> Vec<Value> {
+        ciborium::from_reader(bytes.unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn position_update_carries_the_remaining_time() {
+        let position_update = decode(encode(
+            PositionUpdateMessage::new(0.1, 0.2, 0.3, 0.4, None).into(),
+        ));
+        assert_eq!(position_update.len(), 5);
+        let timed_update = decode(encode(
+            PositionUpdateMessage::new(0.1, 0.2, 0.3, 0.4, Some(Duration::from_secs(90))).into(),
+        ));
+        assert_eq!(timed_update.len(), 6);
+        assert_eq!(timed_update[5], Value::from(90_000));
+    }
+}
@@ -0,0 +1,66 @@
+//! Protocol Version 19 : best-of series between match-made players, as game mode 10, and the series score message. The
+//! rest is the same as in [version 18](super::v18).
+
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v12, v18, v4, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 12, along with the series.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        10 => Some(GameModes::Series),
+        code => v12::game_mode(code),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V19, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The series score message starts with its message id, the other messages are
+/// serialized as in version 18.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::SeriesScore(m) => {
+            ciborium::into_writer(&(18u8, m.own_wins, m.opponent_wins, m.best_of), &mut bytes)
+        }
+        message => return v18::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::SeriesScoreMessage;
+
+    use super::*;
+
+    #[test]
+    fn series_game_mode_and_score() {
+        assert_eq!(v12::game_mode(10), None);
+        assert_eq!(game_mode(10), Some(GameModes::Series));
+        assert_eq!(game_mode(9), Some(GameModes::Spectate));
+
+        assert_eq!(v4::encode(SeriesScoreMessage::new(1, 0, 3).into()), None);
+        let score: Vec<Value> = ciborium::from_reader(
+            encode(SeriesScoreMessage::new(1, 0, 3).into())
+                .unwrap()
+                .as_slice(),
+        )
+        .unwrap();
+        assert_eq!(
+            score,
+            vec![
+                Value::from(18),
+                Value::from(1),
+                Value::from(0),
+                Value::from(3)
+            ]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Protocol Version 20 : the serving player is told to serve the ball, with the serve message, and may serve it before
+//! the deadline with the serve input. The rest is the same as in [version 19](super::v19).
+
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v19, v4, ProtocolVersion};
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 19.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V20, v19::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The serve message starts with its message id, the other messages are
+/// serialized as in version 19.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::Serve(m) => {
+            ciborium::into_writer(&(19u8, m.side, m.deadline), &mut bytes)
+        }
+        message => return v19::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use ciborium::Value;
+
+    use crate::game::Side;
+    use crate::protocol::ServeMessage;
+
+    use super::*;
+
+    #[test]
+    fn serve_message_starts_with_its_id() {
+        let deadline = SystemTime::UNIX_EPOCH + Duration::from_secs(3);
+        assert_eq!(
+            v19::encode(ServeMessage::new(Side::Left, deadline).into()),
+            None
+        );
+        let serve: Vec<Value> = ciborium::from_reader(
+            encode(ServeMessage::new(Side::Left, deadline).into())
+                .unwrap()
+                .as_slice(),
+        )
+        .unwrap();
+        assert_eq!(
+            serve,
+            vec![Value::from(19), Value::from(0), Value::from(3_000)]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Protocol Version 3 : Hello decoding, game mode table and message serialization.
+
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::ProtocolVersion;
+
+/// The game mode table of this version.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        0 => Some(GameModes::MatchMadeRemote1v1),
+        1 => Some(GameModes::Local1v1),
+        _ => None,
+    }
+}
+
+/// Decode the Hello message : {version: u8, id: text string, game_mode: u8, parameters: byte string}. The username
+/// given as id is kept in place of the token.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    let (version, id, game_mode_code, parameters): (u8, String, u8, Vec<u8>) =
+        ciborium::from_reader(bytes)?;
+    match game_mode(game_mode_code) {
+        Some(game_mode) => Ok(HelloMessage::new(
+            ProtocolVersion::V3,
+            id,
+            game_mode,
+            parameters,
+        )),
+        None => Err(HelloUpdateError::UnsupportedGameMode(
+            version,
+            game_mode_code,
+        )),
+    }
+}
+
+/// Serialize a server-to-client message. Game running messages start with their message id. The messages brought by
+/// later versions, the error message included, don't exist in this one.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::PositionUpdate(m) => {
+            ciborium::into_writer(&(0u8, m.l_pad_y, m.r_pad_y, m.ball_x, m.ball_y), &mut bytes)
+        }
+        ServerToClientMessage::PointScored(m) => ciborium::into_writer(
+            &(1u8, m.side, m.l_pad_y, m.r_pad_y, m.ball_x, m.ball_y),
+            &mut bytes,
+        ),
+        ServerToClientMessage::GameDone(m) => ciborium::into_writer(&(2u8, m.side), &mut bytes),
+        ServerToClientMessage::GameAborted(_) => ciborium::into_writer(&(3u8,), &mut bytes),
+        ServerToClientMessage::GameMode0Start(m) => {
+            ciborium::into_writer(&(m.enemy_username, m.side, m.starting_time), &mut bytes)
+        }
+        ServerToClientMessage::GameMode1Start(m) => {
+            ciborium::into_writer(&(m.starting_time,), &mut bytes)
+        }
+        ServerToClientMessage::GameStartStatus(m) => {
+            ciborium::into_writer(&(m.status,), &mut bytes)
+        }
+        _ => return None,
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::{CloseReason, ErrorMessage};
+
+    use super::*;
+
+    fn encode_fields(fields: Vec<Value>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&Value::Array(fields), &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn username_hello_and_no_error_message() {
+        let hello = decode_hello(&encode_fields(vec![
+            Value::from(3),
+            Value::from("ann"),
+            Value::from(0),
+            Value::Bytes(vec![0x80]),
+        ]))
+        .unwrap();
+        assert_eq!(hello.version, ProtocolVersion::V3);
+        assert_eq!(hello.token, "ann");
+        assert_eq!(hello.game_mode, GameModes::MatchMadeRemote1v1);
+        assert!(matches!(
+            decode_hello(&encode_fields(vec![
+                Value::from(3),
+                Value::from("ann"),
+                Value::from(2),
+                Value::Bytes(vec![0x80]),
+            ])),
+            Err(HelloUpdateError::UnsupportedGameMode(3, 2))
+        ));
+
+        let error = ErrorMessage::new(CloseReason::ProtocolViolation).unwrap();
+        assert_eq!(encode(error.into()), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Protocol Version 4 : the Hello message carries a ticket token instead of the username, and the error message is
+//! sent before closing a connection with an error. The rest is the same as in [version 3](super::v3).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v3, ProtocolVersion};
+
+/// The error code table of this version. The reasons brought by later versions have none, nor has
+/// [`CloseReason::Done`], as it is not an error.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::HelloTimeout => Some(0),
+        CloseReason::AuthenticationFailed => Some(1),
+        CloseReason::UnsupportedVersion => Some(2),
+        CloseReason::UnsupportedGameMode => Some(3),
+        CloseReason::InvalidParameters => Some(4),
+        CloseReason::ProtocolViolation => Some(5),
+        CloseReason::Unresponsive => Some(6),
+        CloseReason::ServerShutdown => Some(7),
+        CloseReason::InternalError => Some(8),
+        _ => None,
+    }
+}
+
+/// Decode the Hello message : {version: u8, token: text string, game_mode: u8, parameters: byte string}, with the
+/// game mode table of version 3.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    decode_hello_as(ProtocolVersion::V4, v3::game_mode, bytes)
+}
+
+/// Decode a Hello message of this structure for a later version, with the game mode table of that version.
+pub(super) fn decode_hello_as(
+    protocol_version: ProtocolVersion,
+    game_mode: fn(u8) -> Option<GameModes>,
+    bytes: &[u8],
+) -> Result<HelloMessage, HelloUpdateError> {
+    let (version, token, game_mode_code, parameters): (u8, String, u8, Vec<u8>) =
+        ciborium::from_reader(bytes)?;
+    match game_mode(game_mode_code) {
+        Some(game_mode) => Ok(HelloMessage::new(
+            protocol_version,
+            token,
+            game_mode,
+            parameters,
+        )),
+        None => Err(HelloUpdateError::UnsupportedGameMode(
+            version,
+            game_mode_code,
+        )),
+    }
+}
+
+/// Serialize a server-to-client message. The error message starts with its message id, the other messages are
+/// serialized as in version 3.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v3::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ciborium::Value;
+
+    use crate::game::Side;
+    use crate::protocol::{
+        CloseReason, ErrorMessage, GameAbortedMessage, GameCompletedMessage, PositionUpdateMessage,
+    };
+
+    use super::*;
+
+    fn decode(bytes: Option<Vec<u8>>) -> Vec<Value> {
+        ciborium::from_reader(bytes.unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn running_messages_start_with_their_id() {
+        let position_update = decode(encode(
+            PositionUpdateMessage::new(0.1, 0.2, 0.3, 0.4, None).into(),
+        ));
+        assert_eq!(
+            position_update,
+            vec![
+                Value::from(0),
+                Value::from(0.1),
+                Value::from(0.2),
+                Value::from(0.3),
+                Value::from(0.4)
+            ]
+        );
+        let timed_update = decode(encode(
+            PositionUpdateMessage::new(0.1, 0.2, 0.3, 0.4, Some(Duration::from_secs(90))).into(),
+        ));
+        assert_eq!(timed_update, position_update);
+        let game_done = decode(encode(GameCompletedMessage::new(Side::Right).into()));
+        assert_eq!(game_done, vec![Value::from(2), Value::from(1)]);
+        let game_aborted = decode(encode(GameAbortedMessage.into()));
+        assert_eq!(game_aborted, vec![Value::from(3)]);
+        let error = decode(encode(
+            ErrorMessage::new(CloseReason::AuthenticationFailed)
+                .unwrap()
+                .into(),
+        ));
+        assert_eq!(error, vec![Value::from(4), Value::from(1)]);
+    }
+}
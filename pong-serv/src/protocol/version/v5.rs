@@ -0,0 +1,86 @@
+//! Protocol Version 5 : paused remote games, resumed with game mode 3, along with the opponent connection and game
+//! resume messages, and the error code of a missing game to resume. The rest is the same as in
+//! [version 4](super::v4).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v3, v4, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 3, along with the resume game mode.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        3 => Some(GameModes::Resume),
+        code => v3::game_mode(code),
+    }
+}
+
+/// The error code table of this version : the one of version 4, along with the missing game to resume.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::NoGameToResume => Some(9),
+        reason => v4::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V5, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The opponent connection message starts with its message id, the error
+/// message uses the error code table of this version, and the other messages are serialized as in version 4.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::OpponentConnection(m) => {
+            ciborium::into_writer(&(5u8, m.status, m.time), &mut bytes)
+        }
+        ServerToClientMessage::GameResume(m) => ciborium::into_writer(
+            &(
+                m.enemy_username,
+                m.side,
+                m.left_score,
+                m.right_score,
+                m.l_pad_y,
+                m.r_pad_y,
+                m.ball_x,
+                m.ball_y,
+                m.resuming_time,
+            ),
+            &mut bytes,
+        ),
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v4::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::ErrorMessage;
+
+    use super::*;
+
+    #[test]
+    fn resume_game_mode_and_error_code() {
+        assert_eq!(v3::game_mode(3), None);
+        assert_eq!(game_mode(3), Some(GameModes::Resume));
+        assert_eq!(game_mode(0), Some(GameModes::MatchMadeRemote1v1));
+
+        let no_game = || {
+            ErrorMessage::new(CloseReason::NoGameToResume)
+                .unwrap()
+                .into()
+        };
+        assert_eq!(v4::encode(no_game()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(no_game()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(9)]);
+    }
+}
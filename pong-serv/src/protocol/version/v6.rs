@@ -0,0 +1,43 @@
+//! Protocol Version 6 : the post-game phase of remote games, with the post-game status message. The rest is the same
+//! as in [version 5](super::v5).
+
+use crate::protocol::messages::hello::{HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v4, v5, ProtocolVersion};
+
+/// Decode the Hello message, with the structure of version 4 and the game mode table of version 5.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V6, v5::game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The post-game status message starts with its message id, the other messages
+/// are serialized as in version 5.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::PostGameStatus(m) => {
+            ciborium::into_writer(&(6u8, m.status), &mut bytes)
+        }
+        message => return v5::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::{PostGameStatus, PostGameStatusMessage};
+
+    use super::*;
+
+    #[test]
+    fn post_game_status() {
+        let status = || PostGameStatusMessage::new(PostGameStatus::OpponentGone).into();
+        assert_eq!(v5::encode(status()), None);
+        let bytes = encode(status()).unwrap();
+        let status: Vec<Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(status, vec![Value::from(6), Value::from(1)]);
+    }
+}
@@ -0,0 +1,36 @@
+//! Protocol Version 7 : games against an AI run by the server, as game mode 4. The rest is the same as in
+//! [version 6](super::v6).
+
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v4, v5, v6, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 5, along with the games against the AI.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        4 => Some(GameModes::AiOpponent),
+        code => v5::game_mode(code),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V7, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message, as in version 6.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    v6::encode(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ai_game_mode() {
+        assert_eq!(v5::game_mode(4), None);
+        assert_eq!(game_mode(4), Some(GameModes::AiOpponent));
+        assert_eq!(game_mode(3), Some(GameModes::Resume));
+    }
+}
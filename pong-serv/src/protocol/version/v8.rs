@@ -0,0 +1,72 @@
+//! Protocol Version 8 : private remote games joined with a code, as game mode 2, along with the invite code message and
+//! the error codes of a private game expiring or not found. The rest is the same as in [version 7](super::v7).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v4, v5, v7, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 7, along with the private games.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        2 => Some(GameModes::PrivateRemote1v1),
+        code => v7::game_mode(code),
+    }
+}
+
+/// The error code table of this version : the one of version 5, along with the private games expiring or not found.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::InviteExpired => Some(10),
+        CloseReason::UnknownInviteCode => Some(11),
+        reason => v5::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V8, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The invite code message starts with its message id, the error message uses
+/// the error code table of this version, and the other messages are serialized as in version 7.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::InviteCode(m) => {
+            ciborium::into_writer(&(7u8, m.code, m.expiration_time), &mut bytes)
+        }
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v7::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::ErrorMessage;
+
+    use super::*;
+
+    #[test]
+    fn private_game_mode_and_error_codes() {
+        assert_eq!(v7::game_mode(2), None);
+        assert_eq!(game_mode(2), Some(GameModes::PrivateRemote1v1));
+        assert_eq!(game_mode(4), Some(GameModes::AiOpponent));
+
+        let expired = || {
+            ErrorMessage::new(CloseReason::InviteExpired)
+                .unwrap()
+                .into()
+        };
+        assert_eq!(v7::encode(expired()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(expired()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(10)]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Protocol Version 9 : friend challenges, as game mode 5, and the lobby waiting for them, as game mode 6, along with
+//! the challenge message and the error codes of a failed challenge. The rest is the same as in [version 8](super::v8).
+
+use crate::protocol::messages::error::CloseReason;
+use crate::protocol::messages::hello::{GameModes, HelloMessage, HelloUpdateError};
+use crate::protocol::messages::ServerToClientMessage;
+use crate::protocol::version::{v4, v8, ProtocolVersion};
+
+/// The game mode table of this version : the one of version 8, along with the friend challenges and the lobby.
+pub(super) fn game_mode(code: u8) -> Option<GameModes> {
+    match code {
+        5 => Some(GameModes::FriendChallenge),
+        6 => Some(GameModes::Lobby),
+        code => v8::game_mode(code),
+    }
+}
+
+/// The error code table of this version : the one of version 8, along with the failed challenges.
+pub(super) fn error_code(reason: CloseReason) -> Option<u8> {
+    match reason {
+        CloseReason::NotFriends => Some(12),
+        CloseReason::OpponentUnavailable => Some(13),
+        CloseReason::ChallengeDeclined => Some(14),
+        reason => v8::error_code(reason),
+    }
+}
+
+/// Decode the Hello message, with the structure of version 4.
+pub(super) fn decode_hello(bytes: &[u8]) -> Result<HelloMessage, HelloUpdateError> {
+    v4::decode_hello_as(ProtocolVersion::V9, game_mode, bytes)
+}
+
+/// Serialize a server-to-client message. The challenge message starts with its message id, the error message uses the
+/// error code table of this version, and the other messages are serialized as in version 8.
+pub(super) fn encode(message: ServerToClientMessage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match message {
+        ServerToClientMessage::Challenge(m) => {
+            ciborium::into_writer(&(8u8, m.status, m.challenger_username), &mut bytes)
+        }
+        ServerToClientMessage::Error(m) => {
+            ciborium::into_writer(&(4u8, error_code(m.reason)?), &mut bytes)
+        }
+        message => return v8::encode(message),
+    }
+    .expect("Could not serialize a server-to-client message.");
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ciborium::Value;
+
+    use crate::protocol::{ChallengeMessage, ChallengeStatus, ErrorMessage};
+
+    use super::*;
+
+    #[test]
+    fn challenge_game_modes_and_messages() {
+        assert_eq!(v8::game_mode(5), None);
+        assert_eq!(game_mode(5), Some(GameModes::FriendChallenge));
+        assert_eq!(game_mode(6), Some(GameModes::Lobby));
+        assert_eq!(game_mode(2), Some(GameModes::PrivateRemote1v1));
+
+        let challenge = || ChallengeMessage::new(ChallengeStatus::Challenged, "ann").into();
+        assert_eq!(v8::encode(challenge()), None);
+        let bytes = encode(challenge()).unwrap();
+        let challenge: Vec<Value> = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(
+            challenge,
+            vec![Value::from(8), Value::from(0), Value::from("ann")]
+        );
+
+        let declined = || {
+            ErrorMessage::new(CloseReason::ChallengeDeclined)
+                .unwrap()
+                .into()
+        };
+        assert_eq!(v8::encode(declined()), None);
+        let error: Vec<Value> =
+            ciborium::from_reader(encode(declined()).unwrap().as_slice()).unwrap();
+        assert_eq!(error, vec![Value::from(4), Value::from(14)]);
+    }
+}
@@ -0,0 +1,21 @@
+//! Elo ratings of the registered players.
+//!
+//! Every player starts with the rating given by the website. Remote games between two players are rated : the winner
+//! takes `K_FACTOR * (1 - E)` points from the loser, `E` being its expected score, `1 / (1 + 10^(-D / 400))` for a
+//! rating lead of `D`. Beating a stronger opponent is thus worth more than beating a weaker one. The ratings are
+//! updated along with the writing of the game result, and are used by the match-making to pair players of close
+//! strength.
+
+use std::sync::Arc;
+
+/// The most points a player can win or lose in a single game.
+pub const K_FACTOR: i32 = 32;
+
+/// Get the rating of the player.
+pub async fn fetch_rating(
+    db_client: &Arc<tokio_postgres::Client>,
+    username: &str,
+) -> Result<i32, tokio_postgres::Error> {
+    let query = "select rating from account_player where username = $1;";
+    Ok(db_client.query_one(query, &[&username]).await?.get(0))
+}
@@ -0,0 +1,215 @@
+//! One session per account : the registry of the connections of the authenticated users.
+//!
+//! Every connection is wrapped in a [`SessionStream`] as it is accepted. Once its user is authenticated, the
+//! [`SessionHandle`] of the stream is registered in the server-wide [`Sessions`], which enforce its [`SessionPolicy`]
+//! against any other connection of the same user. A session lasts as long as its stream and handle, wherever the
+//! connection was handed over to, and ends without deregistering when they are dropped, or when it is ended through its
+//! handle.
+//!
+//! A replaced connection is severed : its reads and writes fail from then on, so that whatever task holds it handles it
+//! as a lost connection.
+
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+
+use clap::ValueEnum;
+use futures_util::task::AtomicWaker;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// What to do when a user opens a connection while it already has one.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum SessionPolicy {
+    /// Keep the existing connection, and reject the new one.
+    #[default]
+    Reject,
+    /// Sever the existing connection, and keep the new one.
+    Replace,
+}
+
+/// The new connection was rejected, as the user already has one.
+#[derive(thiserror::Error, Debug)]
+#[error("The user already has a connection")]
+pub struct AlreadyConnected;
+
+/// A server-wide structure, shared in the [`ServerContext`], holding the active session of each authenticated user.
+///
+/// [`ServerContext`]: crate::protocol::ServerContext
+pub struct Sessions {
+    policy: SessionPolicy,
+    active: Mutex<HashMap<String, Weak<Link>>>,
+}
+
+impl Sessions {
+    /// Creates a new empty [`Sessions`] instance, enforcing the given policy.
+    pub fn new(policy: SessionPolicy) -> Self {
+        Self {
+            policy,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Open a session for the user on the connection of the handle. If the user already has an active session, it is
+    /// either kept and [`AlreadyConnected`] is returned, or severed, depending on the [`SessionPolicy`].
+    pub fn open(&self, username: &str, session: &SessionHandle) -> Result<(), AlreadyConnected> {
+        // The lock cannot panic as nothing in the guard's scope can panic.
+        let mut active = self.active.lock().unwrap();
+        active.retain(|_, link| link.upgrade().is_some_and(|link| !link.is_severed()));
+        if let Some(link) = active.get(username).and_then(Weak::upgrade) {
+            match self.policy {
+                SessionPolicy::Reject => return Err(AlreadyConnected),
+                SessionPolicy::Replace => link.sever(),
+            }
+        }
+        active.insert(String::from(username), Arc::downgrade(&session.0));
+        Ok(())
+    }
+}
+
+/// The shared state of a [`SessionStream`], through which it is severed.
+#[derive(Debug, Default)]
+struct Link {
+    severed: AtomicBool,
+    read_waker: AtomicWaker,
+    write_waker: AtomicWaker,
+}
+
+impl Link {
+    fn is_severed(&self) -> bool {
+        self.severed.load(Ordering::Acquire)
+    }
+
+    /// Make the reads and writes of the stream fail, waking the tasks waiting on them.
+    fn sever(&self) {
+        self.severed.store(true, Ordering::Release);
+        self.read_waker.wake();
+        self.write_waker.wake();
+    }
+}
+
+/// Handle on a [`SessionStream`], to register it in the [`Sessions`] once its user is authenticated.
+#[derive(Debug)]
+pub struct SessionHandle(Arc<Link>);
+
+impl SessionHandle {
+    /// End the session, severing its stream, so that the user can open another one whatever the [`SessionPolicy`].
+    pub fn end(&self) {
+        self.0.sever();
+    }
+}
+
+/// A connection stream, which the [`Sessions`] can sever if its user opens another connection.
+pub struct SessionStream<S> {
+    inner: S,
+    link: Arc<Link>,
+}
+
+impl<S> SessionStream<S> {
+    /// Wrap the stream, returning the handle to register it with.
+    pub fn new(inner: S) -> (Self, SessionHandle) {
+        let link = Arc::new(Link::default());
+        let handle = SessionHandle(link.clone());
+        (Self { inner, link }, handle)
+    }
+}
+
+/// The error the operations on a severed stream fail with.
+fn severed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "The session was replaced by another connection",
+    )
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for SessionStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.link.read_waker.register(cx.waker());
+        if this.link.is_severed() {
+            return Poll::Ready(Err(severed()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for SessionStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.link.write_waker.register(cx.waker());
+        if this.link.is_severed() {
+            return Poll::Ready(Err(severed()));
+        }
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.link.write_waker.register(cx.waker());
+        if this.link.is_severed() {
+            return Poll::Ready(Err(severed()));
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    // The underlying connection is still shut down properly once severed.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejecting_policy() {
+        let sessions = Sessions::new(SessionPolicy::Reject);
+        let (first, first_handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &first_handle).is_ok());
+        let (_, handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &handle).is_err());
+        let (bob, handle) = SessionStream::new(());
+        assert!(sessions.open("bob", &handle).is_ok());
+        // The session ends with its stream and handle.
+        drop((first, first_handle));
+        let (_, handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &handle).is_ok());
+        assert!(!bob.link.is_severed());
+    }
+
+    #[test]
+    fn replacing_policy() {
+        let sessions = Sessions::new(SessionPolicy::Replace);
+        let (first, handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &handle).is_ok());
+        let (second, handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &handle).is_ok());
+        assert!(first.link.is_severed());
+        assert!(!second.link.is_severed());
+    }
+
+    #[test]
+    fn ended_session() {
+        let sessions = Sessions::new(SessionPolicy::Reject);
+        let (_first, first_handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &first_handle).is_ok());
+        // The game of the user paused for its reconnection, while its connection is still held.
+        first_handle.end();
+        let (second, handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &handle).is_ok());
+        assert!(!second.link.is_severed());
+        let (_, handle) = SessionStream::new(());
+        assert!(sessions.open("ann", &handle).is_err());
+    }
+}
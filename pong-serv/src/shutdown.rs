@@ -0,0 +1,21 @@
+//! Server-wide notification of the shutdown, so that connections can be closed properly before the tasks stop.
+
+use tokio::sync::watch;
+
+/// Receiving end of the shutdown notification. Every task handling a connection holds one.
+#[derive(Clone, Debug)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Create the notification channel. Sending `true` on the returned [`watch::Sender`] notifies all the
+    /// [`Shutdown`]s cloned from the returned one.
+    pub fn channel() -> (watch::Sender<bool>, Shutdown) {
+        let (sender, receiver) = watch::channel(false);
+        (sender, Shutdown(receiver))
+    }
+
+    /// Complete once the server is shutting down. A dropped sender is considered a shutdown.
+    pub async fn notified(&mut self) {
+        let _: Result<_, _> = self.0.wait_for(|shutting_down| *shutting_down).await;
+    }
+}
@@ -0,0 +1,401 @@
+//! Server-run single-elimination tournaments.
+//!
+//! Once the entrants of a tournament are gathered in a same task by [`register_for_tournament`], they are seeded at
+//! random into a bracket. The matches of a round are played at the same time, each as a game mode 0 game without
+//! post-game phase played with the tournament rules, and their winners move on to the next round. An entrant that left
+//! before its match forfeits it. Entrants are told about their upcoming matches with the tournament alias of their
+//! opponent, and the bracket and final standings are written to the database. This is implemented in
+//! [`run_tournament`].
+//!
+//! Local tournaments, between players sharing a keyboard, are run the same way over a single connection. This is
+//! implemented in [`run_local_tournament`].
+//!
+//! [`register_for_tournament`]: crate::match_making::register_for_tournament
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use futures_util::future::join_all;
+use rand::seq::SliceRandom;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_postgres::types::ToSql;
+
+use crate::game::{
+    play_tournament_match, GameRules, LiveGames, Player, PlayingError, Reconnections, Side,
+};
+use crate::match_making::Registrant;
+use crate::protocol::{CloseReason, TournamentMatchMessage};
+
+pub use local::run_local_tournament;
+
+mod bracket;
+mod local;
+
+/// An entrant of a running tournament. Its player is [`None`] if it left before its next match.
+struct Entrant<S> {
+    id: String,
+    alias: String,
+    player: Option<Player<S>>,
+}
+
+/// Outcome of a match of the bracket.
+struct MatchOutcome<S> {
+    /// The ids of the entrants meeting in this match, left then right.
+    entrant_ids: [Option<String>; 2],
+    /// The entrant moving on to the next round, if any is left.
+    winner: Option<Entrant<S>>,
+    /// The id of the game result, if the match was played rather than forfeited.
+    result_id: Option<i64>,
+}
+
+/// Run a single-elimination tournament between the registrants, until one of them wins it. Its matches are played with
+/// the given rules.
+///
+/// The losers' connections are closed after their match, and the winner's once the tournament is over. If a database
+/// error happens or the server starts shutting down, the tournament is interrupted and the remaining connections are
+/// closed.
+pub async fn run_tournament<S, D>(
+    mut registrants: Vec<Registrant<S>>,
+    rules: GameRules,
+    db_client: &Arc<tokio_postgres::Client>,
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+    log_id: &D,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    let entrant_count = registrants.len();
+    let rounds = bracket::round_count(entrant_count);
+    registrants.shuffle(&mut rand::thread_rng());
+    let ids: Vec<&str> = registrants.iter().map(|r| r.id.as_str()).collect();
+    let setup = match fetch_aliases(db_client, &ids).await {
+        Ok(aliases) => write_tournament_start(db_client, entrant_count)
+            .await
+            .map(|tournament_id| (aliases, tournament_id)),
+        Err(e) => Err(e),
+    };
+    let (aliases, tournament_id) = match setup {
+        Ok(setup) => setup,
+        Err(e) => {
+            log::error!("{log_id}: Database error while setting the tournament up : {e}.");
+            let players = registrants.into_iter().filter_map(|r| r.player);
+            close_all(players, CloseReason::InternalError).await;
+            return;
+        }
+    };
+    log::info!("{log_id}: Tournament {tournament_id} of {entrant_count} entrants starting.");
+    let alias_of = |id: &str| aliases.get(id).cloned().unwrap_or_else(|| String::from(id));
+    let mut slots = bracket::seed(
+        registrants
+            .into_iter()
+            .map(|Registrant { id, player }| {
+                let alias = alias_of(&id);
+                Entrant { id, alias, player }
+            })
+            .collect(),
+    );
+
+    let mut standings = Vec::with_capacity(entrant_count);
+    for round in 1..=rounds {
+        let mut pairs = bracket::pair_up(slots);
+        for (left, right) in &mut pairs {
+            if let (Some(left), Some(right)) = (left, right) {
+                announce_match(left, right, round, rounds).await;
+            }
+        }
+        log::trace!("{log_id}: Playing round {round} of the tournament.");
+        let outcomes = join_all(
+            pairs
+                .into_iter()
+                .enumerate()
+                .map(|(position, (left, right))| {
+                    play_bracket_match(
+                        left,
+                        right,
+                        rules,
+                        db_client,
+                        reconnections,
+                        live_games,
+                        log_id,
+                        round,
+                        position,
+                    )
+                }),
+        )
+        .await;
+
+        let mut interruption = None;
+        let mut winners = Vec::with_capacity(outcomes.len());
+        for (position, outcome) in outcomes.into_iter().enumerate() {
+            let outcome = match outcome {
+                Ok(outcome) => outcome,
+                Err(reason) => {
+                    interruption.get_or_insert(reason);
+                    continue;
+                }
+            };
+            if interruption.is_none() {
+                if let Err(e) =
+                    write_match(db_client, tournament_id, round, position, &outcome).await
+                {
+                    log::error!("{log_id}: Database error while recording a match : {e}.");
+                    interruption = Some(CloseReason::InternalError);
+                }
+            }
+            let winner_id = outcome.winner.as_ref().map(|winner| winner.id.as_str());
+            for loser_id in outcome.entrant_ids.iter().flatten() {
+                if Some(loser_id.as_str()) != winner_id {
+                    standings.push((
+                        loser_id.clone(),
+                        alias_of(loser_id),
+                        bracket::eliminated_rank(entrant_count, round),
+                    ));
+                }
+            }
+            winners.push(outcome.winner);
+        }
+        if let Some(reason) = interruption {
+            log::info!("{log_id}: Tournament {tournament_id} interrupted during round {round}.");
+            let players = winners.into_iter().flatten().filter_map(|e| e.player);
+            close_all(players, reason).await;
+            return;
+        }
+        slots = winners;
+    }
+
+    // Only the winner of the final is left.
+    let champion = slots.pop().flatten();
+    if let Some(champion) = &champion {
+        standings.push((champion.id.clone(), champion.alias.clone(), 1));
+    }
+    let champion_id = champion.as_ref().map(|champion| champion.id.as_str());
+    if let Err(e) = write_tournament_end(db_client, tournament_id, champion_id, &standings).await {
+        log::error!("{log_id}: Database error while recording the standings : {e}.");
+    }
+    log::info!("{log_id}: Tournament {tournament_id} is over.");
+    if let Some(Entrant {
+        player: Some(mut player),
+        ..
+    }) = champion
+    {
+        player.close(CloseReason::Done).await;
+    }
+}
+
+/// Play a match of the bracket with the given rules, in the given round and position. An entrant without a player, or
+/// without an opponent, moves on by forfeit. So does the remaining player if the game startup fails.
+///
+/// On a database error or the server shutting down, the players have been closed, and the reason to close the other
+/// entrants of the tournament with is returned.
+#[allow(clippy::too_many_arguments)]
+async fn play_bracket_match<S, D>(
+    left: Option<Entrant<S>>,
+    right: Option<Entrant<S>>,
+    rules: GameRules,
+    db_client: &Arc<tokio_postgres::Client>,
+    reconnections: &Reconnections<S>,
+    live_games: &LiveGames,
+    log_id: &D,
+    round: u8,
+    position: usize,
+) -> Result<MatchOutcome<S>, CloseReason>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    let entrant_ids = [
+        left.as_ref().map(|entrant| entrant.id.clone()),
+        right.as_ref().map(|entrant| entrant.id.clone()),
+    ];
+    let (mut left, mut right) = match (left, right) {
+        (Some(left), Some(right)) if left.player.is_some() && right.player.is_some() => {
+            (left, right)
+        }
+        (left, right) => {
+            let winner = [left, right]
+                .into_iter()
+                .flatten()
+                .find(|entrant| entrant.player.is_some());
+            return Ok(MatchOutcome {
+                entrant_ids,
+                winner,
+                result_id: None,
+            });
+        }
+    };
+    log::trace!(
+        "{log_id}: [Round {round}, match {position}] {} against {}.",
+        left.id,
+        right.id
+    );
+    // Both players were just checked to be there.
+    let (left_player, right_player) = (left.player.take().unwrap(), right.player.take().unwrap());
+    match play_tournament_match(
+        left_player,
+        right_player,
+        rules,
+        db_client,
+        reconnections,
+        live_games,
+    )
+    .await
+    {
+        Ok((result_id, winner_side, pl, pr)) => {
+            let (mut winner, winner_player, mut loser_player) = match winner_side {
+                Side::Left => (left, pl, pr),
+                Side::Right => (right, pr, pl),
+            };
+            loser_player.close(CloseReason::Done).await;
+            winner.player = Some(winner_player);
+            Ok(MatchOutcome {
+                entrant_ids,
+                winner: Some(winner),
+                result_id: Some(result_id),
+            })
+        }
+        Err(PlayingError::ClientError(e, remaining_player)) => {
+            log::info!(
+                "{log_id}: [Round {round}, match {position}] Game startup failed, forfeit : {e}."
+            );
+            let mut winner = if remaining_player.id() == left.id {
+                left
+            } else {
+                right
+            };
+            winner.player = Some(remaining_player);
+            Ok(MatchOutcome {
+                entrant_ids,
+                winner: Some(winner),
+                result_id: None,
+            })
+        }
+        Err(PlayingError::ConnectionLost(e)) => {
+            log::info!("{log_id}: [Round {round}, match {position}] Game startup failed : {e}.");
+            Ok(MatchOutcome {
+                entrant_ids,
+                winner: None,
+                result_id: None,
+            })
+        }
+        Err(PlayingError::DatabaseError(e)) => {
+            log::error!("{log_id}: Database error during a tournament match : {e}.");
+            Err(CloseReason::InternalError)
+        }
+        Err(PlayingError::ServerShutdown) => Err(CloseReason::ServerShutdown),
+    }
+}
+
+/// Tell both players about their upcoming match, with the alias of their opponent. A failure to send is ignored : it
+/// will be detected when the game starts up.
+async fn announce_match<S>(left: &mut Entrant<S>, right: &mut Entrant<S>, round: u8, rounds: u8)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let pairings = [
+        (&mut left.player, &right.alias),
+        (&mut right.player, &left.alias),
+    ];
+    for (player, opponent_alias) in pairings {
+        if let Some(player) = player {
+            let message = TournamentMatchMessage::new(round, rounds, opponent_alias);
+            let _: Result<_, _> = player.send(message).await;
+        }
+    }
+}
+
+/// End the players' connections, telling them why.
+async fn close_all<S>(players: impl IntoIterator<Item = Player<S>>, reason: CloseReason)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    for mut player in players {
+        player.close(reason).await;
+    }
+}
+
+/// Get the tournament alias of each of the users, defaulting to their username if they haven't set any.
+async fn fetch_aliases(
+    db_client: &Arc<tokio_postgres::Client>,
+    usernames: &[&str],
+) -> Result<HashMap<String, String>, tokio_postgres::Error> {
+    let query = "select username, coalesce(nullif(tournament_username, ''), username) \
+                 from account_player where username = any($1);";
+    let rows = db_client.query(query, &[&usernames]).await?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Record the start of a tournament, returning its id.
+async fn write_tournament_start(
+    db_client: &Arc<tokio_postgres::Client>,
+    entrants: usize,
+) -> Result<i64, tokio_postgres::Error> {
+    let query = "insert into account_tournament(entrants, start_date) values($1, $2) returning id;";
+    let parameters: [&(dyn ToSql + Sync); 2] = [
+        &i16::try_from(entrants).expect("Entrants are beyond an i16."),
+        &SystemTime::now(),
+    ];
+    Ok(db_client.query_one(query, &parameters).await?.get(0))
+}
+
+/// Record a match of the bracket. Entrants and winner missing from the outcome are recorded as null, and so is the game
+/// result of a forfeited match.
+async fn write_match<S>(
+    db_client: &Arc<tokio_postgres::Client>,
+    tournament_id: i64,
+    round: u8,
+    position: usize,
+    outcome: &MatchOutcome<S>,
+) -> Result<(), tokio_postgres::Error> {
+    let query = "insert into account_tournamentmatch(tournament_id, round, position, p1_id, p2_id, winner_id, \
+                                                     result_id) \
+                 values($1, $2, $3, \
+                        (select id from account_player where username = $4), \
+                        (select id from account_player where username = $5), \
+                        (select id from account_player where username = $6), \
+                        $7);";
+    let [p1_id, p2_id] = &outcome.entrant_ids;
+    let winner_id = outcome.winner.as_ref().map(|winner| winner.id.as_str());
+    let parameters: [&(dyn ToSql + Sync); 7] = [
+        &tournament_id,
+        &i16::from(round),
+        &i16::try_from(position).expect("Position is beyond an i16."),
+        p1_id,
+        p2_id,
+        &winner_id,
+        &outcome.result_id,
+    ];
+    db_client.execute(query, &parameters).await.map(|_| ())
+}
+
+/// Record the end of a tournament, with its winner if any is left, and the final standings of its entrants.
+async fn write_tournament_end(
+    db_client: &Arc<tokio_postgres::Client>,
+    tournament_id: i64,
+    champion_id: Option<&str>,
+    standings: &[(String, String, u16)],
+) -> Result<(), tokio_postgres::Error> {
+    let query = "update account_tournament \
+                 set end_date = $2, winner_id = (select id from account_player where username = $3) \
+                 where id = $1;";
+    let parameters: [&(dyn ToSql + Sync); 3] = [&tournament_id, &SystemTime::now(), &champion_id];
+    db_client.execute(query, &parameters).await?;
+
+    let query = "insert into account_tournamentstanding(tournament_id, player_id, alias, rank) \
+                 select $1, account_player.id, standing.alias, standing.rank \
+                 from unnest($2::text[], $3::text[], $4::smallint[]) as standing(username, alias, rank) \
+                 join account_player on account_player.username = standing.username;";
+    let usernames: Vec<&str> = standings.iter().map(|(id, _, _)| id.as_str()).collect();
+    let aliases: Vec<&str> = standings
+        .iter()
+        .map(|(_, alias, _)| alias.as_str())
+        .collect();
+    let ranks: Vec<i16> = standings
+        .iter()
+        .map(|&(_, _, rank)| i16::try_from(rank).expect("Rank is beyond an i16."))
+        .collect();
+    let parameters: [&(dyn ToSql + Sync); 4] = [&tournament_id, &usernames, &aliases, &ranks];
+    db_client.execute(query, &parameters).await.map(|_| ())
+}
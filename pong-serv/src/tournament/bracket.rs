@@ -0,0 +1,77 @@
+//! Shape of a single-elimination bracket, whose number of slots is a power of two.
+
+/// Number of rounds needed to get a single winner out of the given number of slots.
+pub(super) fn round_count(slots: usize) -> u8 {
+    slots.ilog2() as u8
+}
+
+/// Place the entrants in the slots of a bracket, in order. If they don't fill it, the first entrants get a bye : they
+/// are left without an opponent in the first round.
+pub(super) fn seed<T>(entrants: Vec<T>) -> Vec<Option<T>> {
+    let slot_count = entrants.len().next_power_of_two();
+    let mut byes = slot_count - entrants.len();
+    let mut slots = Vec::with_capacity(slot_count);
+    for entrant in entrants {
+        slots.push(Some(entrant));
+        if byes > 0 {
+            slots.push(None);
+            byes -= 1;
+        }
+    }
+    slots
+}
+
+/// Pair the entrants of a round, in bracket order : the first two play together, then the next two, and so on. The
+/// winners of two neighbouring matches meet in the next round.
+pub(super) fn pair_up<T>(slots: Vec<T>) -> Vec<(T, T)> {
+    let mut slots = slots.into_iter();
+    let mut pairs = Vec::with_capacity(slots.len() / 2);
+    while let (Some(left), Some(right)) = (slots.next(), slots.next()) {
+        pairs.push((left, right));
+    }
+    pairs
+}
+
+/// Final rank of the entrants eliminated in the given round, counted from 1. Entrants eliminated in a same round share
+/// their rank : out of 8 entrants, the loser of the final is 2nd, those of the semi-finals 3rd, and those of the
+/// quarter-finals 5th.
+pub(super) fn eliminated_rank(entrants: usize, round: u8) -> u16 {
+    let remaining = entrants >> (round - 1);
+    (remaining / 2 + 1) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bracket_shape() {
+        assert_eq!(round_count(4), 2);
+        assert_eq!(round_count(16), 4);
+        assert_eq!(pair_up(vec![1, 2, 3, 4]), vec![(1, 2), (3, 4)]);
+        assert_eq!(
+            seed(vec![1, 2, 3, 4]),
+            vec![Some(1), Some(2), Some(3), Some(4)]
+        );
+        assert_eq!(
+            seed(vec![1, 2, 3, 4, 5]),
+            vec![
+                Some(1),
+                None,
+                Some(2),
+                None,
+                Some(3),
+                None,
+                Some(4),
+                Some(5)
+            ]
+        );
+        assert_eq!(
+            (1..=3)
+                .map(|round| eliminated_rank(8, round))
+                .collect::<Vec<_>>(),
+            vec![5, 3, 2]
+        );
+        assert_eq!(eliminated_rank(16, 1), 9);
+    }
+}
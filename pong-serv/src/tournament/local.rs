@@ -0,0 +1,70 @@
+//! Local tournaments, between players sharing the keyboard of a single client.
+
+use std::fmt::Display;
+
+use rand::seq::SliceRandom;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::game::{play_local_match, GameRules, Player, PlayingError, Side};
+use crate::protocol::{LocalTournamentMatchMessage, LocalTournamentWinnerMessage};
+use crate::tournament::bracket;
+
+/// Run a single-elimination tournament between the players going by the given aliases, on the [`Player`]'s
+/// connection, with the given rules. The aliases are seeded at random into the bracket, the first ones getting a bye if
+/// there are not enough of them to fill it.
+///
+/// Each pairing is announced before its local game is played. Once the final is over, the winner is announced. The
+/// player is returned for further playing if no error occurred, the tournament being cut short if the client left.
+pub async fn run_local_tournament<S, D>(
+    mut player: Player<S>,
+    mut aliases: Vec<String>,
+    rules: GameRules,
+    log_id: &D,
+) -> Result<Player<S>, PlayingError<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: Display,
+{
+    aliases.shuffle(&mut rand::thread_rng());
+    let mut slots = bracket::seed(aliases);
+    let rounds = bracket::round_count(slots.len());
+    for round in 1..=rounds {
+        let mut winners = Vec::with_capacity(slots.len() / 2);
+        for (left, right) in bracket::pair_up(slots) {
+            let (left, right) = match (left, right) {
+                (Some(left), Some(right)) => (left, right),
+                // A player without an opponent moves on.
+                (left, right) => {
+                    winners.push(left.or(right));
+                    continue;
+                }
+            };
+            log::trace!("{log_id}: [Round {round}] {left} against {right}.");
+            let message = LocalTournamentMatchMessage::new(round, rounds, &left, &right);
+            if player.send(message).await.is_err() {
+                log::info!("{log_id}: The client left the local tournament.");
+                return Ok(player);
+            }
+            let winner_side;
+            (winner_side, player) = play_local_match(player, rules).await?;
+            let winner = match winner_side {
+                Some(Side::Left) => left,
+                Some(Side::Right) => right,
+                None => {
+                    log::info!("{log_id}: The client left the local tournament.");
+                    return Ok(player);
+                }
+            };
+            winners.push(Some(winner));
+        }
+        slots = winners;
+    }
+
+    // Every match has a winner, the last slot can't be empty.
+    let winner = slots.pop().flatten().unwrap();
+    log::trace!("{log_id}: {winner} won the local tournament.");
+    let _: Result<_, _> = player
+        .send(LocalTournamentWinnerMessage::new(&winner))
+        .await;
+    Ok(player)
+}
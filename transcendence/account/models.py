@@ -5,11 +5,23 @@ class Player(models.Model):
     username = models.CharField(max_length=64)
     password = models.BinaryField()
     tournament_username = models.CharField(max_length=64, default='')
+    rating = models.IntegerField(default=1500)
 
     def __str__(self):
         return self.username
 
 
+class GameSeries(models.Model):
+    p1 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="series_p1")
+    p2 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="series_p2")
+    best_of = models.PositiveSmallIntegerField()
+    p1_wins = models.PositiveSmallIntegerField(default=0)
+    p2_wins = models.PositiveSmallIntegerField(default=0)
+    winner = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, null=True, related_name="won_series")
+    start_date = models.DateTimeField()
+    end_date = models.DateTimeField(null=True)
+
+
 class GameResult(models.Model):
     p1 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="p1")
     p2 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="p2")
@@ -18,6 +30,15 @@ class GameResult(models.Model):
     winner = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="winner")
     date = models.DateTimeField()
     duration = models.DurationField()
+    # 0 : score reached, 1 : withdrawal, 2 : time up, 3 : sudden death
+    win_type = models.PositiveSmallIntegerField(default=0)
+    series = models.ForeignKey(GameSeries, on_delete=models.SET_NULL, null=True, related_name="games")
+
+
+class GameTicket(models.Model):
+    token = models.CharField(max_length=64, unique=True)
+    player = models.ForeignKey(Player, on_delete=models.CASCADE)
+    expire_date = models.DateTimeField()
 
 
 class Relationship(models.Model):
@@ -28,3 +49,51 @@ class Relationship(models.Model):
 class RelationshipRequest(models.Model):
     request_user = models.ForeignKey(Player, on_delete=models.CASCADE, default=1, related_name="request_user")
     received_user = models.ForeignKey(Player, on_delete=models.CASCADE, related_name="received_user")
+
+
+class Tournament(models.Model):
+    entrants = models.PositiveSmallIntegerField()
+    start_date = models.DateTimeField()
+    end_date = models.DateTimeField(null=True)
+    winner = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, null=True, related_name="won_tournaments")
+
+
+class TournamentMatch(models.Model):
+    tournament = models.ForeignKey(Tournament, on_delete=models.CASCADE, related_name="matches")
+    round = models.PositiveSmallIntegerField()
+    position = models.PositiveSmallIntegerField()
+    p1 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, null=True, related_name="tournament_p1")
+    p2 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, null=True, related_name="tournament_p2")
+    winner = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, null=True, related_name="tournament_winner")
+    result = models.ForeignKey(GameResult, on_delete=models.SET_NULL, null=True)
+
+
+class TournamentStanding(models.Model):
+    tournament = models.ForeignKey(Tournament, on_delete=models.CASCADE, related_name="standings")
+    player = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="tournament_standings")
+    alias = models.CharField(max_length=64)
+    rank = models.PositiveSmallIntegerField()
+
+
+class League(models.Model):
+    name = models.CharField(max_length=64)
+    members = models.ManyToManyField(Player, related_name="leagues")
+    scheduled = models.BooleanField(default=False)
+
+
+class LeagueFixture(models.Model):
+    league = models.ForeignKey(League, on_delete=models.CASCADE, related_name="fixtures")
+    matchday = models.PositiveSmallIntegerField()
+    p1 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="league_p1")
+    p2 = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="league_p2")
+    result = models.ForeignKey(GameResult, on_delete=models.SET_NULL, null=True)
+
+
+class LeagueStanding(models.Model):
+    league = models.ForeignKey(League, on_delete=models.CASCADE, related_name="standings")
+    player = models.ForeignKey(Player, on_delete=models.SET_DEFAULT, default=1, related_name="league_standings")
+    played = models.PositiveSmallIntegerField(default=0)
+    wins = models.PositiveSmallIntegerField(default=0)
+    losses = models.PositiveSmallIntegerField(default=0)
+    points = models.PositiveSmallIntegerField(default=0)
+    point_difference = models.SmallIntegerField(default=0)
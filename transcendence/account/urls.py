@@ -9,4 +9,5 @@ urlpatterns = [
     path("account/delete", views.user_delete, name='delete'),
     path("account/statistics", views.get_user_statistics, name='statistics'),
     path("account/update", views.user_update, name='update'),
+    path("account/game_ticket", views.get_game_ticket, name='game_ticket'),
 ]
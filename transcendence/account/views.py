@@ -1,17 +1,22 @@
 import json
 import os
+import secrets
 import urllib.parse
 import shutil
+from datetime import timedelta
 from django.shortcuts import render
 from django.http import HttpResponse
 from django.conf import settings
 from argon2 import PasswordHasher
 from django.core.files.storage import FileSystemStorage
+from django.utils import timezone
 from navigation.views import remove_unknown_user_cookies
-from account.models import Player, GameResult
+from account.models import Player, GameResult, GameTicket
 
 ph = PasswordHasher()
 
+GAME_TICKET_LIFETIME = timedelta(seconds=30)
+
 
 def create_pfp(path, credentials):
     if not credentials.FILES.get('pfp', False):
@@ -63,6 +68,11 @@ def make_success_response(player):
     return response
 
 
+def log_in(request, player):
+    request.session.cycle_key()
+    request.session['player_id'] = player.id
+
+
 def valid_username(username, username_type):
     if len(username) >= 64:
         return f"1{username_type} too long. Please remove characters."
@@ -85,12 +95,15 @@ def signup_handler(credentials):
             return HttpResponse("1Username contains invalid characters. Try removing slashes.")
         create_pfp(path, credentials)
         Player.objects.create(username=username, password=hashed_password)
-        return make_success_response(Player.objects.get(username=username))
+        player = Player.objects.get(username=username)
+        log_in(credentials, player)
+        return make_success_response(player)
     else:
         return HttpResponse("1Username is taken.")
 
 
-def signin_handler(player, _):
+def signin_handler(player, credentials):
+    log_in(credentials, player)
     return make_success_response(player)
 
 
@@ -170,6 +183,7 @@ def user_logout(request):
     response.delete_cookie('username', samesite='Lax')
     response.delete_cookie('pfp_uri', samesite='Lax')
     response.delete_cookie('tournament_username', samesite='Lax')
+    request.session.flush()
     return response
 
 
@@ -231,3 +245,16 @@ def get_user_statistics(request):
         if len(all_result) == 0:
             all_result.append(info)
     return HttpResponse(json.dumps(all_result))
+
+
+def get_game_ticket(request):
+    if request.method != 'POST':
+        return remove_unknown_user_cookies(request, render(request, 'base.html'))
+    player_id = request.session.get('player_id')
+    if player_id is None or Player.objects.filter(id=player_id).count() == 0:
+        return HttpResponse('1Please sign in to play online.')
+    now = timezone.now()
+    GameTicket.objects.filter(expire_date__lt=now).delete()
+    ticket = GameTicket.objects.create(token=secrets.token_urlsafe(32), player_id=player_id,
+                                       expire_date=now + GAME_TICKET_LIFETIME)
+    return HttpResponse(f'0{ticket.token}')
@@ -11,7 +11,11 @@ python3 manage.py makemigrations account
 python3 manage.py migrate
 python3 manage.py collectstatic
 
-psql -U 'transcendence' -d 'transcendence' -W -h postgres -p 5432 -c "INSERT INTO account_player(username, password, tournament_username) VALUES ('deleted', '\x00', 'deleted');" <<-EOSQL
+psql -U 'transcendence' -d 'transcendence' -W -h postgres -p 5432 -c "
+INSERT INTO account_player(username, password, tournament_username)
+  SELECT 'deleted', '\x00', 'deleted' WHERE NOT EXISTS (SELECT FROM account_player WHERE username = 'deleted');
+INSERT INTO account_player(username, password, tournament_username)
+  SELECT 'AI', '\x00', 'AI' WHERE NOT EXISTS (SELECT FROM account_player WHERE username = 'AI');" <<-EOSQL
 ${POSTGRES_TRANSCENDENCE_PASSWORD}
 EOSQL
 
@@ -1,11 +1,27 @@
 import { Encoder } from "../encode_cbor.js";
 import { Decoder } from "../decode_cbor.js";
-import { get_username_from_cookie } from "../cookie_getter.js";
+import { get_csrf_token } from "../cookie_getter.js";
 import { update_value_manager } from "./game_loop.js";
-import { init_game, register_in_queue, show_tournament_score } from "./game_engine.js";
+import {
+    init_game,
+    register_in_queue,
+    set_game_start_status,
+    show_match_found,
+    show_queue_status,
+    show_tournament_score
+} from "./game_engine.js";
 import { create_element } from "../lib.js";
 
 const MODE_MULTY = 0;
+const GAME_STARTING = 0;
+const QUEUE_STATUS = 15;
+const MATCH_FOUND = 16;
+const BACK_IN_QUEUE = 17;
+
+// What the next message of the server is, in the order they come in.
+const START_MESSAGE = 0;
+const START_STATUS_MESSAGE = 1;
+const GAME_MESSAGE = 2;
 
 let socket = null;
 
@@ -36,6 +52,28 @@ const close_listener = () => {
         socket = null;
 }
 
+const handle_queue_message = (value) => {
+    const msg_id = value.shift();
+    if (msg_id === QUEUE_STATUS)
+        show_queue_status(value[0], value[1], value[2]);
+    else if (msg_id === MATCH_FOUND)
+        show_match_found(value[0]);
+    else if (msg_id === BACK_IN_QUEUE)
+        register_in_queue();
+}
+
+const request_game_ticket = () => new Promise((resolve) => {
+    let xhr = new XMLHttpRequest();
+    xhr.open('POST', 'account/game_ticket', true);
+    xhr.setRequestHeader("X-CSRFToken", get_csrf_token());
+    xhr.onreadystatechange = () => {
+        if (xhr.readyState !== XMLHttpRequest.DONE)
+            return;
+        resolve(xhr.responseText[0] === '0' ? xhr.responseText.substring(1) : null);
+    };
+    xhr.send(null);
+});
+
 export const establish_connexion = (game_mode, is_PvE = null, tournament_players_username = null) => {
     if (socket !== null)
         return;
@@ -46,15 +84,32 @@ export const establish_connexion = (game_mode, is_PvE = null, tournament_players
     let decoder = new Decoder;
 
     socket.addEventListener("open", async (_) => {
-        send_information([3, get_username_from_cookie(), game_mode]);
+        const ticket = await request_game_ticket();
+        if (ticket === null) {
+            unexpected_quit();
+            return;
+        }
+        const no_parameters = new Encoder().encode([]);
+        send_information([20, ticket, game_mode, no_parameters]);
     });
 
     socket.addEventListener("close", close_listener);
 
+    let expected_message = START_MESSAGE;
     socket.addEventListener("message", async (event) => {
-        socket.addEventListener("message", async (event) => {
-            await update_value_manager(decoder.decode(new Uint8Array(event.data)));
-        });
-        await init_game(game_mode, decoder.decode(new Uint8Array(event.data)), is_PvE, tournament_players_username);
-    }, {once: true});
+        const value = decoder.decode(new Uint8Array(event.data));
+        if (expected_message === GAME_MESSAGE)
+            await update_value_manager(value);
+        else if (expected_message === START_STATUS_MESSAGE) {
+            // The game is aborted when the opponent left during the countdown, and the client is back in queue.
+            expected_message = value[0] === GAME_STARTING ? GAME_MESSAGE : START_MESSAGE;
+            set_game_start_status(value[0]);
+        } else if (game_mode === MODE_MULTY && typeof value[0] === 'number')
+            // Unlike the game start message, the messages of the queue start with their id.
+            handle_queue_message(value);
+        else {
+            expected_message = game_mode === MODE_MULTY ? START_STATUS_MESSAGE : GAME_MESSAGE;
+            await init_game(game_mode, value, is_PvE, tournament_players_username);
+        }
+    });
 }
\ No newline at end of file
@@ -8,12 +8,13 @@ import {
     render_wall
 } from "./rendering.js";
 import {
+    cancel_game_start,
     init_game_info,
     is_game_run,
     launch_game,
     need_abort_game, set_is_tournament_to_true
 } from "./game_loop.js";
-import {close_connexion, establish_connexion, unexpected_quit} from "./communication.js";
+import {close_connexion, establish_connexion, send_information, unexpected_quit} from "./communication.js";
 import {get_tournament_username_from_cookie, get_username_from_cookie} from "../cookie_getter.js";
 import { navigate_to } from "../router.js";
 
@@ -25,9 +26,16 @@ export const BALL_EDGE = 0.017;
 export const BALL_RADIUS = BALL_EDGE / 2.0;
 export const PAD_WIDTH = 0.015;
 export const PAD_HEIGHT = 0.100;
+/// The height of the pads in the running game, given by its rules.
+export let pad_height = PAD_HEIGHT;
+
+const GAME_STARTING = 0;
+const GAME_ABORTED = 1;
 
 let in_queue = false
 let tournament_players_info;
+/// Null until the server tells whether the remote game effectively starts.
+let game_start_status = null;
 const make_ball_struct = () => {
     return {
         x: 0.5 * RATIO,
@@ -49,11 +57,43 @@ const unregister_multi = async () => {
 
 export const register_in_queue = () => {
     delete_app_element();
-    create_element('div', 'body', 'in queue', 'queue');
+    if (!document.getElementById('queue'))
+        create_element('div', 'body', 'in queue', 'queue');
     create_button('app', 'unregister', async () => unregister_multi(), 'unregister');
     in_queue = true;
 }
 
+export const show_queue_status = (waited, players, search_range) => {
+    if (document.getElementById('queue'))
+        document.getElementById('queue').innerText =
+            'in queue for ' + waited + 's, ' + players + ' players waiting, rating range ' + search_range;
+}
+
+const answer_match_found = async (ready) => {
+    send_information([ready]);
+    delete_app_element();
+    if (ready) {
+        create_element('p', 'app', 'waiting for the opponent to be ready', 'match_found');
+        return;
+    }
+    close_connexion();
+    in_queue = false;
+    if (document.getElementById('queue'))
+        document.getElementById('queue').remove();
+    await choose_mode();
+}
+
+export const show_match_found = (opponent_username) => {
+    delete_app_element();
+    create_element('p', 'app', 'match found against ' + opponent_username, 'match_found');
+    create_button('app', 'ready', () => answer_match_found(true), 'ready_button').classList.add("play_button");
+    create_button('app', 'decline', () => answer_match_found(false), 'decline_button').classList.add("play_button");
+}
+
+export const set_game_start_status = (status) => {
+    game_start_status = status;
+}
+
 export let handle_running_game = () => {
     // implement for local when it's finish and if it keeps
     if (is_game_run === true) {
@@ -82,7 +122,7 @@ export const render_elements = (left_wall, right_wall, ball, left_point, right_p
 const starting_screen = async (starting_time, timer) => {
     timer.classList.add("starting_timer")
     let time = Date.now();
-    while (time < starting_time) {
+    while ((time < starting_time || game_start_status === null) && game_start_status !== GAME_ABORTED) {
         time = Date.now();
         let diff = Number(BigInt(starting_time) - BigInt(time));
         timer.innerHTML = Math.ceil(diff / 1000).toString();
@@ -104,15 +144,20 @@ const init_element = () => {
 
 export const init_game = async (game_mode, value, is_PvE, tournament_players_username) => {
     let starting_time;
+    game_start_status = game_mode === 0 ? null : GAME_STARTING;
     if (game_mode === 0) {
         if (location.pathname !== '/play')
             await navigate_to('/play');
         in_queue = false;
+        if (document.getElementById('queue'))
+            document.getElementById('queue').remove();
     }
     delete_app_element()
     create_element('p', 'app', 0 + '', 'left_score');
     create_element('p', 'app', 0 + '', 'right_score');
     render_target = make_render_target();
+    // The game start message ends with the rules : target score, win by two, ball speed and pad height.
+    pad_height = value[value.length - 1][3];
     let [left_wall, right_wall, ball] = init_element();
     starting_time = await init_game_info(game_mode, value, [left_wall, right_wall, ball], is_PvE, tournament_players_username);
     if (!starting_time)
@@ -120,6 +165,11 @@ export const init_game = async (game_mode, value, is_PvE, tournament_players_use
     await starting_screen(starting_time, create_element('p', 'app', null, 'start_timer'));
     if (document.getElementById('start_timer'))
         document.getElementById('start_timer').remove();
+    if (game_start_status !== GAME_STARTING) {
+        cancel_game_start();
+        register_in_queue();
+        return;
+    }
     await launch_game();
 }
 
@@ -1,5 +1,5 @@
-import { render_elements, tournament_algorithm, tournament_game_result } from "./game_engine.js";
-import { l_down, l_up, r_down, r_up, reset_player_direction, set_player_direction } from "./wall.js";
+import { pad_height, render_elements, tournament_algorithm, tournament_game_result } from "./game_engine.js";
+import { l_down, l_up, r_down, r_up, reset_player_direction, serve, set_player_direction } from "./wall.js";
 import { create_element, sleep } from "../lib.js";
 import { close_connexion, send_information } from "./communication.js";
 import { say_game_done_to_friends, say_in_game_to_friends } from "../account/friends_communication.js";
@@ -10,6 +10,8 @@ const RIGHT_WIN = 1
 const WIN_BY_WITHDRAWAL = 2
 const UNEXPECTED_DISCONNECTION = -2
 const MODE_MULTi = 0;
+const LEFT_SIDE = 0;
+const RIGHT_SIDE = 1;
 
 let previous_actualise_bot = 0;
 let is_multi = -1;
@@ -27,6 +29,8 @@ let next_pos_bot = 0.5
 let nb_frame_between_bot_render = 0;
 let is_tournament = false;
 let tournament_players_username = null;
+let serving_side = -1;
+let serve_deadline = 0;
 
 export let is_game_run = false;
 
@@ -38,6 +42,11 @@ export const need_abort_game = () => {
     is_game_aborted = true;
 }
 
+export const cancel_game_start = () => {
+    is_game_run = false;
+    say_game_done_to_friends();
+}
+
 const update_position = (value) => {
     if (is_bot_game) {
         nb_frame_between_bot_render++;
@@ -85,6 +94,34 @@ const update_game_done = (side) => {
         win_side = close_connexion();
 }
 
+const update_serve = (value) => {
+    serving_side = value[0];
+    serve_deadline = Number(value[1]);
+    if (can_serve() && !document.getElementById('serve_prompt'))
+        create_element('p', 'app', 'press space to serve', 'serve_prompt');
+}
+
+const can_serve = () => {
+    if (is_multi === MODE_MULTi)
+        return serving_side === own_side;
+    return !is_bot_game || serving_side === LEFT_SIDE;
+}
+
+const send_serve = () => {
+    if (serving_side === -1)
+        return;
+    // The bot serves right away. Past the deadline, the server served the ball itself.
+    const is_served = (is_bot_game && serving_side === RIGHT_SIDE) || (serve === 1 && can_serve());
+    const is_expired = Date.now() >= serve_deadline;
+    if (!is_served && !is_expired)
+        return;
+    if (!is_expired)
+        send_information([]);
+    serving_side = -1;
+    if (document.getElementById('serve_prompt'))
+        document.getElementById('serve_prompt').remove();
+}
+
 export const update_value_manager = (value) => {
     if (value === null) {
         win_side = close_connexion();
@@ -99,6 +136,8 @@ export const update_value_manager = (value) => {
         update_game_done(value);
     else if (msg_id === 3)
         win_side = WIN_BY_WITHDRAWAL;
+    else if (msg_id === 19)
+        update_serve(value);
     else
         win_side = close_connexion();
 }
@@ -116,9 +155,9 @@ const send_input = (previous_input) => {
         if (is_bot_game) {
             next_input = [0, 0];
             next_input[0] = (r_down === 1 ? r_down : l_down) - (r_up === 1 ? r_up : l_up);
-            if (next_pos_bot === null || Math.abs(- next_pos_bot + right_wall.y + 0.05) < 0.025)
+            if (next_pos_bot === null || Math.abs(- next_pos_bot + right_wall.y + pad_height / 2) < 0.025)
                 next_input[1] = 0;
-            else if (0 > - next_pos_bot + right_wall.y + 0.05)
+            else if (0 > - next_pos_bot + right_wall.y + pad_height / 2)
                 next_input[1] = 1;
             else
                 next_input[1] = -1;
@@ -132,6 +171,7 @@ const send_input = (previous_input) => {
 
 export const game_loop = async (time) => {
     previous_input = send_input(previous_input);
+    send_serve();
     render_elements(left_wall, right_wall, ball, left_point, right_point);
     if (win_side === -1 && !is_game_aborted) {
         window.requestAnimationFrame(game_loop);
@@ -191,6 +231,7 @@ export const init_game_info = async (game_mode, value, element, is_PvE, get_tour
     right_wall = element[1];
     ball = element[2];
     is_multi = game_mode;
+    serving_side = -1;
     if (game_mode === MODE_MULTi) {
         create_element('p', 'app', "opponent: " + value[0], 'players_username');
         own_side = value[1];
@@ -199,5 +240,5 @@ export const init_game_info = async (game_mode, value, element, is_PvE, get_tour
     else if (tournament_players_username != null) {
         create_element('p', 'app', tournament_players_username[0] + " VS " + tournament_players_username[1], 'players_username');
     }
-    return value[1];
+    return value[0];
 }
@@ -1,5 +1,5 @@
 import { set_player_direction, reset_player_direction } from "./wall.js"
-import { BALL_EDGE, PAD_HEIGHT, PAD_WIDTH, RATIO } from "./game_engine.js";
+import { BALL_EDGE, PAD_WIDTH, RATIO, pad_height } from "./game_engine.js";
 import { create_element } from "../lib.js";
 
 export const render_ball = (ball, render_target, element) => {
@@ -17,7 +17,7 @@ export const render_wall = (wall, render_target, element) => {
     element.style.left = (render_target.x + (wall.x / RATIO * render_target.width)) + 'px';
     element.style.top = (render_target.y + (wall.y * render_target.height)) + 'px';
     element.style.width = (PAD_WIDTH * render_target.height) + 'px';
-    element.style.height = (PAD_HEIGHT * render_target.height) + 'px';
+    element.style.height = (pad_height * render_target.height) + 'px';
 }
 
 export const create_ball = (game_element) => {